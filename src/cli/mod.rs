@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -8,6 +8,16 @@ pub struct Cli {
     #[arg(long)]
     pub tools_dir: Option<PathBuf>,
 
+    /// Lock file of reviewed module hashes: modules in the tools directory that
+    /// changed since they were locked aren't loaded or run. Create or update it
+    /// with the `lock-tools` subcommand.
+    #[arg(long)]
+    pub tools_lock: Option<PathBuf>,
+
+    /// Only log a warning for modules that don't match --tools-lock
+    #[arg(long, default_value_t = false)]
+    pub tools_lock_warn: bool,
+
     /// Enable the default `run` tool when using tools-dir
     #[arg(long, default_value_t = false)]
     pub enable_run_nu: bool,
@@ -16,6 +26,130 @@ pub struct Cli {
     /// The current working directory is always accessible. This adds additional paths.
     #[arg(long = "add-path")]
     pub add_paths: Vec<PathBuf>,
+
+    /// Add read-only paths (can be specified multiple times)
+    /// Commands can read files there, but not create, modify or delete them.
+    #[arg(long = "add-path-ro")]
+    pub add_paths_ro: Vec<PathBuf>,
+
+    /// Deny access to paths matching a glob, even inside the sandbox (can be specified
+    /// multiple times). Patterns without `/` match a name at any depth, e.g. `*.sqlite`.
+    #[arg(long = "deny-path")]
+    pub deny_paths: Vec<String>,
+
+    /// Don't deny the default credential files (`.env`, `.ssh`, `.aws`, `id_rsa*`, ...)
+    #[arg(long, default_value_t = false)]
+    pub no_default_deny: bool,
+
+    /// Let commands modify the server's own files: the tools directory, the policy,
+    /// environment policy and safe pattern files, and the audit log. By default
+    /// they are read-only, even inside the sandbox.
+    #[arg(long, default_value_t = false)]
+    pub no_protect_server_files: bool,
+
+    /// Don't redact the built-in secret formats (GitHub/GitLab tokens, AWS keys,
+    /// JWTs, private keys) from tool output
+    #[arg(long, default_value_t = false)]
+    pub no_default_redact: bool,
+
+    /// Redact matches of this regex from tool output (can be specified multiple times).
+    /// A `secret` capture group limits the replacement to that part of the match.
+    #[arg(long = "redact-pattern")]
+    pub redact_patterns: Vec<String>,
+
+    /// Redact the value of this environment variable from tool output (can be
+    /// specified multiple times)
+    #[arg(long = "redact-env")]
+    pub redact_env: Vec<String>,
+
+    /// Only let `http` commands, `curl` and `wget` reach hosts matching these rules
+    /// (can be specified multiple times): `api.github.com`, `*.example.com`,
+    /// `10.0.0.0/8`, or with a scheme, `https://api.github.com`
+    #[arg(long = "allow-host")]
+    pub allow_hosts: Vec<String>,
+
+    /// File of extra safe command patterns, one regex per line, whose matching
+    /// commands bypass path validation (can be specified multiple times)
+    #[arg(long = "safe-patterns-file")]
+    pub safe_patterns_files: Vec<PathBuf>,
+
+    /// Use only the patterns from --safe-patterns-file, not the built-in ones
+    #[arg(long, default_value_t = false)]
+    pub replace_safe_patterns: bool,
+
+    /// Print the safe command patterns in effect, with where each comes from, and exit
+    #[arg(long, default_value_t = false)]
+    pub list_safe_patterns: bool,
+
+    /// JSON file with allow/deny/ask rules for command names, subcommands and flags
+    #[arg(long)]
+    pub policy_file: Option<PathBuf>,
+
+    /// JSON file choosing which environment variables `run`, `shell` and each
+    /// extension module pass to their processes (all of them by default)
+    #[arg(long)]
+    pub env_policy_file: Option<PathBuf>,
+
+    /// Enforce the sandbox in the kernel with Landlock (Linux 5.13+): spawned `nu`
+    /// processes can only open files in the sandbox, tool and system directories.
+    /// Ignored with a warning where Landlock isn't available.
+    #[arg(long, default_value_t = false)]
+    pub landlock: bool,
+
+    /// Stop a command after this many seconds of CPU time
+    #[arg(long)]
+    pub limit_cpu: Option<u64>,
+
+    /// Limit a command's address space (virtual memory) to this many megabytes
+    #[arg(long)]
+    pub limit_memory_mb: Option<u64>,
+
+    /// Limit the number of processes (counted across all of the user's processes)
+    #[arg(long)]
+    pub limit_processes: Option<u64>,
+
+    /// Limit the number of files a command can have open
+    #[arg(long)]
+    pub limit_open_files: Option<u64>,
+
+    /// Limit the size of files a command writes to this many megabytes
+    #[arg(long)]
+    pub limit_file_size_mb: Option<u64>,
+
+    /// Queue up to N concurrent `shell` calls while the shell is busy, running them in
+    /// arrival order. 0 rejects concurrent calls with a "Shell is busy" error.
+    #[arg(long, default_value_t = 0)]
+    pub shell_queue_depth: usize,
+
+    /// Seconds a queued `shell` call waits for its turn before failing
+    #[arg(long, default_value_t = 60)]
+    pub shell_queue_timeout: u64,
+
+    /// Append a JSON Lines record of every tool call to this file
+    #[arg(long)]
+    pub audit_log: Option<PathBuf>,
+
+    /// Rotate the audit log when it reaches this many megabytes, keeping 5 old files.
+    /// 0 never rotates.
+    #[arg(long, default_value_t = 10)]
+    pub audit_log_max_mb: u64,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Explain what the sandbox would decide about a command, without running it.
+    /// Uses the sandbox, policy and network options given before the subcommand.
+    /// Exits with status 1 unless the command would be allowed.
+    CheckCommand {
+        /// The Nushell command to check
+        command: String,
+    },
+    /// Record the hashes of the modules in --tools-dir in --tools-lock, after
+    /// reviewing them. Prints the modules that were added, changed or removed.
+    LockTools,
 }
 
 #[cfg(test)]
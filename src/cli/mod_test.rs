@@ -167,3 +167,247 @@ fn test_invalid_flag_fails() {
 
     assert!(result.is_err());
 }
+
+#[test]
+fn test_cli_shell_queue_defaults() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse default args");
+
+    assert_eq!(cli.shell_queue_depth, 0);
+    assert_eq!(cli.shell_queue_timeout, 60);
+}
+
+#[test]
+fn test_cli_shell_queue_flags() {
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--shell-queue-depth",
+        "4",
+        "--shell-queue-timeout",
+        "120",
+    ])
+    .expect("Failed to parse shell queue flags");
+
+    assert_eq!(cli.shell_queue_depth, 4);
+    assert_eq!(cli.shell_queue_timeout, 120);
+}
+
+#[test]
+fn test_cli_add_path_ro() {
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--add-path=/tmp",
+        "--add-path-ro=/nix/store",
+        "--add-path-ro",
+        "/opt/reference",
+    ])
+    .expect("Failed to parse read-only paths");
+
+    assert_eq!(cli.add_paths, vec![PathBuf::from("/tmp")]);
+    assert_eq!(
+        cli.add_paths_ro,
+        vec![PathBuf::from("/nix/store"), PathBuf::from("/opt/reference")]
+    );
+}
+
+#[test]
+fn test_cli_deny_paths() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(cli.deny_paths.is_empty());
+    assert!(!cli.no_default_deny);
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--deny-path=*.sqlite",
+        "--deny-path",
+        "/secrets",
+        "--no-default-deny",
+    ])
+    .expect("Failed to parse deny flags");
+
+    assert_eq!(cli.deny_paths, vec!["*.sqlite", "/secrets"]);
+    assert!(cli.no_default_deny);
+}
+
+#[test]
+fn test_cli_policy_file() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert_eq!(cli.policy_file, None);
+
+    let cli = super::Cli::try_parse_from(["nu-mcp", "--policy-file=policy.json"])
+        .expect("Failed to parse policy file");
+    assert_eq!(cli.policy_file, Some(PathBuf::from("policy.json")));
+}
+
+#[test]
+fn test_cli_audit_log() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert_eq!(cli.audit_log, None);
+    assert_eq!(cli.audit_log_max_mb, 10);
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--audit-log=/var/log/nu-mcp.jsonl",
+        "--audit-log-max-mb",
+        "0",
+    ])
+    .expect("Failed to parse audit log flags");
+    assert_eq!(cli.audit_log, Some(PathBuf::from("/var/log/nu-mcp.jsonl")));
+    assert_eq!(cli.audit_log_max_mb, 0);
+}
+
+#[test]
+fn test_cli_landlock() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(!cli.landlock);
+
+    let cli =
+        super::Cli::try_parse_from(["nu-mcp", "--landlock"]).expect("Failed to parse --landlock");
+    assert!(cli.landlock);
+}
+
+#[test]
+fn test_cli_resource_limits() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert_eq!(cli.limit_cpu, None);
+    assert_eq!(cli.limit_memory_mb, None);
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--limit-cpu=60",
+        "--limit-memory-mb=2048",
+        "--limit-processes=256",
+        "--limit-open-files=1024",
+        "--limit-file-size-mb=100",
+    ])
+    .expect("Failed to parse limit flags");
+    assert_eq!(cli.limit_cpu, Some(60));
+    assert_eq!(cli.limit_memory_mb, Some(2048));
+    assert_eq!(cli.limit_processes, Some(256));
+    assert_eq!(cli.limit_open_files, Some(1024));
+    assert_eq!(cli.limit_file_size_mb, Some(100));
+}
+
+#[test]
+fn test_cli_env_policy_file() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert_eq!(cli.env_policy_file, None);
+
+    let cli = super::Cli::try_parse_from(["nu-mcp", "--env-policy-file", "env.json"])
+        .expect("Failed to parse --env-policy-file");
+    assert_eq!(cli.env_policy_file, Some(PathBuf::from("env.json")));
+}
+
+#[test]
+fn test_cli_redaction() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(!cli.no_default_redact);
+    assert!(cli.redact_patterns.is_empty());
+    assert!(cli.redact_env.is_empty());
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--no-default-redact",
+        "--redact-pattern",
+        "sk-[a-z0-9]+",
+        "--redact-env",
+        "DB_PASSWORD",
+        "--redact-env",
+        "API_KEY",
+    ])
+    .expect("Failed to parse redaction flags");
+    assert!(cli.no_default_redact);
+    assert_eq!(cli.redact_patterns, vec!["sk-[a-z0-9]+"]);
+    assert_eq!(cli.redact_env, vec!["DB_PASSWORD", "API_KEY"]);
+}
+
+#[test]
+fn test_cli_allow_host() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(cli.allow_hosts.is_empty());
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--allow-host",
+        "api.github.com",
+        "--allow-host",
+        "10.0.0.0/8",
+    ])
+    .expect("Failed to parse --allow-host");
+    assert_eq!(cli.allow_hosts, vec!["api.github.com", "10.0.0.0/8"]);
+}
+
+#[test]
+fn test_cli_check_command() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(cli.command.is_none());
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--add-path",
+        "/tmp",
+        "check-command",
+        "kubectl get --raw /metrics",
+    ])
+    .expect("Failed to parse check-command");
+    assert_eq!(cli.add_paths, vec![PathBuf::from("/tmp")]);
+    assert!(matches!(
+        cli.command,
+        Some(super::Command::CheckCommand { command }) if command == "kubectl get --raw /metrics"
+    ));
+}
+
+#[test]
+fn test_cli_safe_patterns() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(cli.safe_patterns_files.is_empty());
+    assert!(!cli.replace_safe_patterns);
+    assert!(!cli.list_safe_patterns);
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--safe-patterns-file",
+        "internal.txt",
+        "--safe-patterns-file",
+        "team.txt",
+        "--replace-safe-patterns",
+        "--list-safe-patterns",
+    ])
+    .expect("Failed to parse safe pattern flags");
+    assert_eq!(
+        cli.safe_patterns_files,
+        vec![PathBuf::from("internal.txt"), PathBuf::from("team.txt")]
+    );
+    assert!(cli.replace_safe_patterns);
+    assert!(cli.list_safe_patterns);
+}
+
+#[test]
+fn test_cli_no_protect_server_files() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(!cli.no_protect_server_files);
+
+    let cli = super::Cli::try_parse_from(["nu-mcp", "--no-protect-server-files"])
+        .expect("Failed to parse --no-protect-server-files");
+    assert!(cli.no_protect_server_files);
+}
+
+#[test]
+fn test_cli_tools_lock() {
+    let cli = super::Cli::try_parse_from(["nu-mcp"]).expect("Failed to parse empty args");
+    assert!(cli.tools_lock.is_none());
+    assert!(!cli.tools_lock_warn);
+
+    let cli = super::Cli::try_parse_from([
+        "nu-mcp",
+        "--tools-dir",
+        "tools",
+        "--tools-lock",
+        "tools.lock.json",
+        "--tools-lock-warn",
+        "lock-tools",
+    ])
+    .expect("Failed to parse lock-tools");
+    assert_eq!(cli.tools_lock, Some(PathBuf::from("tools.lock.json")));
+    assert!(cli.tools_lock_warn);
+    assert!(matches!(cli.command, Some(super::Command::LockTools)));
+}
@@ -1,10 +1,44 @@
+use crate::execution::{EnvPolicies, ResourceLimits};
+use crate::security::{DenyList, EgressAllowlist, Policy, Redactor, SafePatterns};
+use crate::tools::ModuleLock;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Default)]
 pub struct Config {
     pub tools_dir: Option<PathBuf>,
+    /// Hashes extension modules must match to be loaded and run (any module by default)
+    pub module_lock: ModuleLock,
     pub enable_run_nu: bool,
     pub sandbox_directories: Vec<PathBuf>,
+    /// Subset of `sandbox_directories` that commands may read but not modify
+    pub read_only_directories: Vec<PathBuf>,
+    /// Server files commands may not modify, even inside the sandbox: the tools
+    /// directory, config files and audit log (none by default)
+    pub protected_paths: Vec<PathBuf>,
+    /// Paths inside the sandbox that commands may not access (empty by default)
+    pub deny_list: DenyList,
+    /// Command segments that bypass path validation (the built-in patterns by default)
+    pub safe_patterns: SafePatterns,
+    /// Allow/deny/ask rules for the commands a call runs (no rules by default)
+    pub policy: Policy,
+    /// Hosts HTTP commands may reach (any host by default)
+    pub egress: EgressAllowlist,
+    /// Secrets replaced with placeholders in tool output (none by default)
+    pub redactor: Redactor,
+    /// Max concurrent `shell` calls waiting for the busy shell (0 = reject when busy)
+    pub shell_queue_depth: usize,
+    /// Seconds a queued `shell` call waits for its turn before failing
+    pub shell_queue_timeout_secs: u64,
+    /// Confine spawned `nu` processes to the sandbox with Landlock (Linux)
+    pub landlock: bool,
+    /// rlimits for every spawned command (none by default)
+    pub limits: ResourceLimits,
+    /// Environment for each executor and extension module (inherit all by default)
+    pub env: EnvPolicies,
+    /// JSON Lines file recording every tool call (no audit log by default)
+    pub audit_log: Option<PathBuf>,
+    /// Size at which the audit log is rotated (0 = never rotate)
+    pub audit_log_max_bytes: u64,
 }
 
 #[cfg(test)]
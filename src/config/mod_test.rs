@@ -9,6 +9,7 @@ fn test_config_creation_default() {
         tools_dir: None,
         enable_run_nu: false,
         sandbox_directories: vec![],
+        ..Default::default()
     };
 
     assert!(config.tools_dir.is_none());
@@ -24,6 +25,7 @@ fn test_config_creation_tools_dir() {
         tools_dir: Some(tools_path.clone()),
         enable_run_nu: false,
         sandbox_directories: vec![],
+        ..Default::default()
     };
 
     assert_eq!(config.tools_dir, Some(tools_path));
@@ -35,6 +37,7 @@ fn test_config_creation_enable_run() {
         tools_dir: None,
         enable_run_nu: true,
         sandbox_directories: vec![],
+        ..Default::default()
     };
 
     assert!(config.enable_run_nu);
@@ -48,6 +51,7 @@ fn test_config_creation_sandbox_directories() {
         tools_dir: None,
         enable_run_nu: false,
         sandbox_directories: vec![sandbox_path.clone()],
+        ..Default::default()
     };
 
     assert_eq!(config.sandbox_directories, vec![sandbox_path]);
@@ -63,6 +67,7 @@ fn test_config_creation_full_configuration() {
         tools_dir: Some(tools_path.clone()),
         enable_run_nu: true,
         sandbox_directories: vec![sandbox1.clone(), sandbox2.clone()],
+        ..Default::default()
     };
 
     assert_eq!(config.tools_dir, Some(tools_path));
@@ -0,0 +1,104 @@
+//! Confinement applied to the processes executors start
+
+use super::{EnvPolicy, ResourceLimits};
+use crate::security::Landlock;
+
+/// How child processes are confined. The default applies nothing.
+#[derive(Debug, Clone, Default)]
+pub struct ChildConfig {
+    /// Kernel filesystem sandbox, when enabled and supported
+    pub landlock: Option<Landlock>,
+    /// rlimits for CPU, memory, processes, open files and file size
+    pub limits: ResourceLimits,
+    /// Environment variables the child gets (the server's whole environment by default)
+    pub env: EnvPolicy,
+}
+
+impl ChildConfig {
+    /// The same confinement with a different environment
+    pub fn with_env(&self, env: EnvPolicy) -> Self {
+        Self {
+            env,
+            ..self.clone()
+        }
+    }
+
+    /// Confine `command` when it is spawned
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        if !self.env.is_default() {
+            self.env.apply(command);
+        }
+        #[cfg(unix)]
+        if !self.limits.is_empty() {
+            let limits = self.limits;
+            // SAFETY: apply only makes system calls
+            unsafe {
+                command.pre_exec(move || limits.apply());
+            }
+        }
+        #[cfg(unix)]
+        if let Some(landlock) = self.landlock.clone() {
+            // SAFETY: restrict_self only makes system calls, which is all a
+            // pre-exec hook may do between fork and exec
+            unsafe {
+                command.pre_exec(move || landlock.restrict_self());
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = command;
+    }
+
+    /// Set the environment of a PTY command, which `apply` can't take
+    pub fn apply_env_to_pty(&self, command: &mut portable_pty::CommandBuilder) {
+        if !self.env.is_default() {
+            self.env.apply_to_pty(command);
+        }
+    }
+
+    /// Run `spawn` on a new thread confined the way a child would be, for
+    /// spawners without a pre-exec hook (the PTY). Processes started by
+    /// `spawn` inherit the thread's confinement; the server's other threads
+    /// are not affected.
+    pub fn spawn_confined<T: Send + 'static>(
+        &self,
+        spawn: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, String> {
+        let Some(landlock) = self.landlock.clone() else {
+            return Ok(spawn());
+        };
+        std::thread::spawn(move || {
+            landlock
+                .restrict_self()
+                .map_err(|e| format!("Failed to apply Landlock rules: {}", e))?;
+            Ok(spawn())
+        })
+        .join()
+        .map_err(|_| "Spawner thread panicked".to_string())?
+    }
+
+    /// Set the resource limits on a process that was spawned without a
+    /// pre-exec hook (the PTY). Where that isn't supported, the process runs
+    /// without limits and a warning is logged.
+    pub fn limit_process(&self, pid: u32) -> Result<(), String> {
+        if self.limits.is_empty() {
+            return Ok(());
+        }
+        match self.limits.apply_to(pid) {
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+                tracing::warn!(
+                    "Resource limits can't be set on the persistent shell on this platform; it runs without them"
+                );
+                Ok(())
+            }
+            result => result
+                .map_err(|e| format!("Failed to set resource limits on process {}: {}", pid, e)),
+        }
+    }
+
+    /// Restart the CPU limit of a long-lived process before its next command
+    pub fn limit_next_command(&self, pid: u32) -> Result<(), String> {
+        self.limits
+            .extend_cpu_limit(pid)
+            .map_err(|e| format!("Failed to reset the CPU limit of process {}: {}", pid, e))
+    }
+}
@@ -0,0 +1,262 @@
+//! Environment variables passed to the processes executors start
+//!
+//! By default every child inherits the server's environment, including
+//! tokens the MCP client set for other purposes. An env policy limits what
+//! each executor and each extension module sees. It is loaded from a JSON
+//! file at startup:
+//!
+//! ```json
+//! {
+//!   "run": { "mode": "allowlist", "vars": ["HOME", "LANG", "LC_*"] },
+//!   "shell": { "mode": "denylist", "vars": ["GITHUB_TOKEN", "*_SECRET"] },
+//!   "tools": { "mode": "allowlist", "vars": ["HOME"] },
+//!   "modules": {
+//!     "k8s": { "mode": "allowlist", "vars": ["HOME", "KUBECONFIG"],
+//!              "set": { "KUBE_EDITOR": "true" } }
+//!   }
+//! }
+//! ```
+//!
+//! `run` and `shell` cover the two executors, `tools` every extension tool,
+//! and `modules` replaces `tools` for the named module directory. Missing
+//! entries inherit everything. A `*` at the start or end of a name matches
+//! any prefix or suffix. `PATH` is always kept by allowlists so `nu` can find
+//! externals; `set` values are added last and win over everything else.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Variables an allowlist keeps even when they aren't listed
+const ALWAYS_INHERITED: &[&str] = &["PATH"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvMode {
+    /// Pass the server's whole environment
+    #[default]
+    Inherit,
+    /// Pass only the listed variables
+    Allowlist,
+    /// Pass everything except the listed variables
+    Denylist,
+}
+
+/// The environment for one kind of child process. The default inherits
+/// everything and sets nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvPolicy {
+    #[serde(default)]
+    pub mode: EnvMode,
+    /// Names for the allowlist or denylist
+    #[serde(default)]
+    pub vars: Vec<String>,
+    /// Values set in the child regardless of the mode
+    #[serde(default)]
+    pub set: BTreeMap<String, String>,
+}
+
+impl EnvPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The child's environment, taken from `inherited` (the server's)
+    pub fn vars(
+        &self,
+        inherited: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Vec<(OsString, OsString)> {
+        let mut vars: Vec<_> = inherited
+            .into_iter()
+            .filter(|(name, _)| self.passes(&name.to_string_lossy()))
+            .filter(|(name, _)| !self.set.contains_key(&*name.to_string_lossy()))
+            .collect();
+        vars.extend(
+            self.set
+                .iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        vars
+    }
+
+    fn passes(&self, name: &str) -> bool {
+        let listed = || self.vars.iter().any(|pattern| matches(pattern, name));
+        match self.mode {
+            EnvMode::Inherit => true,
+            EnvMode::Allowlist => ALWAYS_INHERITED.contains(&name) || listed(),
+            EnvMode::Denylist => !listed(),
+        }
+    }
+
+    /// Replace `command`'s environment with the one this policy allows
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        command.env_clear().envs(self.vars(std::env::vars_os()));
+    }
+
+    /// Like `apply`, for the PTY
+    pub fn apply_to_pty(&self, command: &mut portable_pty::CommandBuilder) {
+        command.env_clear();
+        for (name, value) in self.vars(std::env::vars_os()) {
+            command.env(name, value);
+        }
+    }
+
+    fn check(&self, name: &str) -> Result<(), String> {
+        if self.mode == EnvMode::Inherit && !self.vars.is_empty() {
+            return Err(format!(
+                "{}: \"vars\" needs \"mode\": \"allowlist\" or \"denylist\"",
+                name
+            ));
+        }
+        for var in self.vars.iter().chain(self.set.keys()) {
+            if var.is_empty() || var.contains('=') || var.contains('\0') {
+                return Err(format!("{}: invalid variable name {:?}", name, var));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `*` at either end of `pattern` matches any prefix or suffix
+fn matches(pattern: &str, name: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        name.starts_with(prefix)
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        name.ends_with(suffix)
+    } else {
+        pattern == name
+    }
+}
+
+/// Env policies for each executor and extension module, loaded from a file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvPolicies {
+    /// The `run` tool
+    #[serde(default)]
+    pub run: EnvPolicy,
+    /// The persistent `shell` tool
+    #[serde(default)]
+    pub shell: EnvPolicy,
+    /// Extension tools without an entry in `modules`
+    #[serde(default)]
+    pub tools: EnvPolicy,
+    /// Extension tools by module directory name
+    #[serde(default)]
+    pub modules: HashMap<String, EnvPolicy>,
+}
+
+impl EnvPolicies {
+    /// Load a policy file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read env policy file {}: {}", path.display(), e))?;
+        Self::from_json(&content)
+            .map_err(|e| format!("Invalid env policy file {}: {}", path.display(), e))
+    }
+
+    /// Parse and check policies from JSON
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        let policies: Self = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        policies.run.check("run")?;
+        policies.shell.check("shell")?;
+        policies.tools.check("tools")?;
+        for (module, policy) in &policies.modules {
+            policy.check(&format!("modules.{}", module))?;
+        }
+        Ok(policies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_env() -> Vec<(OsString, OsString)> {
+        [
+            ("PATH", "/usr/bin"),
+            ("HOME", "/home/agent"),
+            ("LC_ALL", "C"),
+            ("GITHUB_TOKEN", "ghp_x"),
+            ("DB_SECRET", "hunter2"),
+            ("KUBECONFIG", "/home/agent/.kube/config"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.into(), value.into()))
+        .collect()
+    }
+
+    fn names(vars: Vec<(OsString, OsString)>) -> Vec<String> {
+        vars.into_iter()
+            .map(|(name, _)| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(EnvPolicy::default().vars(server_env()).len(), 6);
+
+        let allow = EnvPolicy {
+            mode: EnvMode::Allowlist,
+            vars: vec!["HOME".into(), "LC_*".into()],
+            ..Default::default()
+        };
+        assert_eq!(names(allow.vars(server_env())), ["PATH", "HOME", "LC_ALL"]);
+
+        let deny = EnvPolicy {
+            mode: EnvMode::Denylist,
+            vars: vec!["GITHUB_TOKEN".into(), "*_SECRET".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(deny.vars(server_env())),
+            ["PATH", "HOME", "LC_ALL", "KUBECONFIG"]
+        );
+    }
+
+    #[test]
+    fn test_set_overrides_inherited() {
+        let policy = EnvPolicy {
+            mode: EnvMode::Allowlist,
+            vars: vec!["HOME".into()],
+            set: BTreeMap::from([("HOME".into(), "/sandbox".into())]),
+        };
+        let vars = policy.vars(server_env());
+        assert_eq!(
+            vars,
+            [
+                ("PATH".into(), "/usr/bin".into()),
+                ("HOME".into(), "/sandbox".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_json() {
+        let policies = EnvPolicies::from_json(
+            r#"{
+                "run": { "mode": "denylist", "vars": ["GITHUB_TOKEN"] },
+                "modules": { "k8s": { "mode": "allowlist", "vars": ["KUBECONFIG"] } }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(policies.run.mode, EnvMode::Denylist);
+        assert!(policies.shell.is_default());
+        assert_eq!(policies.modules["k8s"].vars, ["KUBECONFIG"]);
+
+        let err = EnvPolicies::from_json(r#"{ "tools": { "vars": ["HOME"] } }"#).unwrap_err();
+        assert!(err.contains("tools"), "{}", err);
+        let err = EnvPolicies::from_json(r#"{ "run": { "set": { "A=B": "c" } } }"#).unwrap_err();
+        assert!(err.contains("invalid variable name"), "{}", err);
+        assert!(EnvPolicies::from_json(r#"{ "runn": {} }"#).is_err());
+    }
+
+    #[test]
+    fn test_example_file_is_valid() {
+        let policies =
+            EnvPolicies::from_json(include_str!("../../docs/env-policy.example.json")).unwrap();
+        assert_eq!(policies.modules["k8s"].set["KUBE_EDITOR"], "true");
+    }
+}
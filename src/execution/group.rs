@@ -0,0 +1,262 @@
+//! Process groups for spawned commands
+//!
+//! `kill_on_drop` signals only the `nu` process. Externals it started
+//! (`cargo`, `kubectl port-forward`, `sleep`) would keep running as orphans.
+//! Each command therefore runs in its own process group, and on timeout or
+//! cancellation the whole group gets SIGTERM, then SIGKILL after a grace period.
+//! `nu` is part of the group, so it isn't killed on drop as well: that would
+//! SIGKILL it before it saw the SIGTERM.
+
+use std::process::Output;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+/// How long processes get to exit after SIGTERM before they are killed
+pub const TERM_GRACE: Duration = Duration::from_secs(2);
+
+/// How `output_with_timeout` failed
+#[derive(Debug)]
+pub enum WaitError {
+    Spawn(std::io::Error),
+    Wait(std::io::Error),
+    /// The command ran past its timeout; its process group was terminated
+    TimedOut(Termination),
+}
+
+/// Processes left after terminating a group
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Termination {
+    /// Ignored SIGTERM and were killed with SIGKILL
+    pub killed: Vec<String>,
+    /// Still running after SIGKILL (e.g. stuck in uninterruptible I/O)
+    pub survivors: Vec<String>,
+}
+
+impl Termination {
+    /// A note for the agent about processes that needed SIGKILL or survived it
+    pub fn note(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.killed.is_empty() {
+            parts.push(format!(
+                "killed after ignoring SIGTERM: {}",
+                self.killed.join(", ")
+            ));
+        }
+        if !self.survivors.is_empty() {
+            parts.push(format!(
+                "still running after SIGKILL: {}",
+                self.survivors.join(", ")
+            ));
+        }
+        (!parts.is_empty()).then(|| format!("[{}]", parts.join("; ")))
+    }
+}
+
+/// Run `command` in a new process group and collect its output. If it runs
+/// past `timeout`, or the returned future is dropped, the whole group is
+/// terminated.
+pub async fn output_with_timeout(
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<Output, WaitError> {
+    #[cfg(unix)]
+    command.process_group(0);
+    // Without process groups, killing `nu` is all that can be done
+    #[cfg(not(unix))]
+    command.kill_on_drop(true);
+    let mut child = command
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(WaitError::Spawn)?;
+    let mut group = GroupGuard::new(child.id());
+
+    // Like `wait_with_output`, but the child stays here so that it is only
+    // dropped once the group has been terminated
+    let (mut stdout, mut stderr) = (child.stdout.take(), child.stderr.take());
+    let collect = async {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let (status, _, _) = tokio::try_join!(
+            child.wait(),
+            read_to_end(stdout.as_mut(), &mut out),
+            read_to_end(stderr.as_mut(), &mut err),
+        )?;
+        Ok::<_, std::io::Error>(Output {
+            status,
+            stdout: out,
+            stderr: err,
+        })
+    };
+
+    match tokio::time::timeout(timeout, collect).await {
+        Ok(Ok(output)) => {
+            group.disarm();
+            Ok(output)
+        }
+        Ok(Err(e)) => Err(WaitError::Wait(e)),
+        Err(_) => Err(WaitError::TimedOut(group.terminate().await)),
+    }
+}
+
+/// Read a piped output to the end; nothing if it wasn't piped
+async fn read_to_end<R: AsyncReadExt + Unpin>(
+    pipe: Option<&mut R>,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    match pipe {
+        Some(pipe) => pipe.read_to_end(buf).await,
+        None => Ok(0),
+    }
+}
+
+/// Terminates a process group when dropped, unless disarmed
+struct GroupGuard {
+    pgid: Option<i32>,
+}
+
+impl GroupGuard {
+    /// The group led by `pid`, which was spawned with `process_group(0)`
+    fn new(pid: Option<u32>) -> Self {
+        Self {
+            pgid: pid.map(|pid| pid as i32),
+        }
+    }
+
+    fn disarm(&mut self) {
+        self.pgid = None;
+    }
+
+    async fn terminate(&mut self) -> Termination {
+        match self.pgid.take() {
+            Some(pgid) => tokio::task::spawn_blocking(move || terminate_group(pgid, TERM_GRACE))
+                .await
+                .unwrap_or_default(),
+            None => Termination::default(),
+        }
+    }
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        // Cancelled: nobody waits for the result, so terminate in the background
+        if let Some(pgid) = self.pgid.take() {
+            std::thread::spawn(move || terminate_group(pgid, TERM_GRACE));
+        }
+    }
+}
+
+/// SIGTERM the group, give it `grace` to exit, then SIGKILL what is left
+#[cfg(unix)]
+fn terminate_group(pgid: i32, grace: Duration) -> Termination {
+    use std::time::Instant;
+
+    const POLL: Duration = Duration::from_millis(50);
+    const KILL_WAIT: Duration = Duration::from_millis(500);
+
+    let wait_until_empty = |timeout: Duration| {
+        let deadline = Instant::now() + timeout;
+        while !members(pgid).is_empty() && Instant::now() < deadline {
+            std::thread::sleep(POLL);
+        }
+        members(pgid)
+    };
+
+    if !signal_group(pgid, libc::SIGTERM) {
+        return Termination::default();
+    }
+    let killed = wait_until_empty(grace);
+    if killed.is_empty() {
+        return Termination::default();
+    }
+    signal_group(pgid, libc::SIGKILL);
+    Termination {
+        killed,
+        survivors: wait_until_empty(KILL_WAIT),
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate_group(_pgid: i32, _grace: Duration) -> Termination {
+    Termination::default()
+}
+
+/// Send `signal` to every process in the group; false if the group is gone
+#[cfg(unix)]
+fn signal_group(pgid: i32, signal: i32) -> bool {
+    // SAFETY: kill has no memory-safety requirements
+    unsafe { libc::kill(-pgid, signal) == 0 }
+}
+
+/// Live processes in the group, as `pid (name)`. Zombies are dead and left out.
+#[cfg(target_os = "linux")]
+fn members(pgid: i32) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let pid: i32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            // pid (comm) state ppid pgrp ...; comm may contain spaces and parens
+            let (head, rest) = stat.rsplit_once(')')?;
+            let name = head.split_once('(')?.1;
+            let mut fields = rest.split_whitespace();
+            let state = fields.next()?;
+            let pgrp: i32 = fields.nth(1)?.parse().ok()?;
+            (pgrp == pgid && state != "Z").then(|| format!("{} ({})", pid, name))
+        })
+        .collect()
+}
+
+/// Without /proc the members can't be listed, only whether any remain
+#[cfg(all(unix, not(target_os = "linux")))]
+fn members(pgid: i32) -> Vec<String> {
+    if signal_group(pgid, 0) {
+        vec![format!("processes in group {}", pgid)]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note() {
+        assert_eq!(Termination::default().note(), None);
+        let termination = Termination {
+            killed: vec!["42 (sleep)".to_string()],
+            survivors: vec![],
+        };
+        assert_eq!(
+            termination.note().as_deref(),
+            Some("[killed after ignoring SIGTERM: 42 (sleep)]")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_sends_sigterm_before_sigkill() {
+        let dir = std::env::temp_dir().join("nu_mcp_group_sigterm");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("terminated");
+
+        // The leader only records SIGTERM if it is still alive to handle it
+        let script = format!(
+            "trap 'echo term > {}; exit 0' TERM; sleep 30 & wait",
+            marker.display()
+        );
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(script);
+        let result = output_with_timeout(&mut command, Duration::from_millis(300)).await;
+
+        let Err(WaitError::TimedOut(termination)) = result else {
+            panic!("expected a timeout: {:?}", result);
+        };
+        assert_eq!(termination, Termination::default());
+        assert_eq!(std::fs::read_to_string(&marker).unwrap().trim(), "term");
+    }
+}
@@ -0,0 +1,178 @@
+//! Heuristics for detecting a command that is blocked waiting for terminal input
+//!
+//! A command running in the persistent shell can stall forever when it expects
+//! a human at the keyboard (pagers, confirmations, full-screen programs).
+//! Three signals are used, cheapest first:
+//! - Alternate screen: the command switched to the alternate screen buffer
+//! - Prompt signature: the last output line looks like a pager or confirmation prompt
+//! - Blocked read: the foreground process is sleeping in a terminal read (Linux only)
+
+/// Escape sequences that switch the terminal to the alternate screen buffer.
+/// Full-screen programs (less, vim, man, top) emit one of these on startup.
+const ALT_SCREEN_ENTER: &[&[u8]] = &[b"\x1b[?1049h", b"\x1b[?1047h", b"\x1b[?47h"];
+
+/// Escape sequences that switch back to the normal screen buffer.
+const ALT_SCREEN_LEAVE: &[&[u8]] = &[b"\x1b[?1049l", b"\x1b[?1047l", b"\x1b[?47l"];
+
+/// Prompts printed by pagers and interactive confirmations on the last output line.
+const PROMPT_SIGNATURES: &[&str] = &[
+    "(END)",
+    "--More--",
+    "--more--",
+    "Press RETURN",
+    "Press ENTER",
+    "Press any key",
+    "[Y/n]",
+    "[y/N]",
+    "(y/n)",
+    "(yes/no)",
+    "Password:",
+    "password:",
+    "passphrase",
+];
+
+/// Find the last alternate screen switch in the output.
+/// Returns `Some(true)` if it entered, `Some(false)` if it left, `None` if neither.
+pub fn alternate_screen_switch(output: &[u8]) -> Option<bool> {
+    let last_enter = ALT_SCREEN_ENTER
+        .iter()
+        .filter_map(|seq| rfind(output, seq))
+        .max();
+    let last_leave = ALT_SCREEN_LEAVE
+        .iter()
+        .filter_map(|seq| rfind(output, seq))
+        .max();
+
+    match (last_enter, last_leave) {
+        (Some(enter), Some(leave)) => Some(enter > leave),
+        (Some(_), None) => Some(true),
+        (None, Some(_)) => Some(false),
+        (None, None) => None,
+    }
+}
+
+/// Find a pager or confirmation prompt on the last non-empty output line
+pub fn trailing_prompt(output: &[u8]) -> Option<&'static str> {
+    let clean = strip_ansi_escapes::strip(output);
+    let text = String::from_utf8_lossy(&clean);
+    let last_line = text.lines().map(str::trim).rfind(|line| !line.is_empty())?;
+
+    // less without the alternate screen (e.g. `git diff` with -X) shows a bare colon
+    if last_line == ":" {
+        return Some(":");
+    }
+
+    PROMPT_SIGNATURES
+        .iter()
+        .find(|signature| last_line.contains(*signature))
+        .copied()
+}
+
+/// Check whether a process is sleeping in a read from a terminal device
+#[cfg(target_os = "linux")]
+pub fn is_blocked_on_tty_read(pid: i32) -> bool {
+    let proc_dir = std::path::PathBuf::from(format!("/proc/{pid}"));
+    let Ok(wchan) = std::fs::read_to_string(proc_dir.join("wchan")) else {
+        return false;
+    };
+
+    match wchan.trim() {
+        "n_tty_read" => true,
+        // Newer kernels report the generic wait; only count it when the
+        // pending syscall's first argument is a terminal file descriptor.
+        "wait_woken" => std::fs::read_to_string(proc_dir.join("syscall"))
+            .ok()
+            .and_then(|syscall| {
+                let fd = syscall.split_whitespace().nth(1)?;
+                i64::from_str_radix(fd.trim_start_matches("0x"), 16).ok()
+            })
+            .and_then(|fd| std::fs::read_link(proc_dir.join("fd").join(fd.to_string())).ok())
+            .is_some_and(|target| {
+                let target = target.to_string_lossy();
+                target.starts_with("/dev/pts/") || target.starts_with("/dev/tty")
+            }),
+        _ => false,
+    }
+}
+
+/// Blocked-read detection needs /proc; other platforms rely on the output heuristics
+#[cfg(not(target_os = "linux"))]
+pub fn is_blocked_on_tty_read(_pid: i32) -> bool {
+    false
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alternate_screen_entered() {
+        assert_eq!(
+            alternate_screen_switch(b"text\x1b[?1049hscreen"),
+            Some(true)
+        );
+        assert_eq!(alternate_screen_switch(b"\x1b[?47h"), Some(true));
+    }
+
+    #[test]
+    fn test_alternate_screen_left_again() {
+        assert_eq!(
+            alternate_screen_switch(b"\x1b[?1049hscreen\x1b[?1049ldone"),
+            Some(false)
+        );
+        assert_eq!(alternate_screen_switch(b"\x1b[?1049l"), Some(false));
+    }
+
+    #[test]
+    fn test_alternate_screen_reentered() {
+        assert_eq!(
+            alternate_screen_switch(b"\x1b[?1049h\x1b[?1049l\x1b[?1049h"),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_no_alternate_screen() {
+        assert_eq!(alternate_screen_switch(b"plain output\r\n"), None);
+    }
+
+    #[test]
+    fn test_pager_end_prompt() {
+        assert_eq!(trailing_prompt(b"line 1\r\nline 2\r\n(END)"), Some("(END)"));
+    }
+
+    #[test]
+    fn test_bare_colon_pager_prompt() {
+        assert_eq!(trailing_prompt(b"diff --git a/x b/x\r\n:"), Some(":"));
+    }
+
+    #[test]
+    fn test_confirmation_prompt_with_ansi() {
+        assert_eq!(
+            trailing_prompt(b"\x1b[1mRemove 3 files? [y/N]\x1b[0m "),
+            Some("[y/N]")
+        );
+    }
+
+    #[test]
+    fn test_prompt_only_checked_on_last_line() {
+        assert_eq!(trailing_prompt(b"Password: set\r\nall done\r\n"), None);
+    }
+
+    #[test]
+    fn test_empty_output_has_no_prompt() {
+        assert_eq!(trailing_prompt(b""), None);
+        assert_eq!(trailing_prompt(b"\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_nonexistent_process_is_not_blocked() {
+        assert!(!is_blocked_on_tty_read(i32::MAX));
+    }
+}
@@ -0,0 +1,173 @@
+//! Named key encoding for sending input to interactive commands
+//!
+//! Keys are matched case-insensitively. Single characters are sent as-is,
+//! so `q` or `y` work without a name. Control keys use `Ctrl-<letter>`.
+//!
+//! `TypedLine` follows the line being typed across several inputs, so that a
+//! command split over calls is validated as a whole when Enter submits it.
+
+/// Encode a named key as the bytes a terminal would send for it
+pub fn encode_key(name: &str) -> Result<Vec<u8>, String> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c.to_string().into_bytes());
+    }
+
+    let bytes: &[u8] = match name.to_ascii_lowercase().as_str() {
+        "enter" | "return" => b"\r",
+        "tab" => b"\t",
+        "space" => b" ",
+        "escape" | "esc" => b"\x1b",
+        "backspace" => b"\x7f",
+        "up" => b"\x1b[A",
+        "down" => b"\x1b[B",
+        "right" => b"\x1b[C",
+        "left" => b"\x1b[D",
+        "home" => b"\x1b[H",
+        "end" => b"\x1b[F",
+        "pageup" => b"\x1b[5~",
+        "pagedown" => b"\x1b[6~",
+        "delete" => b"\x1b[3~",
+        lower => {
+            return lower
+                .strip_prefix("ctrl-")
+                .or_else(|| lower.strip_prefix("ctrl+"))
+                .and_then(|key| match key.as_bytes() {
+                    [c @ b'a'..=b'z'] => Some(vec![c - b'a' + 1]),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("Unknown key '{}'", name));
+        }
+    };
+
+    Ok(bytes.to_vec())
+}
+
+/// The line typed so far, rebuilt from the bytes sent to the terminal.
+/// Backspace removes a character and Ctrl-C or Ctrl-U drop the line. Other
+/// escape sequences are skipped: cursor movement isn't followed, so text is
+/// taken to be typed at the end of the line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypedLine {
+    bytes: Vec<u8>,
+}
+
+impl TypedLine {
+    /// Type `input` after the current line. Returns the lines it submits with
+    /// Enter; the unfinished rest becomes the current line.
+    pub fn feed(&mut self, input: &[u8]) -> Vec<String> {
+        let mut submitted = Vec::new();
+        let mut rest = input;
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            match byte {
+                b'\r' | b'\n' => submitted.push(self.take()),
+                // Ctrl-C, Ctrl-U
+                0x03 | 0x15 => self.bytes.clear(),
+                // Backspace: drop the last character, with its continuation bytes
+                0x7f | 0x08 => {
+                    while let Some(last) = self.bytes.pop() {
+                        if last & 0xc0 != 0x80 {
+                            break;
+                        }
+                    }
+                }
+                0x1b => rest = skip_escape_sequence(rest),
+                _ => self.bytes.push(byte),
+            }
+        }
+        submitted
+    }
+
+    /// The unfinished line
+    pub fn current(&self) -> String {
+        String::from_utf8_lossy(&self.bytes).into_owned()
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    fn take(&mut self) -> String {
+        let line = self.current();
+        self.bytes.clear();
+        line
+    }
+}
+
+/// The input after an escape sequence (CSI `ESC [ ... final` or SS3 `ESC O x`)
+/// whose ESC was already read. A lone ESC or Alt-key skips only the ESC.
+fn skip_escape_sequence(input: &[u8]) -> &[u8] {
+    match input {
+        [b'[', rest @ ..] => match rest.iter().position(|b| (0x40..=0x7e).contains(b)) {
+            Some(end) => &rest[end + 1..],
+            None => &[],
+        },
+        [b'O', _, rest @ ..] => rest,
+        _ => input,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_character_is_literal() {
+        assert_eq!(encode_key("q").unwrap(), b"q");
+        assert_eq!(encode_key("Y").unwrap(), b"Y");
+    }
+
+    #[test]
+    fn test_named_keys_case_insensitive() {
+        assert_eq!(encode_key("Enter").unwrap(), b"\r");
+        assert_eq!(encode_key("ESCAPE").unwrap(), b"\x1b");
+        assert_eq!(encode_key("pagedown").unwrap(), b"\x1b[6~");
+    }
+
+    #[test]
+    fn test_arrow_keys() {
+        assert_eq!(encode_key("Up").unwrap(), b"\x1b[A");
+        assert_eq!(encode_key("Down").unwrap(), b"\x1b[B");
+        assert_eq!(encode_key("Right").unwrap(), b"\x1b[C");
+        assert_eq!(encode_key("Left").unwrap(), b"\x1b[D");
+    }
+
+    #[test]
+    fn test_control_keys() {
+        assert_eq!(encode_key("Ctrl-C").unwrap(), b"\x03");
+        assert_eq!(encode_key("ctrl+d").unwrap(), b"\x04");
+        assert_eq!(encode_key("Ctrl-Z").unwrap(), b"\x1a");
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        assert!(encode_key("Hyper").is_err());
+        assert!(encode_key("Ctrl-1").is_err());
+        assert!(encode_key("").is_err());
+    }
+
+    #[test]
+    fn test_typed_line_across_inputs() {
+        let mut line = TypedLine::default();
+        assert!(line.feed(b"cat /et").is_empty());
+        assert_eq!(line.current(), "cat /et");
+        assert_eq!(line.feed(b"c/passwd\rls"), vec!["cat /etc/passwd"]);
+        assert_eq!(line.current(), "ls");
+    }
+
+    #[test]
+    fn test_typed_line_editing_keys() {
+        let mut line = TypedLine::default();
+        line.feed("cat /tmp/é".as_bytes());
+        line.feed(b"\x7fx");
+        assert_eq!(line.current(), "cat /tmp/x");
+        line.feed(b"\x03rm -r");
+        assert_eq!(line.current(), "rm -r");
+        // Arrow keys and Escape add nothing to the line
+        line.feed(b"\x1b[D\x1b[3~\x1bOA\x1bf");
+        assert_eq!(line.current(), "rm -rf");
+        line.feed(b"\x15");
+        assert_eq!(line.current(), "");
+    }
+}
@@ -0,0 +1,324 @@
+//! Resource limits for spawned commands
+//!
+//! Limits are set with `setrlimit` in the child right before it executes
+//! `nu`, so they cover the command and every process it starts. A runaway
+//! command is stopped by the kernel instead of running until the timeout.
+//!
+//! The persistent shell outlives its commands, and the kernel counts CPU time
+//! over a process's whole life, so its CPU limit is moved forward before each
+//! command (see `extend_cpu_limit`).
+
+/// Limits for one child process. `None` leaves the server's own limit in place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// CPU seconds. The process gets SIGXCPU when it reaches the limit.
+    pub cpu_secs: Option<u64>,
+    /// Address space (virtual memory) in bytes
+    pub memory_bytes: Option<u64>,
+    /// Processes for the user. The kernel counts all of the user's processes,
+    /// not only those started by the command.
+    pub max_processes: Option<u64>,
+    pub open_files: Option<u64>,
+    /// Largest file a command may write, in bytes. Writing past it raises SIGXFSZ.
+    pub file_size_bytes: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Set the limits on the calling process. Only makes system calls, so it
+    /// is safe to use in a pre-exec hook.
+    #[cfg(unix)]
+    pub fn apply(&self) -> std::io::Result<()> {
+        for (resource, limit) in self.rlimits() {
+            // SAFETY: limit is a valid rlimit for the duration of the call
+            if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the limits on a running process, for spawners without a pre-exec
+    /// hook. The CPU limit starts counting now (see `extend_cpu_limit`).
+    #[cfg(target_os = "linux")]
+    pub fn apply_to(&self, pid: u32) -> std::io::Result<()> {
+        for (resource, limit) in self.rlimits() {
+            if resource == libc::RLIMIT_CPU {
+                continue;
+            }
+            // SAFETY: limit is a valid rlimit; the old limit isn't requested
+            if unsafe { libc::prlimit(pid as libc::pid_t, resource, &limit, std::ptr::null_mut()) }
+                != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        self.extend_cpu_limit(pid)
+    }
+
+    /// Give a running process `cpu_secs` of CPU time from now on. The kernel
+    /// compares the limit with all the CPU time the process has used, so a
+    /// long-lived shell needs this before each command. Only the soft limit
+    /// moves, since an unprivileged process can't raise a hard limit again;
+    /// processes the shell starts inherit it, so they may use slightly more
+    /// than `cpu_secs` (the shell's own CPU time so far).
+    #[cfg(target_os = "linux")]
+    pub fn extend_cpu_limit(&self, pid: u32) -> std::io::Result<()> {
+        let Some(secs) = self.cpu_secs else {
+            return Ok(());
+        };
+        let pid = pid as libc::pid_t;
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: current is a valid rlimit to write the old limit to
+        if unsafe { libc::prlimit(pid, libc::RLIMIT_CPU, std::ptr::null(), &mut current) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let soft = (cpu_time_secs(pid)?.saturating_add(secs) as libc::rlim_t).min(current.rlim_max);
+        let limit = libc::rlimit {
+            rlim_cur: soft,
+            rlim_max: current.rlim_max,
+        };
+        // SAFETY: limit is a valid rlimit; the old limit isn't requested
+        if unsafe { libc::prlimit(pid, libc::RLIMIT_CPU, &limit, std::ptr::null_mut()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Limits can only be set on another process on Linux
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply_to(&self, _pid: u32) -> std::io::Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+        }
+    }
+
+    /// Nothing to extend where `apply_to` can't set limits
+    #[cfg(not(target_os = "linux"))]
+    pub fn extend_cpu_limit(&self, _pid: u32) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// The configured limits as `setrlimit` arguments, without allocating
+    #[cfg(unix)]
+    fn rlimits(&self) -> impl Iterator<Item = (RlimitResource, libc::rlimit)> {
+        let to_rlim = |value: u64| value as libc::rlim_t;
+        [
+            // The hard limit is a second later, so SIGXCPU arrives before SIGKILL
+            (
+                libc::RLIMIT_CPU,
+                self.cpu_secs
+                    .map(|secs| (to_rlim(secs), to_rlim(secs.saturating_add(1)))),
+            ),
+            (
+                libc::RLIMIT_AS,
+                self.memory_bytes
+                    .map(|bytes| (to_rlim(bytes), to_rlim(bytes))),
+            ),
+            (
+                libc::RLIMIT_NPROC,
+                self.max_processes.map(|n| (to_rlim(n), to_rlim(n))),
+            ),
+            (
+                libc::RLIMIT_NOFILE,
+                self.open_files.map(|n| (to_rlim(n), to_rlim(n))),
+            ),
+            (
+                libc::RLIMIT_FSIZE,
+                self.file_size_bytes
+                    .map(|bytes| (to_rlim(bytes), to_rlim(bytes))),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(resource, limit)| {
+            limit.map(|(soft, hard)| {
+                (
+                    resource,
+                    libc::rlimit {
+                        rlim_cur: soft,
+                        rlim_max: hard,
+                    },
+                )
+            })
+        })
+    }
+
+    /// Describe the limit that stopped a failed command, judging by the signal
+    /// that killed it or its error output. `None` if no configured limit fits.
+    pub fn explain(&self, signal: Option<i32>, output: &str) -> Option<String> {
+        let mentions = |patterns: &[&str]| patterns.iter().any(|p| output.contains(p));
+        let killed_by = |expected: i32| signal == Some(expected);
+
+        let limit = if let Some(secs) = self.cpu_secs
+            && (killed_by(SIGXCPU) || mentions(&["SIGXCPU", "CPU time limit exceeded"]))
+        {
+            format!("CPU time limit of {} seconds", secs)
+        } else if let Some(bytes) = self.file_size_bytes
+            && (killed_by(SIGXFSZ) || mentions(&["SIGXFSZ", "File size limit exceeded"]))
+        {
+            format!("file size limit of {}", format_bytes(bytes))
+        } else if let Some(bytes) = self.memory_bytes
+            && mentions(&[
+                "memory allocation of",
+                "Cannot allocate memory",
+                "out of memory",
+            ])
+        {
+            format!("memory limit of {}", format_bytes(bytes))
+        } else if let Some(n) = self.open_files
+            && mentions(&["Too many open files"])
+        {
+            format!("open file limit of {}", n)
+        } else if let Some(n) = self.max_processes
+            && mentions(&["Resource temporarily unavailable"])
+        {
+            format!("process limit of {}", n)
+        } else {
+            return None;
+        };
+        Some(format!("[stopped by resource limit: {}]", limit))
+    }
+
+    /// Append the `explain` note to the error output of a failed command
+    pub fn annotate(&self, status: &std::process::ExitStatus, output: &mut String) {
+        if status.success() {
+            return;
+        }
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(status);
+        #[cfg(not(unix))]
+        let signal = None;
+
+        if let Some(note) = self.explain(signal, output) {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(&note);
+        }
+    }
+}
+
+/// CPU time (user and system) a process has used, rounded up to whole seconds
+#[cfg(target_os = "linux")]
+fn cpu_time_secs(pid: libc::pid_t) -> std::io::Result<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected /proc stat");
+    // The command name in parentheses may contain spaces; fields follow the last `)`.
+    // After it come state (field 3) ... utime (field 14) and stime (field 15).
+    let fields: Vec<&str> = stat
+        .rsplit_once(')')
+        .ok_or_else(invalid)?
+        .1
+        .split_whitespace()
+        .collect();
+    let ticks = |index: usize| -> std::io::Result<u64> {
+        fields
+            .get(index)
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let used = ticks(11)? + ticks(12)?;
+    // SAFETY: sysconf has no side effects
+    let per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+    Ok(used.div_ceil(per_sec))
+}
+
+#[cfg(all(unix, target_env = "gnu", target_os = "linux"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_env = "gnu", target_os = "linux"))))]
+type RlimitResource = libc::c_int;
+
+#[cfg(unix)]
+const SIGXCPU: i32 = libc::SIGXCPU;
+#[cfg(unix)]
+const SIGXFSZ: i32 = libc::SIGXFSZ;
+#[cfg(not(unix))]
+const SIGXCPU: i32 = 24;
+#[cfg(not(unix))]
+const SIGXFSZ: i32 = 25;
+
+fn format_bytes(bytes: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    if bytes >= MB && bytes.is_multiple_of(MB) {
+        format!("{} MB", bytes / MB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_matches_configured_limits() {
+        let limits = ResourceLimits {
+            cpu_secs: Some(30),
+            file_size_bytes: Some(10 * 1024 * 1024),
+            open_files: Some(64),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits
+                .explain(None, "terminated by SIGXCPU (24)")
+                .as_deref(),
+            Some("[stopped by resource limit: CPU time limit of 30 seconds]")
+        );
+        assert_eq!(
+            limits.explain(Some(SIGXFSZ), "").as_deref(),
+            Some("[stopped by resource limit: file size limit of 10 MB]")
+        );
+        assert_eq!(
+            limits
+                .explain(None, "Too many open files (os error 24)")
+                .as_deref(),
+            Some("[stopped by resource limit: open file limit of 64]")
+        );
+        // Limits that aren't set don't explain anything
+        assert_eq!(
+            limits.explain(None, "memory allocation of 1024 bytes failed"),
+            None
+        );
+        assert_eq!(limits.explain(Some(9), "killed"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_in_child() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+        use std::process::Command;
+
+        let run = |limits: ResourceLimits, script: &str| {
+            let mut command = Command::new("/bin/sh");
+            command.arg("-c").arg(script);
+            // SAFETY: apply only makes system calls
+            unsafe {
+                command.pre_exec(move || limits.apply());
+            }
+            command.output().unwrap()
+        };
+
+        let open_files = ResourceLimits {
+            open_files: Some(64),
+            ..Default::default()
+        };
+        let output = run(open_files, "ulimit -n");
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "64");
+
+        let cpu = ResourceLimits {
+            cpu_secs: Some(1),
+            ..Default::default()
+        };
+        let output = run(cpu, "while :; do :; done");
+        assert_eq!(output.status.signal(), Some(libc::SIGXCPU));
+        assert!(cpu.explain(output.status.signal(), "").is_some());
+    }
+}
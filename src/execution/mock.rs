@@ -1,10 +1,12 @@
-use super::CommandExecutor;
-use std::path::Path;
+use super::{CommandExecutor, RunningCommand};
+use std::path::{Path, PathBuf};
 
 pub struct MockExecutor {
     pub stdout: String,
     pub stderr: String,
     pub should_fail: bool,
+    pub running: Option<RunningCommand>,
+    pub working_dir: Option<PathBuf>,
 }
 
 impl MockExecutor {
@@ -13,6 +15,8 @@ impl MockExecutor {
             stdout,
             stderr,
             should_fail: false,
+            running: None,
+            working_dir: None,
         }
     }
 
@@ -21,8 +25,20 @@ impl MockExecutor {
             stdout: String::new(),
             stderr: error.clone(),
             should_fail: true,
+            running: None,
+            working_dir: None,
         }
     }
+
+    pub fn with_running(mut self, running: RunningCommand) -> Self {
+        self.running = Some(running);
+        self
+    }
+
+    pub fn with_working_dir(mut self, working_dir: PathBuf) -> Self {
+        self.working_dir = Some(working_dir);
+        self
+    }
 }
 
 impl CommandExecutor for MockExecutor {
@@ -38,4 +54,24 @@ impl CommandExecutor for MockExecutor {
             Ok((self.stdout.clone(), self.stderr.clone()))
         }
     }
+
+    async fn send_input(
+        &self,
+        _input: &[u8],
+        _timeout_secs: Option<u64>,
+    ) -> Result<(String, String), String> {
+        if self.should_fail {
+            Err(self.stderr.clone())
+        } else {
+            Ok((self.stdout.clone(), self.stderr.clone()))
+        }
+    }
+
+    fn peek(&self) -> Option<RunningCommand> {
+        self.running.clone()
+    }
+
+    fn working_dir(&self) -> Option<PathBuf> {
+        self.working_dir.clone()
+    }
 }
@@ -1,4 +1,5 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 const DEFAULT_TIMEOUT_SECS: u64 = 300;
 
@@ -11,6 +12,43 @@ pub(crate) fn get_default_timeout() -> u64 {
         .unwrap_or(DEFAULT_TIMEOUT_SECS)
 }
 
+/// Snapshot of a command that is still running in an executor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningCommand {
+    pub command: String,
+    pub elapsed: Duration,
+    /// Output produced so far, with ANSI escape codes stripped
+    pub output: String,
+    pub waiting_for_input: bool,
+}
+
+impl std::fmt::Display for RunningCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Command: {}", self.command)?;
+        writeln!(f, "Elapsed: {:.1}s", self.elapsed.as_secs_f64())?;
+        let status = if self.waiting_for_input {
+            "waiting for input"
+        } else {
+            "running"
+        };
+        writeln!(f, "Status: {}", status)?;
+        if self.output.is_empty() {
+            write!(f, "Output so far: (none)")
+        } else {
+            write!(f, "Output so far:\n{}", self.output)
+        }
+    }
+}
+
+/// Output of a command that returned, with its exit code when known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the executor can't tell, or the command is still running
+    pub exit_code: Option<i32>,
+}
+
 pub trait CommandExecutor: Send + Sync {
     fn execute(
         &self,
@@ -19,16 +57,71 @@ pub trait CommandExecutor: Send + Sync {
         timeout_secs: Option<u64>,
     ) -> impl std::future::Future<Output = Result<(String, String), String>> + Send;
 
+    /// Like `execute`, also reporting the exit code.
+    /// Default implementation doesn't know the exit code.
+    fn execute_with_status(
+        &self,
+        command: &str,
+        working_dir: &Path,
+        timeout_secs: Option<u64>,
+    ) -> impl std::future::Future<Output = Result<ExecutionOutput, String>> + Send {
+        async move {
+            let (stdout, stderr) = self.execute(command, working_dir, timeout_secs).await?;
+            Ok(ExecutionOutput {
+                stdout,
+                stderr,
+                exit_code: None,
+            })
+        }
+    }
+
     /// Reset the executor to a clean state (e.g., fresh shell).
     /// Default implementation is a no-op for stateless executors.
     fn reset(&self) -> impl std::future::Future<Output = Result<(), String>> + Send {
         async { Ok(()) }
     }
+
+    /// Send input to a command that is waiting for it, returning the output
+    /// produced since the last read.
+    /// Default implementation rejects input for executors without interactive sessions.
+    fn send_input(
+        &self,
+        _input: &[u8],
+        _timeout_secs: Option<u64>,
+    ) -> impl std::future::Future<Output = Result<(String, String), String>> + Send {
+        async { Err("This executor does not accept interactive input".to_string()) }
+    }
+
+    /// Look at the command currently running, without waiting for it.
+    /// Default implementation reports nothing running for stateless executors.
+    fn peek(&self) -> Option<RunningCommand> {
+        None
+    }
+
+    /// The directory relative paths in the next command resolve against, for
+    /// executors that keep one between calls (a persistent shell after `cd`).
+    /// Default implementation reports none for stateless executors.
+    fn working_dir(&self) -> Option<PathBuf> {
+        None
+    }
 }
 
 pub mod nushell;
 pub use nushell::NushellExecutor;
 
+pub mod child;
+pub use child::ChildConfig;
+
+pub mod env;
+pub use env::{EnvMode, EnvPolicies, EnvPolicy};
+
+pub mod group;
+
+pub mod limits;
+pub use limits::ResourceLimits;
+
+pub mod input_wait;
+pub mod keys;
 pub mod osc133;
 pub mod persistent;
 
@@ -1,10 +1,19 @@
-use super::CommandExecutor;
-use std::{path::Path, process::Stdio, time::Duration};
+use super::group::{WaitError, output_with_timeout};
+use super::{ChildConfig, CommandExecutor, ExecutionOutput};
+use std::{path::Path, time::Duration};
 use tokio::process::Command;
-use tokio::time::timeout;
 
-#[derive(Clone)]
-pub struct NushellExecutor;
+#[derive(Clone, Default)]
+pub struct NushellExecutor {
+    child: ChildConfig,
+}
+
+impl NushellExecutor {
+    /// An executor whose `nu` processes are confined by `child`
+    pub fn new(child: ChildConfig) -> Self {
+        Self { child }
+    }
+}
 
 impl CommandExecutor for NushellExecutor {
     async fn execute(
@@ -13,38 +22,51 @@ impl CommandExecutor for NushellExecutor {
         working_dir: &Path,
         timeout_secs: Option<u64>,
     ) -> Result<(String, String), String> {
+        let output = self
+            .execute_with_status(command, working_dir, timeout_secs)
+            .await?;
+        Ok((output.stdout, output.stderr))
+    }
+
+    async fn execute_with_status(
+        &self,
+        command: &str,
+        working_dir: &Path,
+        timeout_secs: Option<u64>,
+    ) -> Result<ExecutionOutput, String> {
         // Priority: parameter > env var > built-in default (60s)
         let timeout_duration =
             Duration::from_secs(timeout_secs.unwrap_or_else(super::get_default_timeout));
 
-        // Spawn child process
-        let child = Command::new("nu")
-            .arg("-c")
-            .arg(command)
-            .current_dir(working_dir)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true) // Ensure child is killed if future is dropped/cancelled
-            .spawn()
-            .map_err(|e| format!("Failed to spawn nu process: {}", e))?;
-
-        // Wait for process with timeout - child is consumed by wait_with_output
-        let output_result = timeout(timeout_duration, child.wait_with_output()).await;
+        // Run in its own process group so a timeout or cancellation also
+        // stops the externals `nu` started
+        let mut cmd = Command::new("nu");
+        cmd.arg("-c").arg(command).current_dir(working_dir);
+        self.child.apply(&mut cmd);
 
-        match output_result {
-            Ok(Ok(output)) => {
+        match output_with_timeout(&mut cmd, timeout_duration).await {
+            Ok(output) => {
                 let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                Ok((stdout, stderr))
+                let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+                self.child.limits.annotate(&output.status, &mut stderr);
+                Ok(ExecutionOutput {
+                    stdout,
+                    stderr,
+                    exit_code: output.status.code(),
+                })
             }
-            Ok(Err(e)) => Err(e.to_string()),
-            Err(_) => {
-                // Timeout occurred - child should be killed by kill_on_drop
-                // when the timeout future is dropped/cancelled
-                Err(format!(
+            Err(WaitError::Spawn(e)) => Err(format!("Failed to spawn nu process: {}", e)),
+            Err(WaitError::Wait(e)) => Err(e.to_string()),
+            Err(WaitError::TimedOut(termination)) => {
+                let mut message = format!(
                     "Command timed out after {} seconds",
                     timeout_duration.as_secs()
-                ))
+                );
+                if let Some(note) = termination.note() {
+                    message.push('\n');
+                    message.push_str(&note);
+                }
+                Err(message)
             }
         }
     }
@@ -4,7 +4,7 @@ use super::{CommandExecutor, NushellExecutor};
 
 #[tokio::test]
 async fn test_nushell_executor_basic_command() {
-    let executor = NushellExecutor;
+    let executor = NushellExecutor::default();
     let work_dir = env::current_dir().unwrap();
 
     let result = executor.execute("echo 'hello'", &work_dir, None).await;
@@ -17,7 +17,7 @@ async fn test_nushell_executor_basic_command() {
 #[tokio::test]
 async fn test_nushell_executor_timeout_short_command() {
     // Short command should complete within default timeout
-    let executor = NushellExecutor;
+    let executor = NushellExecutor::default();
     let work_dir = env::current_dir().unwrap();
 
     let result = executor.execute("sleep 1sec", &work_dir, None).await;
@@ -28,7 +28,7 @@ async fn test_nushell_executor_timeout_short_command() {
 #[tokio::test]
 async fn test_nushell_executor_timeout_with_parameter() {
     // Explicitly pass timeout parameter (5 seconds)
-    let executor = NushellExecutor;
+    let executor = NushellExecutor::default();
     let work_dir = env::current_dir().unwrap();
 
     // Command that takes 10 seconds should timeout with 5s limit
@@ -50,7 +50,7 @@ async fn test_nushell_executor_timeout_with_parameter() {
 
 #[tokio::test]
 async fn test_nushell_executor_stderr_preserved() {
-    let executor = NushellExecutor;
+    let executor = NushellExecutor::default();
     let work_dir = env::current_dir().unwrap();
 
     // Command that outputs to stderr
@@ -62,3 +62,167 @@ async fn test_nushell_executor_stderr_preserved() {
     let (_stdout, stderr) = result.unwrap();
     assert!(stderr.contains("error message"));
 }
+
+#[tokio::test]
+async fn test_nushell_executor_landlock_confines_commands() {
+    use crate::execution::ChildConfig;
+    use crate::security::{Landlock, LandlockPaths};
+
+    let dir = |name: &str| {
+        let dir = env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    };
+    let sandbox = dir("nu_mcp_landlock_executor");
+    let outside = dir("nu_mcp_landlock_executor_outside");
+    std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+
+    let Some(landlock) =
+        Landlock::new(&LandlockPaths::new(std::slice::from_ref(&sandbox), &[])).unwrap()
+    else {
+        eprintln!("Skipping: Landlock is not supported here");
+        return;
+    };
+    let executor = NushellExecutor::new(ChildConfig {
+        landlock: Some(landlock),
+        ..Default::default()
+    });
+
+    let (stdout, _stderr) = executor
+        .execute("'inside' | save out.txt; open out.txt", &sandbox, None)
+        .await
+        .unwrap();
+    assert!(stdout.contains("inside"), "{}", stdout);
+
+    // A path built at runtime gets past path validation, but not the kernel
+    let command = format!("open ('{}' | path join secret.txt)", outside.display());
+    let output = executor
+        .execute_with_status(&command, &sandbox, None)
+        .await
+        .unwrap();
+    assert_ne!(output.exit_code, Some(0), "{:?}", output);
+    assert!(output.stderr.contains("Permission denied"), "{:?}", output);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_nushell_executor_reports_resource_limit() {
+    use crate::execution::{ChildConfig, ResourceLimits};
+
+    let work_dir = env::temp_dir().join("nu_mcp_limits_executor");
+    std::fs::create_dir_all(&work_dir).unwrap();
+    let executor = NushellExecutor::new(ChildConfig {
+        limits: ResourceLimits {
+            file_size_bytes: Some(1024 * 1024),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    let output = executor
+        .execute_with_status(
+            "^sh -c 'head -c 2000000 /dev/zero > big.bin'",
+            &work_dir,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_ne!(output.exit_code, Some(0));
+    assert!(
+        output
+            .stderr
+            .contains("[stopped by resource limit: file size limit of 1 MB]"),
+        "{:?}",
+        output
+    );
+}
+
+/// True if the process exists and isn't a zombie
+#[cfg(target_os = "linux")]
+fn is_running(pid_file: &std::path::Path) -> bool {
+    let pid = std::fs::read_to_string(pid_file).unwrap();
+    std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+        .map(|stat| {
+            !stat
+                .rsplit_once(')')
+                .unwrap()
+                .1
+                .trim_start()
+                .starts_with('Z')
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_nushell_executor_timeout_kills_process_group() {
+    let work_dir = env::temp_dir().join("nu_mcp_group_timeout");
+    std::fs::create_dir_all(&work_dir).unwrap();
+    let pid_file = work_dir.join("sleep.pid");
+    let _ = std::fs::remove_file(&pid_file);
+
+    let result = NushellExecutor::default()
+        .execute(
+            "^sh -c 'echo $$ > sleep.pid; exec sleep 60'",
+            &work_dir,
+            Some(1),
+        )
+        .await;
+    let err = result.unwrap_err();
+    assert!(err.contains("timed out after 1 seconds"), "{}", err);
+    // The external exited on SIGTERM, so there is nothing to report
+    assert!(!err.contains("SIGKILL"), "{}", err);
+    assert!(!is_running(&pid_file));
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_nushell_executor_timeout_reports_killed_processes() {
+    let work_dir = env::temp_dir().join("nu_mcp_group_ignore_term");
+    std::fs::create_dir_all(&work_dir).unwrap();
+    let pid_file = work_dir.join("sh.pid");
+    let _ = std::fs::remove_file(&pid_file);
+
+    let result = NushellExecutor::default()
+        .execute(
+            "^sh -c 'trap \"\" TERM; echo $$ > sh.pid; while :; do sleep 0.1; done'",
+            &work_dir,
+            Some(1),
+        )
+        .await;
+    let err = result.unwrap_err();
+    assert!(err.contains("killed after ignoring SIGTERM:"), "{}", err);
+    assert!(err.contains("(sh)"), "{}", err);
+    assert!(!is_running(&pid_file));
+}
+
+#[tokio::test]
+async fn test_nushell_executor_env_policy() {
+    use crate::execution::{ChildConfig, EnvMode, EnvPolicy};
+
+    let executor = NushellExecutor::new(ChildConfig {
+        env: EnvPolicy {
+            mode: EnvMode::Denylist,
+            vars: vec!["HOME".to_string()],
+            set: [("NU_MCP_GREETING".to_string(), "hi".to_string())].into(),
+        },
+        ..Default::default()
+    });
+    let work_dir = env::current_dir().unwrap();
+
+    let (stdout, stderr) = executor
+        .execute(
+            "print ($env.HOME? | default 'unset') $env.NU_MCP_GREETING",
+            &work_dir,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["unset", "hi"],
+        "{}",
+        stderr
+    );
+}
@@ -8,12 +8,18 @@
 //!
 //! Wire format: ESC ] 133 ; <cmd> [; <params>] ST
 //! where ST is either BEL (0x07) or ESC \ (0x1B 0x5C)
+//!
+//! The parser also records the working directory the shell reports with
+//! OSC 7 (`ESC ] 7 ; file://<host><path> ST`) before each prompt.
+
+use std::path::{Path, PathBuf};
 
 const ESC: u8 = 0x1B;
 const BEL: u8 = 0x07;
 const BACKSLASH: u8 = b'\\';
 const RIGHT_BRACKET: u8 = b']';
-const PARAM_BUF_CAP: usize = 32;
+/// Room for an OSC 7 URL with a path up to PATH_MAX
+const PARAM_BUF_CAP: usize = 4096 + 64;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum State {
@@ -54,8 +60,12 @@ pub enum Zone {
 pub struct Parser {
     state: State,
     zone: Zone,
-    param_buf: [u8; PARAM_BUF_CAP],
+    param_buf: Box<[u8; PARAM_BUF_CAP]>,
     param_len: usize,
+    /// The sequence was longer than the buffer
+    param_overflow: bool,
+    /// Last working directory reported with OSC 7
+    working_dir: Option<PathBuf>,
 }
 
 impl Parser {
@@ -64,8 +74,10 @@ impl Parser {
         Self {
             state: State::Ground,
             zone: Zone::Unknown,
-            param_buf: [0u8; PARAM_BUF_CAP],
+            param_buf: Box::new([0u8; PARAM_BUF_CAP]),
             param_len: 0,
+            param_overflow: false,
+            working_dir: None,
         }
     }
 
@@ -74,6 +86,11 @@ impl Parser {
         self.zone
     }
 
+    /// The working directory from the last OSC 7 report, if the shell sent one
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
     /// Process a chunk of bytes, calling callback for each OSC 133 event detected
     pub fn push(&mut self, data: &[u8], mut on_event: impl FnMut(Event)) {
         for &byte in data {
@@ -87,6 +104,7 @@ impl Parser {
                     if byte == RIGHT_BRACKET {
                         self.state = State::OscParam;
                         self.param_len = 0;
+                        self.param_overflow = false;
                     } else {
                         self.state = State::Ground;
                     }
@@ -100,6 +118,8 @@ impl Parser {
                     } else if self.param_len < PARAM_BUF_CAP {
                         self.param_buf[self.param_len] = byte;
                         self.param_len += 1;
+                    } else {
+                        self.param_overflow = true;
                     }
                 }
                 State::OscEsc => {
@@ -115,6 +135,15 @@ impl Parser {
     fn dispatch(&mut self, on_event: &mut impl FnMut(Event)) {
         let params = &self.param_buf[..self.param_len];
 
+        if let Some(url) = params.strip_prefix(b"7;") {
+            // A truncated path would be wrong, so forget the old one instead
+            self.working_dir = match self.param_overflow {
+                true => None,
+                false => parse_file_url(url),
+            };
+            return;
+        }
+
         // Must start with "133;"
         if params.len() < 5 || &params[..4] != b"133;" {
             return;
@@ -152,6 +181,33 @@ impl Parser {
     }
 }
 
+/// The path of a `file://host/path` URL, percent-decoded
+fn parse_file_url(url: &[u8]) -> Option<PathBuf> {
+    let rest = url.strip_prefix(b"file://")?;
+    let path = &rest[rest.iter().position(|&byte| byte == b'/')?..];
+
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let hex = [*bytes.next()?, *bytes.next()?];
+        decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Some(PathBuf::from(std::ffi::OsString::from_vec(decoded)))
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8(decoded).ok().map(PathBuf::from)
+    }
+}
+
 impl Default for Parser {
     fn default() -> Self {
         Self::new()
@@ -303,4 +359,29 @@ mod tests {
 
         assert_eq!(events, vec![Event::PromptStart]);
     }
+
+    #[test]
+    fn test_working_directory() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.working_directory(), None);
+
+        let mut events = Vec::new();
+        parser.push(b"\x1b]7;file://host/tmp/my%20dir\x1b\\", |e| events.push(e));
+
+        assert!(events.is_empty());
+        assert_eq!(parser.working_directory(), Some(Path::new("/tmp/my dir")));
+    }
+
+    #[test]
+    fn test_truncated_working_directory_is_forgotten() {
+        let mut parser = Parser::new();
+        parser.push(b"\x1b]7;file://host/tmp\x07", |_| {});
+
+        let mut long = b"\x1b]7;file://host/".to_vec();
+        long.extend(std::iter::repeat_n(b'a', PARAM_BUF_CAP));
+        long.push(BEL);
+        parser.push(&long, |_| {});
+
+        assert_eq!(parser.working_directory(), None);
+    }
 }
@@ -5,19 +5,28 @@
 //! The overhead is negligible for a long-lived, I/O-bound object.
 //! Can be optimized later with platform-specific code if needed.
 
-use super::CommandExecutor;
+use super::input_wait;
 use super::osc133;
+use super::{ChildConfig, CommandExecutor, ExecutionOutput, RunningCommand};
 use portable_pty::{Child, ChildKiller, CommandBuilder, MasterPty, PtySize, native_pty_system};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, TryLockError, mpsc};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 const BUFFER_SIZE: usize = 8192;
 const STARTUP_TIMEOUT_SECS: u64 = 10;
 /// Max queued PTY read chunks before the reader thread blocks (backpressure).
 /// 64 chunks * 8KB = 512KB max buffered data.
 const CHANNEL_CAPACITY: usize = 64;
+/// How often the reader channel is polled while a command produces no output.
+const IDLE_POLL: Duration = Duration::from_millis(250);
+/// How long a command must be quiet before checking whether it waits for input.
+const INPUT_IDLE_THRESHOLD: Duration = Duration::from_millis(750);
+/// How long to wait for the child to be reaped after the PTY closes.
+const EXIT_GRACE: Duration = Duration::from_millis(500);
 
 /// DSR (Device Status Report) sequence: ESC [ 6 n
 /// Reedline/crossterm sends this to query cursor position.
@@ -27,6 +36,16 @@ const DSR_SEQUENCE: &[u8] = b"\x1b[6n";
 /// Reedline uses this to set prompt_start_row = 0.
 const CPR_RESPONSE: &[u8] = b"\x1b[1;1R";
 
+/// Append a line to the PTY trace file, if tracing is enabled
+macro_rules! trace_log {
+    ($file:expr, $($arg:tt)*) => {
+        if let Some(f) = $file.as_mut() {
+            let _ = writeln!(f, $($arg)*);
+            let _ = f.flush();
+        }
+    };
+}
+
 /// Messages sent from the background reader thread to the main thread.
 enum PtyRead {
     Data(Vec<u8>),
@@ -38,8 +57,14 @@ pub struct PersistentShell {
     writer: Box<dyn Write + Send>,
     osc_parser: osc133::Parser,
     reader_rx: mpsc::Receiver<PtyRead>,
-    _master: Box<dyn MasterPty + Send>,
+    master: Box<dyn MasterPty + Send>,
     child: Box<dyn Child + Send + Sync>,
+    /// Command that returned early while waiting for input and is still running
+    pending: Option<PendingCommand>,
+    /// Output of the running command, readable without holding the shell
+    monitor: CommandMonitor,
+    /// Confinement the shell was started with; its CPU limit is per command
+    child_config: ChildConfig,
 }
 
 impl Drop for PersistentShell {
@@ -52,7 +77,7 @@ impl Drop for PersistentShell {
         if let Err(e) = self.child.wait() {
             eprintln!("Failed to wait on shell process: {e}");
         }
-        // reader thread exits naturally when _master is dropped (PTY fd closes)
+        // reader thread exits naturally when master is dropped (PTY fd closes)
         // and reader_rx is dropped (tx.send fails)
     }
 }
@@ -60,6 +85,17 @@ impl Drop for PersistentShell {
 impl PersistentShell {
     /// Create a new persistent Nushell process
     pub fn new() -> Result<Self, String> {
+        Self::with_monitor(CommandMonitor::default(), &ChildConfig::default())
+    }
+
+    /// Create a new persistent Nushell process, confined by `child`, that
+    /// reports its running command to an existing monitor (kept across resets)
+    pub fn with_monitor(
+        monitor: CommandMonitor,
+        child_config: &ChildConfig,
+    ) -> Result<Self, String> {
+        monitor.finish();
+
         let pty_system = native_pty_system();
         let (rows, cols) = (24, 80);
 
@@ -74,17 +110,23 @@ impl PersistentShell {
 
         let mut cmd = CommandBuilder::new("nu");
         cmd.cwd(std::env::current_dir().map_err(|e| e.to_string())?);
+        child_config.apply_env_to_pty(&mut cmd);
 
         cmd.env("TERM", "xterm-256color");
         cmd.env("COLORTERM", "truecolor");
         cmd.env("NO_COLOR", "1");
 
-        let child = pair
-            .slave
-            .spawn_command(cmd)
+        // portable-pty has no pre-exec hook, so confine the spawning thread instead
+        let slave = pair.slave;
+        let mut child = child_config
+            .spawn_confined(move || slave.spawn_command(cmd))?
             .map_err(|e| format!("Failed to spawn nu: {}", e))?;
-
-        drop(pair.slave);
+        if let Some(pid) = child.process_id()
+            && let Err(e) = child_config.limit_process(pid)
+        {
+            let _ = child.kill();
+            return Err(e);
+        }
 
         let master = pair.master;
         let writer = master
@@ -121,11 +163,17 @@ impl PersistentShell {
             writer,
             osc_parser: osc133::Parser::new(),
             reader_rx: rx,
-            _master: master,
+            master,
             child,
+            pending: None,
+            monitor,
+            child_config: child_config.clone(),
         };
 
         shell.wait_for_prompt(Duration::from_secs(STARTUP_TIMEOUT_SECS))?;
+        shell
+            .monitor
+            .set_working_dir(shell.osc_parser.working_directory());
 
         Ok(shell)
     }
@@ -136,6 +184,37 @@ impl PersistentShell {
         self.child.process_id()
     }
 
+    /// Describe how the child Nushell process ended, or `None` if it is still running
+    pub fn exit_reason(&mut self) -> Option<String> {
+        match self.child.try_wait() {
+            Ok(Some(status)) => Some(match status.signal() {
+                Some(signal) => format!("was terminated by {}", signal),
+                None => format!("exited with code {}", status.exit_code()),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Like `exit_reason`, but gives the child up to `grace` to finish exiting.
+    /// The PTY can report EOF slightly before the process is reaped.
+    pub fn wait_exit_reason(&mut self, grace: Duration) -> Option<String> {
+        let deadline = Instant::now() + grace;
+        loop {
+            if let Some(reason) = self.exit_reason() {
+                return Some(reason);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Get a handle to the monitor tracking the running command
+    pub fn monitor(&self) -> CommandMonitor {
+        self.monitor.clone()
+    }
+
     /// Clone the child killer handle for signaling from another thread.
     /// This enables reset() to kill a running command without blocking on the shell mutex.
     pub fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
@@ -161,8 +240,21 @@ impl PersistentShell {
     fn drain_until<F>(&mut self, timeout: Duration, mut handler: F) -> Result<(), String>
     where
         F: FnMut(&mut Self, &[u8]) -> ControlFlow,
+    {
+        self.drain_until_idle(timeout, |shell, data, _idle| match data {
+            Some(data) => handler(shell, data),
+            None => ControlFlow::Continue,
+        })
+    }
+
+    /// Like `drain_until`, but also calls the handler with `None` every `IDLE_POLL`
+    /// without data, passing how long the PTY has been quiet.
+    fn drain_until_idle<F>(&mut self, timeout: Duration, mut handler: F) -> Result<(), String>
+    where
+        F: FnMut(&mut Self, Option<&[u8]>, Duration) -> ControlFlow,
     {
         let deadline = std::time::Instant::now() + timeout;
+        let mut last_data = std::time::Instant::now();
 
         loop {
             let remaining = deadline
@@ -173,16 +265,19 @@ impl PersistentShell {
                 return Err(format!("Timeout after {} seconds", timeout.as_secs()));
             }
 
-            match self.reader_rx.recv_timeout(remaining) {
+            match self.reader_rx.recv_timeout(remaining.min(IDLE_POLL)) {
                 Ok(PtyRead::Data(data)) => {
-                    if let ControlFlow::Break = handler(self, &data) {
+                    last_data = std::time::Instant::now();
+                    if let ControlFlow::Break = handler(self, Some(&data), Duration::ZERO) {
                         return Ok(());
                     }
                 }
                 Ok(PtyRead::Eof) => return Err("PTY EOF".to_string()),
                 Ok(PtyRead::Error(e)) => return Err(format!("PTY read error: {}", e)),
                 Err(mpsc::RecvTimeoutError::Timeout) => {
-                    return Err(format!("Timeout after {} seconds", timeout.as_secs()));
+                    if let ControlFlow::Break = handler(self, None, last_data.elapsed()) {
+                        return Ok(());
+                    }
                 }
                 Err(mpsc::RecvTimeoutError::Disconnected) => {
                     return Err("PTY reader disconnected".to_string());
@@ -191,6 +286,24 @@ impl PersistentShell {
         }
     }
 
+    /// Check whether the running command is blocked waiting for terminal input.
+    /// Returns a short reason when it is.
+    fn detect_input_wait(&self, output: &[u8], alt_screen: bool) -> Option<String> {
+        if alt_screen {
+            return Some("entered the alternate screen (pager or full-screen program)".to_string());
+        }
+        if let Some(prompt) = input_wait::trailing_prompt(output) {
+            return Some(format!("prompt detected: {:?}", prompt));
+        }
+        #[cfg(unix)]
+        if let Some(pid) = self.master.process_group_leader()
+            && input_wait::is_blocked_on_tty_read(pid)
+        {
+            return Some("process is blocked reading from the terminal".to_string());
+        }
+        None
+    }
+
     fn wait_for_prompt(&mut self, timeout: Duration) -> Result<(), String> {
         let mut got_marker = false;
 
@@ -222,75 +335,154 @@ impl PersistentShell {
     /// 1. Write the command — Reedline processes it and returns to Nushell
     /// 2. Wait for C (CommandExecuted) — Nushell is about to run the command
     /// 3. Collect output between C and D (CommandFinished)
+    ///
+    /// If the command goes quiet while blocked on terminal input (pager, prompt,
+    /// full-screen program), we return early with `CommandStatus::WaitingForInput`
+    /// and the output so far. The shell stays busy until the command is answered
+    /// via `send_input()` or the shell is reset.
     pub fn execute(&mut self, command: &str, timeout: Duration) -> Result<CommandOutput, String> {
-        // Trace file for debugging (only when MCP_PTY_TRACE is set)
-        let trace = std::env::var("MCP_PTY_TRACE").is_ok();
-        let mut trace_file = if trace {
-            use std::fs::OpenOptions;
-            OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("/tmp/pty_trace.log")
-                .ok()
-        } else {
-            None
-        };
-
-        macro_rules! trace_log {
-            ($($arg:tt)*) => {
-                if let Some(ref mut f) = trace_file {
-                    use std::io::Write;
-                    let _ = writeln!(f, $($arg)*);
-                    let _ = f.flush();
-                }
-            };
+        if let Some(pending) = &self.pending {
+            return Err(format!(
+                "Shell is still running '{}', which is waiting for input. Answer it with the shell_input tool or send reset=true to abort it.",
+                pending.command
+            ));
         }
 
-        trace_log!("=== EXECUTE: {:?} ===", command);
+        let mut trace_file = open_trace_file();
+        trace_log!(trace_file, "=== EXECUTE: {:?} ===", command);
 
         // Establish single deadline for entire operation (command execution + prompt wait)
         let deadline = std::time::Instant::now() + timeout;
 
+        // CPU time is counted over the shell's life; give this command the full limit
+        if let Some(pid) = self.child.process_id() {
+            self.child_config.limit_next_command(pid)?;
+        }
+
         // Write command — Reedline is in event::read(), ready for input
         writeln!(self.writer, "{}", command).map_err(|e| format!("Write failed: {}", e))?;
         self.writer
             .flush()
             .map_err(|e| format!("Flush failed: {}", e))?;
 
-        // Wait for C→D, respond to DSR during prompt rendering phase
-        let mut output_buffer = Vec::new();
+        let pending = PendingCommand {
+            command: command.to_string(),
+            alt_screen: false,
+        };
+        self.monitor.start(command);
+        self.collect_output(pending, false, deadline, &mut trace_file)
+    }
+
+    /// Write input to a command that is waiting for it, then collect the output
+    /// it produces until it finishes or blocks on input again.
+    pub fn send_input(&mut self, input: &[u8], timeout: Duration) -> Result<CommandOutput, String> {
+        let Some(pending) = self.pending.take() else {
+            return Err(
+                "No command is waiting for input. Input can only be sent after a shell call reports that its command is waiting for input."
+                    .to_string(),
+            );
+        };
+
+        let mut trace_file = open_trace_file();
+        trace_log!(
+            trace_file,
+            "=== INPUT: {:?} ===",
+            String::from_utf8_lossy(input)
+        );
+
+        let deadline = std::time::Instant::now() + timeout;
+
+        let written = self
+            .writer
+            .write_all(input)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("Write failed: {}", e));
+        if let Err(e) = written {
+            self.pending = Some(pending);
+            return Err(e);
+        }
+
+        self.monitor.resume();
+        let result = self.collect_output(pending.clone(), true, deadline, &mut trace_file);
+        // A failed read leaves the command running; keep the shell marked busy
+        if result.is_err() && self.pending.is_none() {
+            self.pending = Some(pending);
+        }
+        result
+    }
+
+    /// Collect output of the running command until OSC 133 D, or until it
+    /// blocks on terminal input. `saw_command_executed` is true when resuming
+    /// a command that already passed C. Output is buffered in the monitor so
+    /// it can be peeked while collection is in progress.
+    fn collect_output(
+        &mut self,
+        pending: PendingCommand,
+        mut saw_command_executed: bool,
+        deadline: std::time::Instant,
+        trace_file: &mut Option<std::fs::File>,
+    ) -> Result<CommandOutput, String> {
+        let timeout = deadline.saturating_duration_since(std::time::Instant::now());
+
+        // Wait for C→D, respond to DSR during prompt rendering phase.
+        // While the command is quiet, check whether it is blocked on input.
         let mut final_exit_code: Option<i32> = None;
-        let mut saw_command_executed = false;
+        let mut alt_screen = pending.alt_screen;
+        let mut waiting_reason: Option<String> = None;
 
-        self.drain_until(timeout, |shell, data| {
-            trace_log!("CHUNK len={} saw_c={}", data.len(), saw_command_executed,);
+        self.drain_until_idle(timeout, |shell, data, idle| {
+            let Some(data) = data else {
+                if saw_command_executed && idle >= INPUT_IDLE_THRESHOLD {
+                    shell.monitor.with_output(|output| {
+                        alt_screen = input_wait::alternate_screen_switch(output)
+                            .unwrap_or(pending.alt_screen);
+                        waiting_reason = shell.detect_input_wait(output, alt_screen);
+                    });
+                }
+                return if waiting_reason.is_some() {
+                    ControlFlow::Break
+                } else {
+                    ControlFlow::Continue
+                };
+            };
+
+            trace_log!(
+                trace_file,
+                "CHUNK len={} saw_c={}",
+                data.len(),
+                saw_command_executed,
+            );
 
             // Respond to DSR during prompt phase (before C)
             if !saw_command_executed {
                 shell.respond_to_dsr(data);
             }
 
-            // Collect output bytes only after C
-            if saw_command_executed {
-                output_buffer.extend_from_slice(data);
-            }
-
+            // Feed the parser byte by byte to find where C ends: output often
+            // arrives in the same chunk as the C marker
+            let mut output_start = saw_command_executed.then_some(0);
             let mut done = false;
-            shell.osc_parser.push(data, |event| {
-                trace_log!("  EVENT: {:?}", event);
-                match event {
-                    osc133::Event::CommandExecuted => {
-                        saw_command_executed = true;
-                    }
-                    osc133::Event::CommandFinished { exit_code } => {
-                        if saw_command_executed {
+            for (offset, byte) in data.iter().enumerate() {
+                shell.osc_parser.push(std::slice::from_ref(byte), |event| {
+                    trace_log!(trace_file, "  EVENT: {:?}", event);
+                    match event {
+                        osc133::Event::CommandExecuted => {
+                            saw_command_executed = true;
+                            output_start.get_or_insert(offset + 1);
+                        }
+                        osc133::Event::CommandFinished { exit_code } if saw_command_executed => {
                             final_exit_code = exit_code;
                             done = true;
                         }
+                        _ => {}
                     }
-                    _ => {}
-                }
-            });
+                });
+            }
+
+            // Collect output bytes only after C
+            if let Some(start) = output_start {
+                shell.monitor.append(&data[start..]);
+            }
 
             if done {
                 ControlFlow::Break
@@ -300,9 +492,29 @@ impl PersistentShell {
         })?;
 
         // Strip ANSI escape codes
-        let clean = strip_ansi_escapes::strip(&output_buffer);
+        let clean = self
+            .monitor
+            .with_output(|output| strip_ansi_escapes::strip(output));
         let stdout = String::from_utf8_lossy(&clean).trim().to_string();
 
+        // The command is still running; return what it printed so far and leave
+        // the shell marked busy until it is answered or reset.
+        if let Some(reason) = waiting_reason {
+            trace_log!(trace_file, "=== WAITING FOR INPUT: {} ===", reason);
+            self.monitor.set_waiting();
+            self.pending = Some(PendingCommand {
+                alt_screen,
+                ..pending
+            });
+            return Ok(CommandOutput {
+                stdout,
+                exit_code: 0,
+                status: CommandStatus::WaitingForInput { reason },
+            });
+        }
+
+        self.monitor.finish();
+
         // After D, the next prompt cycle starts (DSR queries → A → B).
         // Drain until B (CommandStart) so Reedline is back at event::read()
         // before we return. This prevents CPR response bytes from leaking
@@ -335,12 +547,20 @@ impl PersistentShell {
             }
         });
 
+        // The prompt reports the working directory (OSC 7) after a `cd`
+        self.monitor
+            .set_working_dir(self.osc_parser.working_directory());
+
         // If prompt wait timed out, log warning but don't fail - output was collected
         if prompt_wait_result.is_err() && !saw_next_ready {
-            trace_log!("WARNING: Prompt wait timed out - shell may need re-sync on next command");
+            trace_log!(
+                trace_file,
+                "WARNING: Prompt wait timed out - shell may need re-sync on next command"
+            );
         }
 
         trace_log!(
+            trace_file,
             "=== RETURNING stdout={:?} exit={:?} ===",
             stdout,
             final_exit_code
@@ -349,10 +569,125 @@ impl PersistentShell {
         Ok(CommandOutput {
             stdout,
             exit_code: final_exit_code.unwrap_or(0),
+            status: CommandStatus::Completed,
+        })
+    }
+}
+
+/// A command that returned early while waiting for input and is still running
+#[derive(Clone)]
+struct PendingCommand {
+    command: String,
+    /// Whether the command left the terminal in the alternate screen
+    alt_screen: bool,
+}
+
+/// Shared view of the command currently running in a shell, and of the
+/// shell's working directory.
+///
+/// `collect_output()` appends to it while holding the shell; `snapshot()` reads
+/// it from other threads without waiting for the shell mutex.
+#[derive(Clone, Default)]
+pub struct CommandMonitor {
+    current: Arc<Mutex<Option<MonitoredCommand>>>,
+    working_dir: Arc<Mutex<Option<PathBuf>>>,
+}
+
+struct MonitoredCommand {
+    command: String,
+    started: Instant,
+    output: Vec<u8>,
+    waiting_for_input: bool,
+}
+
+impl CommandMonitor {
+    /// Start tracking a newly submitted command
+    fn start(&self, command: &str) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = Some(MonitoredCommand {
+                command: command.to_string(),
+                started: Instant::now(),
+                output: Vec::new(),
+                waiting_for_input: false,
+            });
+        }
+    }
+
+    /// Resume a command after input was sent; output restarts from empty
+    fn resume(&self) {
+        if let Ok(mut current) = self.current.lock()
+            && let Some(running) = current.as_mut()
+        {
+            running.output.clear();
+            running.waiting_for_input = false;
+        }
+    }
+
+    fn append(&self, data: &[u8]) {
+        if let Ok(mut current) = self.current.lock()
+            && let Some(running) = current.as_mut()
+        {
+            running.output.extend_from_slice(data);
+        }
+    }
+
+    fn with_output<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+        match self.current.lock() {
+            Ok(current) => f(current.as_ref().map_or(&[], |running| &running.output)),
+            Err(_) => f(&[]),
+        }
+    }
+
+    fn set_waiting(&self) {
+        if let Ok(mut current) = self.current.lock()
+            && let Some(running) = current.as_mut()
+        {
+            running.waiting_for_input = true;
+        }
+    }
+
+    /// Stop tracking: the command finished or the shell was replaced
+    fn finish(&self) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = None;
+        }
+    }
+
+    fn set_working_dir(&self, dir: Option<&Path>) {
+        if let Ok(mut working_dir) = self.working_dir.lock() {
+            *working_dir = dir.map(Path::to_path_buf);
+        }
+    }
+
+    /// The working directory the shell last reported at its prompt
+    pub fn working_dir(&self) -> Option<PathBuf> {
+        self.working_dir.lock().ok()?.clone()
+    }
+
+    /// Copy of the running command's state, or `None` if the shell is idle
+    pub fn snapshot(&self) -> Option<RunningCommand> {
+        let current = self.current.lock().ok()?;
+        current.as_ref().map(|running| RunningCommand {
+            command: running.command.clone(),
+            elapsed: running.started.elapsed(),
+            output: String::from_utf8_lossy(&strip_ansi_escapes::strip(&running.output))
+                .trim()
+                .to_string(),
+            waiting_for_input: running.waiting_for_input,
         })
     }
 }
 
+/// Open the PTY trace file for debugging (only when MCP_PTY_TRACE is set)
+fn open_trace_file() -> Option<std::fs::File> {
+    std::env::var("MCP_PTY_TRACE").ok()?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open("/tmp/pty_trace.log")
+        .ok()
+}
+
 /// Control flow for drain_until handler
 enum ControlFlow {
     Continue,
@@ -363,47 +698,228 @@ enum ControlFlow {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CommandOutput {
     pub stdout: String,
+    /// Exit code reported by OSC 133 D (0 while the command is still running)
     pub exit_code: i32,
+    pub status: CommandStatus,
+}
+
+/// How far a command got before `execute()` returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandStatus {
+    /// The command finished and the shell is back at its prompt
+    Completed,
+    /// The command is still running, blocked waiting for terminal input
+    WaitingForInput { reason: String },
+}
+
+impl CommandOutput {
+    /// Render the output for the agent, noting when the command still waits for input
+    pub fn into_text(self) -> String {
+        match self.status {
+            CommandStatus::Completed => self.stdout,
+            CommandStatus::WaitingForInput { reason } => {
+                let notice = format!(
+                    "[command is waiting for input: {}] The command is still running in the shell. Answer it with the shell_input tool or send reset=true to abort it.",
+                    reason
+                );
+                if self.stdout.is_empty() {
+                    notice
+                } else {
+                    format!("{}\n\n{}", self.stdout, notice)
+                }
+            }
+        }
+    }
 }
 
 /// Async executor that wraps a persistent Nushell shell.
 /// Implements `CommandExecutor` so it can be swapped in for `NushellExecutor`.
-/// Uses try_lock() to reject concurrent execute() calls with a clear error.
+/// Uses try_lock() to reject concurrent execute() calls with a clear error,
+/// unless a CommandQueue is configured, in which case they wait in FIFO order.
 /// Uses clone_killer() for forcible reset while a command is running.
+/// Uses a shared CommandMonitor so the running command can be peeked while busy.
+/// Replaces the shell automatically when its process has died.
 #[derive(Clone)]
 pub struct PersistentNuExecutor {
     shell: Arc<Mutex<PersistentShell>>,
     killer: Arc<Mutex<Box<dyn ChildKiller + Send + Sync>>>,
+    monitor: CommandMonitor,
+    queue: Option<CommandQueue>,
+    restarts: Arc<AtomicUsize>,
+    /// Confinement for every shell this executor starts
+    child: ChildConfig,
 }
 
 impl PersistentNuExecutor {
     pub fn new() -> Result<Self, String> {
-        let shell = PersistentShell::new()?;
+        Self::with_child_config(ChildConfig::default())
+    }
+
+    /// An executor whose shells are confined by `child`
+    pub fn with_child_config(child: ChildConfig) -> Result<Self, String> {
+        let shell = PersistentShell::with_monitor(CommandMonitor::default(), &child)?;
         let killer = shell.clone_killer();
+        let monitor = shell.monitor();
         Ok(Self {
             shell: Arc::new(Mutex::new(shell)),
             killer: Arc::new(Mutex::new(killer)),
+            monitor,
+            queue: None,
+            restarts: Arc::new(AtomicUsize::new(0)),
+            child,
         })
     }
+
+    /// Number of times a dead shell was replaced automatically
+    pub fn restart_count(&self) -> usize {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    /// Replace the shell if its process has exited (`exit`, a crash, a signal).
+    /// Returns a notice for the agent when it did, since session state is gone.
+    fn revive(&self, shell: &mut PersistentShell) -> Result<Option<String>, String> {
+        let Some(reason) = shell.exit_reason() else {
+            return Ok(None);
+        };
+
+        let new_shell = PersistentShell::with_monitor(self.monitor.clone(), &self.child)
+            .map_err(|e| format!("The shell {} and could not be restarted: {}", reason, e))?;
+        let new_killer = new_shell.clone_killer();
+        *shell = new_shell;
+
+        let mut killer = self
+            .killer
+            .lock()
+            .map_err(|_| "Killer mutex poisoned".to_string())?;
+        *killer = new_killer;
+
+        let restarts = self.restarts.fetch_add(1, Ordering::SeqCst) + 1;
+        tracing::warn!(
+            "Persistent shell {}; restarted it (restart #{})",
+            reason,
+            restarts
+        );
+
+        Ok(Some(format!(
+            "[shell restarted: the previous shell {}. Session state (variables, environment, aliases, working directory) was lost. Restart #{}]",
+            reason, restarts
+        )))
+    }
+
+    /// Queue concurrent execute() calls instead of rejecting them.
+    /// At most `depth` calls wait at once, each for at most `wait_timeout`.
+    /// A depth of 0 keeps the reject-when-busy behavior.
+    pub fn with_queue(mut self, depth: usize, wait_timeout: Duration) -> Self {
+        self.queue = (depth > 0).then(|| CommandQueue::new(depth, wait_timeout));
+        self
+    }
+}
+
+/// Bounded FIFO queue of execute() calls waiting for the shell.
+/// The single semaphore permit is the shell's turn; tokio hands it to
+/// waiters in the order they asked for it.
+#[derive(Clone)]
+pub(crate) struct CommandQueue {
+    turn: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    depth: usize,
+    wait_timeout: Duration,
+}
+
+impl CommandQueue {
+    pub(crate) fn new(depth: usize, wait_timeout: Duration) -> Self {
+        Self {
+            turn: Arc::new(Semaphore::new(1)),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            depth,
+            wait_timeout,
+        }
+    }
+
+    /// Wait for this call's turn at the shell
+    pub(crate) async fn wait_turn(&self) -> Result<OwnedSemaphorePermit, String> {
+        if let Ok(permit) = Arc::clone(&self.turn).try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let _slot = QueueSlot::claim(&self.waiting, self.depth).ok_or_else(|| {
+            format!(
+                "Shell queue is full ({} commands already waiting). Wait for earlier commands to finish, or use the 'run' tool for independent commands.",
+                self.depth
+            )
+        })?;
+
+        match tokio::time::timeout(self.wait_timeout, Arc::clone(&self.turn).acquire_owned()).await
+        {
+            Ok(Ok(permit)) => Ok(permit),
+            Ok(Err(_)) => Err("Shell queue closed".to_string()),
+            Err(_) => Err(format!(
+                "Command waited {} seconds in the shell queue without getting a turn; the shell is still busy with earlier commands.",
+                self.wait_timeout.as_secs()
+            )),
+        }
+    }
+}
+
+/// A claimed place in the queue, released on drop (also when the caller is cancelled)
+struct QueueSlot<'a> {
+    waiting: &'a AtomicUsize,
+}
+
+impl<'a> QueueSlot<'a> {
+    fn claim(waiting: &'a AtomicUsize, depth: usize) -> Option<Self> {
+        waiting
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < depth).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self { waiting })
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl CommandExecutor for PersistentNuExecutor {
     async fn execute(
         &self,
         command: &str,
-        _working_dir: &Path,
+        working_dir: &Path,
         timeout_secs: Option<u64>,
     ) -> Result<(String, String), String> {
+        let output = self
+            .execute_with_status(command, working_dir, timeout_secs)
+            .await?;
+        Ok((output.stdout, output.stderr))
+    }
+
+    async fn execute_with_status(
+        &self,
+        command: &str,
+        _working_dir: &Path,
+        timeout_secs: Option<u64>,
+    ) -> Result<ExecutionOutput, String> {
         let timeout = Duration::from_secs(timeout_secs.unwrap_or_else(super::get_default_timeout));
         let command = command.to_string();
-        let shell = Arc::clone(&self.shell);
+        let this = self.clone();
+
+        // With a queue, wait for our turn before touching the shell
+        let turn = match &self.queue {
+            Some(queue) => Some(queue.wait_turn().await?),
+            None => None,
+        };
 
         // The shell does blocking I/O (PTY reads via recv_timeout).
         // Must run on a blocking thread to avoid starving the tokio runtime,
         // which needs to stay responsive for MCP protocol heartbeats.
-        let result = tokio::task::spawn_blocking(move || {
+        let (restarted, result) = tokio::task::spawn_blocking(move || {
+            // Hold the turn until the shell is released, even if the caller is cancelled
+            let _turn = turn;
             // Use try_lock to reject concurrent calls
-            let mut guard = shell.try_lock().map_err(|e| match e {
+            let mut guard = this.shell.try_lock().map_err(|e| match e {
                 TryLockError::WouldBlock => {
                     "Shell is busy executing another command. Wait for the current command to complete before sending the next one. Use the 'run' tool for independent concurrent commands.".to_string()
                 }
@@ -411,18 +927,88 @@ impl CommandExecutor for PersistentNuExecutor {
                     "Shell mutex poisoned — a previous command panicked. Send reset=true to recover.".to_string()
                 }
             })?;
-            guard.execute(&command, timeout)
+            let restarted = this.revive(&mut guard)?;
+            let result = guard.execute(&command, timeout).map_err(|e| match guard.wait_exit_reason(EXIT_GRACE) {
+                Some(reason) => {
+                    let note = this.child.limits.explain(None, &reason).map(|note| format!(" {}", note)).unwrap_or_default();
+                    format!(
+                        "{}: the shell {} while running the command. It will be restarted on the next call; session state was lost.{}",
+                        e, reason, note
+                    )
+                }
+                None => e,
+            })?;
+            Ok::<_, String>((restarted, result))
         })
         .await
         .map_err(|e| format!("Shell task failed: {}", e))??;
 
+        let exit_code = match result.status {
+            CommandStatus::Completed => Some(result.exit_code),
+            CommandStatus::WaitingForInput { .. } => None,
+        };
         // PTY merges stdout/stderr into one stream; stderr is empty
-        Ok((result.stdout, String::new()))
+        let mut text = result.into_text();
+        if exit_code.is_some_and(|code| code != 0)
+            && let Some(note) = self.child.limits.explain(None, &text)
+        {
+            text = format!("{}\n{}", text, note);
+        }
+        let text = match restarted {
+            Some(notice) if text.is_empty() => notice,
+            Some(notice) => format!("{}\n\n{}", notice, text),
+            None => text,
+        };
+        Ok(ExecutionOutput {
+            stdout: text,
+            stderr: String::new(),
+            exit_code,
+        })
+    }
+
+    async fn send_input(
+        &self,
+        input: &[u8],
+        timeout_secs: Option<u64>,
+    ) -> Result<(String, String), String> {
+        let timeout = Duration::from_secs(timeout_secs.unwrap_or_else(super::get_default_timeout));
+        let input = input.to_vec();
+        let this = self.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            let mut guard = this.shell.try_lock().map_err(|e| match e {
+                TryLockError::WouldBlock => {
+                    "Shell is busy executing a command that is not waiting for input. Wait for the shell call to return first.".to_string()
+                }
+                TryLockError::Poisoned(_) => {
+                    "Shell mutex poisoned — a previous command panicked. Send reset=true to recover.".to_string()
+                }
+            })?;
+            if let Some(notice) = this.revive(&mut guard)? {
+                return Err(format!(
+                    "{} The command that was waiting for input was lost.",
+                    notice
+                ));
+            }
+            guard.send_input(&input, timeout)
+        })
+        .await
+        .map_err(|e| format!("Shell task failed: {}", e))??;
+
+        Ok((result.into_text(), String::new()))
+    }
+
+    fn peek(&self) -> Option<RunningCommand> {
+        self.monitor.snapshot()
+    }
+
+    fn working_dir(&self) -> Option<PathBuf> {
+        self.monitor.working_dir()
     }
 
     /// Tear down the current shell and create a fresh one.
     /// This gives a clean environment (no env vars, aliases, etc.).
-    /// 
+    ///
     /// If a command is currently running, this:
     /// 1. Kills the child process (via clone_killer)
     /// 2. Waits for the shell mutex to be released (execute() returns after PTY EOF)
@@ -434,32 +1020,38 @@ impl CommandExecutor for PersistentNuExecutor {
         // killing the child causes the PTY read to return EOF/error,
         // which makes shell.execute() return an error, releasing the shell mutex.
         {
-            let mut killer = self.killer.lock()
+            let mut killer = self
+                .killer
+                .lock()
                 .map_err(|_| "Killer mutex poisoned".to_string())?;
             let _ = killer.kill(); // Best effort — process may already be dead
         }
-        
+
         // Step 2: Lock the shell mutex. If execute() was in progress,
         // it should have returned by now (child was killed, PTY returned EOF).
         // Use lock() (blocking wait), not try_lock() — reset MUST succeed.
         let shell_arc = Arc::clone(&self.shell);
         let killer_arc = Arc::clone(&self.killer);
+        let monitor = self.monitor.clone();
+        let child = self.child.clone();
         tokio::task::spawn_blocking(move || {
-            let mut shell_guard = shell_arc.lock()
+            let mut shell_guard = shell_arc
+                .lock()
                 .map_err(|_| "Shell mutex poisoned after kill".to_string())?;
-            
+
             // Step 3: Create new shell
-            let new_shell = PersistentShell::new()?;
+            let new_shell = PersistentShell::with_monitor(monitor, &child)?;
             let new_killer = new_shell.clone_killer();
-            
+
             // Step 4: Replace shell and killer
             *shell_guard = new_shell;
             drop(shell_guard); // Release shell mutex before locking killer
-            
-            let mut killer_guard = killer_arc.lock()
+
+            let mut killer_guard = killer_arc
+                .lock()
                 .map_err(|_| "Killer mutex poisoned".to_string())?;
             *killer_guard = new_killer;
-            
+
             Ok::<(), String>(())
         })
         .await
@@ -1,9 +1,11 @@
 use crate::execution::CommandExecutor;
 use crate::execution::persistent::PersistentNuExecutor;
 use crate::execution::persistent::PersistentShell;
+use crate::execution::persistent::CommandStatus;
+use crate::execution::persistent::CommandQueue;
 use serial_test::serial;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -233,6 +235,105 @@ fn test_multi_command_sequence_with_adequate_timeout() {
     assert_eq!(output.exit_code, 0);
 }
 
+// --- Input wait detection ---
+
+#[test]
+#[serial]
+fn test_blocked_read_returns_waiting_for_input() {
+    skip_if_no_pty!();
+    let mut shell = PersistentShell::new().expect("Failed to create shell");
+
+    // `^cat` with no arguments blocks reading the terminal forever
+    let start = Instant::now();
+    let result = shell.execute("print 'before'; ^cat", DEFAULT_TIMEOUT);
+    assert!(result.is_ok(), "Execute failed: {:?}", result.err());
+    assert!(
+        start.elapsed() < Duration::from_secs(10),
+        "Should return early instead of burning the timeout"
+    );
+
+    let output = result.unwrap();
+    assert!(
+        matches!(output.status, CommandStatus::WaitingForInput { .. }),
+        "Expected waiting status, got: {:?}",
+        output.status
+    );
+    assert!(output.stdout.contains("before"), "Missing partial output");
+
+    // Shell stays busy with the blocked command
+    let next = shell.execute("print 'next'", DEFAULT_TIMEOUT);
+    assert!(next.is_err(), "Shell should reject commands while waiting");
+    assert!(next.unwrap_err().contains("waiting for input"));
+}
+
+#[test]
+#[serial]
+fn test_alternate_screen_returns_waiting_for_input() {
+    skip_if_no_pty!();
+    let mut shell = PersistentShell::new().expect("Failed to create shell");
+
+    let result = shell.execute(r#"print -n "\e[?1049h"; sleep 20sec"#, DEFAULT_TIMEOUT);
+    assert!(result.is_ok(), "Execute failed: {:?}", result.err());
+    match result.unwrap().status {
+        CommandStatus::WaitingForInput { reason } => {
+            assert!(reason.contains("alternate screen"), "Got: {}", reason)
+        }
+        status => panic!("Expected waiting status, got: {:?}", status),
+    }
+}
+
+#[test]
+#[serial]
+fn test_quiet_command_is_not_waiting_for_input() {
+    skip_if_no_pty!();
+    let mut shell = PersistentShell::new().expect("Failed to create shell");
+
+    let result = shell.execute("sleep 2sec; print 'done'", DEFAULT_TIMEOUT);
+    assert!(result.is_ok(), "Execute failed: {:?}", result.err());
+    let output = result.unwrap();
+    assert_eq!(output.status, CommandStatus::Completed);
+    assert!(output.stdout.contains("done"));
+}
+
+#[test]
+#[serial]
+fn test_send_input_answers_waiting_command() {
+    skip_if_no_pty!();
+    let mut shell = PersistentShell::new().expect("Failed to create shell");
+
+    let result = shell.execute(
+        r#"let answer = (input "Continue? [y/N] "); print $"got ($answer)""#,
+        DEFAULT_TIMEOUT,
+    );
+    assert!(result.is_ok(), "Execute failed: {:?}", result.err());
+    assert!(matches!(
+        result.unwrap().status,
+        CommandStatus::WaitingForInput { .. }
+    ));
+
+    let answered = shell.send_input(b"y\r", DEFAULT_TIMEOUT);
+    assert!(answered.is_ok(), "Send input failed: {:?}", answered.err());
+    let output = answered.unwrap();
+    assert_eq!(output.status, CommandStatus::Completed);
+    assert!(output.stdout.contains("got y"), "Got: {:?}", output.stdout);
+
+    // Shell is usable again
+    let next = shell.execute("print 'next'", DEFAULT_TIMEOUT);
+    assert!(next.is_ok(), "Shell should accept commands: {:?}", next.err());
+    assert!(next.unwrap().stdout.contains("next"));
+}
+
+#[test]
+#[serial]
+fn test_send_input_without_waiting_command() {
+    skip_if_no_pty!();
+    let mut shell = PersistentShell::new().expect("Failed to create shell");
+
+    let result = shell.send_input(b"q", DEFAULT_TIMEOUT);
+    assert!(result.is_err(), "Input without a waiting command should fail");
+    assert!(result.unwrap_err().contains("No command is waiting for input"));
+}
+
 // --- Reset tests (via PersistentNuExecutor) ---
 
 #[tokio::test]
@@ -277,6 +378,48 @@ async fn test_reset() {
     assert!(r4.unwrap().0.contains("alive"));
 }
 
+#[tokio::test]
+#[serial]
+async fn test_dead_shell_is_restarted_on_next_call() {
+    skip_if_no_pty!();
+    let executor = PersistentNuExecutor::new().expect("Failed to create executor");
+    let work_dir = PathBuf::from(".");
+
+    executor
+        .execute("$env.RESTART_TEST = 'before'", &work_dir, Some(30))
+        .await
+        .expect("Set state failed");
+
+    // Exiting the shell fails the current call and reports why
+    let r1 = executor.execute("exit 3", &work_dir, Some(30)).await;
+    let err = r1.expect_err("exit should end the shell");
+    assert!(
+        err.contains("exited with code 3"),
+        "Expected exit reason in error, got: {}",
+        err
+    );
+    assert_eq!(executor.restart_count(), 0);
+
+    // The next call gets a fresh shell and is told the state is gone
+    let r2 = executor
+        .execute("$env.RESTART_TEST? | default 'gone'", &work_dir, Some(30))
+        .await
+        .expect("Execute after shell exit failed");
+    assert!(r2.0.contains("[shell restarted"), "Missing notice: {}", r2.0);
+    assert!(r2.0.contains("exited with code 3"), "Missing reason: {}", r2.0);
+    assert!(r2.0.contains("gone"), "State should be cleared: {}", r2.0);
+    assert_eq!(executor.restart_count(), 1);
+
+    // Later calls run normally without the notice
+    let r3 = executor
+        .execute("print 'alive'", &work_dir, Some(30))
+        .await
+        .expect("Execute after restart failed");
+    assert!(r3.0.contains("alive"));
+    assert!(!r3.0.contains("[shell restarted"));
+    assert_eq!(executor.restart_count(), 1);
+}
+
 #[test]
 #[serial]
 fn test_drop_kills_child_process() {
@@ -390,3 +533,271 @@ async fn test_reset_kills_running_command() {
     );
 }
 
+
+#[tokio::test]
+#[serial]
+async fn test_reset_recovers_from_waiting_command() {
+    skip_if_no_pty!();
+    let executor = PersistentNuExecutor::new().expect("Failed to create executor");
+    let work_dir = PathBuf::from(".");
+
+    let r1 = executor.execute("^cat", &work_dir, Some(30)).await;
+    assert!(r1.is_ok(), "Execute failed: {:?}", r1.err());
+    assert!(
+        r1.unwrap().0.contains("command is waiting for input"),
+        "Result should carry the waiting status"
+    );
+
+    executor.reset().await.expect("Reset failed");
+
+    let r2 = executor.execute("print 'alive'", &work_dir, Some(30)).await;
+    assert!(r2.is_ok(), "Post-reset execute failed: {:?}", r2.err());
+    assert!(r2.unwrap().0.contains("alive"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_peek_shows_running_command_output() {
+    skip_if_no_pty!();
+    let executor = PersistentNuExecutor::new().expect("Failed to create executor");
+    let work_dir = PathBuf::from(".");
+
+    assert!(executor.peek().is_none(), "Idle shell should have nothing to peek");
+
+    let executor1 = executor.clone();
+    let work_dir1 = work_dir.clone();
+    let task1 = tokio::spawn(async move {
+        executor1
+            .execute("print 'started'; sleep 3sec; print 'done'", &work_dir1, Some(10))
+            .await
+    });
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let running = executor.peek().expect("Command should be running");
+    assert!(running.command.contains("sleep 3sec"));
+    assert!(running.elapsed >= Duration::from_secs(1));
+    assert!(!running.waiting_for_input);
+    assert!(running.output.contains("started"), "Got: {:?}", running.output);
+    assert!(!running.output.contains("done"));
+
+    let result1 = task1.await.expect("Task 1 panicked");
+    assert!(result1.is_ok(), "Task 1 failed: {:?}", result1.err());
+    assert!(executor.peek().is_none(), "Finished command should be cleared");
+}
+
+// --- Command queue ---
+
+#[tokio::test]
+async fn test_queue_grants_turns_in_arrival_order() {
+    let queue = CommandQueue::new(3, Duration::from_secs(5));
+    let first = queue.wait_turn().await.expect("Free shell should grant a turn");
+
+    let order = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut tasks = Vec::new();
+    for i in 0..3 {
+        let queue = queue.clone();
+        let order = order.clone();
+        tasks.push(tokio::spawn(async move {
+            let _turn = queue.wait_turn().await.expect("Queued call should get a turn");
+            order.lock().unwrap().push(i);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }));
+        // Let each task enqueue before the next one
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    drop(first);
+    for task in tasks {
+        task.await.expect("Task panicked");
+    }
+    assert_eq!(*order.lock().unwrap(), vec![0, 1, 2]);
+}
+
+#[tokio::test]
+async fn test_queue_rejects_when_full() {
+    let queue = CommandQueue::new(1, Duration::from_secs(5));
+    let _first = queue.wait_turn().await.expect("Free shell should grant a turn");
+
+    let waiting = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.wait_turn().await.map(|_| ()) })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let rejected = queue.wait_turn().await;
+    assert!(rejected.is_err(), "Call beyond queue depth should be rejected");
+    assert!(rejected.unwrap_err().contains("Shell queue is full"));
+
+    waiting.abort();
+}
+
+#[tokio::test]
+async fn test_queue_wait_times_out() {
+    let queue = CommandQueue::new(2, Duration::from_millis(100));
+    let _first = queue.wait_turn().await.expect("Free shell should grant a turn");
+
+    let result = queue.wait_turn().await;
+    assert!(result.is_err(), "Queued call should time out");
+    assert!(result.unwrap_err().contains("in the shell queue"));
+
+    // The timed-out call released its place in the queue
+    let queue_after = queue.clone();
+    let retry = tokio::spawn(async move { queue_after.wait_turn().await.map(|_| ()) });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!retry.is_finished(), "Retry should be waiting, not rejected as full");
+    retry.abort();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_concurrent_execute_is_queued() {
+    skip_if_no_pty!();
+    let executor = PersistentNuExecutor::new()
+        .expect("Failed to create executor")
+        .with_queue(2, Duration::from_secs(20));
+    let work_dir = PathBuf::from(".");
+
+    let executor1 = executor.clone();
+    let work_dir1 = work_dir.clone();
+    let task1 = tokio::spawn(async move {
+        executor1.execute("sleep 2sec; print 'first'", &work_dir1, Some(10)).await
+    });
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Second call waits for the first instead of failing with "Shell is busy"
+    let result2 = executor.execute("print 'second'", &work_dir, Some(10)).await;
+    assert!(result2.is_ok(), "Queued command failed: {:?}", result2.err());
+    assert!(result2.unwrap().0.contains("second"));
+
+    let result1 = task1.await.expect("Task 1 panicked");
+    assert!(result1.is_ok(), "Task 1 failed: {:?}", result1.err());
+    assert!(result1.unwrap().0.contains("first"));
+}
+
+#[test]
+#[serial]
+fn test_landlock_confines_persistent_shell() {
+    use crate::execution::ChildConfig;
+    use crate::execution::persistent::CommandMonitor;
+    use crate::security::{Landlock, LandlockPaths};
+
+    skip_if_no_pty!();
+    let outside = std::env::temp_dir().join("nu_mcp_landlock_shell_outside");
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+
+    let cwd = std::env::current_dir().unwrap();
+    let Some(landlock) = Landlock::new(&LandlockPaths::new(&[cwd], &[])).unwrap() else {
+        eprintln!("Skipping: Landlock is not supported here");
+        return;
+    };
+    let child = ChildConfig {
+        landlock: Some(landlock),
+        ..Default::default()
+    };
+    let mut shell = PersistentShell::with_monitor(CommandMonitor::default(), &child)
+        .expect("Failed to create confined shell");
+
+    let inside = shell.execute("open --raw Cargo.toml | lines | first", DEFAULT_TIMEOUT).unwrap();
+    assert!(inside.stdout.contains("[package]"), "{:?}", inside.stdout);
+
+    let command = format!("open ('{}' | path join secret.txt)", outside.display());
+    let result = shell.execute(&command, DEFAULT_TIMEOUT).unwrap();
+    assert_ne!(result.exit_code, 0, "{:?}", result);
+    assert!(result.stdout.contains("Permission denied"), "{:?}", result.stdout);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[serial]
+fn test_resource_limits_apply_to_persistent_shell() {
+    use crate::execution::persistent::CommandMonitor;
+    use crate::execution::{ChildConfig, ResourceLimits};
+
+    skip_if_no_pty!();
+    let child = ChildConfig {
+        limits: ResourceLimits {
+            open_files: Some(128),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut shell = PersistentShell::with_monitor(CommandMonitor::default(), &child)
+        .expect("Failed to create limited shell");
+
+    let result = shell.execute("^sh -c 'ulimit -n'", DEFAULT_TIMEOUT).unwrap();
+    assert!(result.stdout.contains("128"), "{:?}", result.stdout);
+}
+
+#[test]
+#[serial]
+fn test_env_policy_applies_to_persistent_shell() {
+    use crate::execution::persistent::CommandMonitor;
+    use crate::execution::{ChildConfig, EnvMode, EnvPolicy};
+
+    skip_if_no_pty!();
+    let child = ChildConfig {
+        env: EnvPolicy {
+            mode: EnvMode::Allowlist,
+            vars: vec![],
+            set: [("NU_MCP_GREETING".to_string(), "hi".to_string())].into(),
+        },
+        ..Default::default()
+    };
+    let mut shell = PersistentShell::with_monitor(CommandMonitor::default(), &child)
+        .expect("Failed to create shell");
+
+    let result = shell
+        .execute("$env.HOME? | default 'unset'", DEFAULT_TIMEOUT)
+        .unwrap();
+    assert!(result.stdout.contains("unset"), "{:?}", result.stdout);
+    let result = shell.execute("$env.NU_MCP_GREETING", DEFAULT_TIMEOUT).unwrap();
+    assert!(result.stdout.contains("hi"), "{:?}", result.stdout);
+}
+
+#[test]
+#[serial]
+fn test_shell_reports_working_directory() {
+    skip_if_no_pty!();
+    let mut shell = PersistentShell::new().expect("Failed to create shell");
+    let monitor = shell.monitor();
+    let start = std::env::current_dir().unwrap();
+    assert_eq!(monitor.working_dir(), Some(start));
+
+    let dir = std::env::temp_dir().join("nu_mcp_shell_cwd");
+    std::fs::create_dir_all(&dir).unwrap();
+    shell
+        .execute(&format!("cd '{}'", dir.display()), DEFAULT_TIMEOUT)
+        .unwrap();
+    assert_eq!(monitor.working_dir(), Some(dir));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[serial]
+fn test_cpu_limit_applies_per_command() {
+    use crate::execution::persistent::CommandMonitor;
+    use crate::execution::{ChildConfig, ResourceLimits};
+
+    skip_if_no_pty!();
+    let child = ChildConfig {
+        limits: ResourceLimits {
+            cpu_secs: Some(2),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut shell = PersistentShell::with_monitor(CommandMonitor::default(), &child)
+        .expect("Failed to create limited shell");
+
+    // About a second of CPU in the shell itself each time: more than the
+    // limit together, but within it for every command
+    let busy = "mut i = 0; while $i < 600000 { $i += 1 }; 'done'";
+    for _ in 0..4 {
+        let result = shell.execute(busy, Duration::from_secs(30)).unwrap();
+        assert!(result.stdout.contains("done"), "{:?}", result);
+    }
+    assert_eq!(shell.exit_reason(), None);
+}
@@ -1,7 +1,22 @@
 use anyhow::Result;
 use clap::Parser;
-use nu_mcp::{cli::Cli, config::Config, mcp::run_server};
+use nu_mcp::{
+    cli::{Cli, Command},
+    config::Config,
+    execution::{EnvPolicies, ResourceLimits},
+    mcp::{
+        audit::AuditLog,
+        explain::{Outcome, explain_command},
+        run_server,
+    },
+    security::{
+        CUSTOM_LABEL, DEFAULT_DENY_PATTERNS, DenyList, EgressAllowlist, PathCache, Policy,
+        Redactor, SafePatterns,
+    },
+    tools::{ModuleLock, lock::write_lock},
+};
 use std::env;
+use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -16,15 +31,157 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    // Always include current directory, plus any additional paths
+    // Runs before the lock is loaded, since it may not exist yet
+    if let Some(Command::LockTools) = cli.command {
+        return lock_tools(&cli);
+    }
+
+    // Always include current directory, plus any additional paths.
+    // Read-only paths are accessible too; writes to them are rejected separately.
     let mut sandbox_directories = vec![env::current_dir()?];
     sandbox_directories.extend(cli.add_paths);
+    sandbox_directories.extend(cli.add_paths_ro.iter().cloned());
+
+    // Default credential patterns, plus any from --deny-path
+    let mut deny_patterns: Vec<String> = if cli.no_default_deny {
+        Vec::new()
+    } else {
+        DEFAULT_DENY_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    };
+    deny_patterns.extend(cli.deny_paths);
+    let deny_list = DenyList::new(&deny_patterns).map_err(anyhow::Error::msg)?;
+
+    // Built-in safe command patterns, plus (or replaced by) any from --safe-patterns-file
+    let mut safe_patterns = if cli.replace_safe_patterns {
+        SafePatterns::empty()
+    } else {
+        SafePatterns::default()
+    };
+    for path in &cli.safe_patterns_files {
+        safe_patterns.extend(SafePatterns::load(path).map_err(anyhow::Error::msg)?);
+    }
+    if cli.list_safe_patterns {
+        for pattern in safe_patterns.iter() {
+            println!("{}", pattern);
+        }
+        return Ok(());
+    }
+
+    let policy = match &cli.policy_file {
+        Some(path) => Policy::load(path).map_err(anyhow::Error::msg)?,
+        None => Policy::default(),
+    };
+
+    let egress = EgressAllowlist::new(&cli.allow_hosts).map_err(anyhow::Error::msg)?;
+
+    let mut redactor = if cli.no_default_redact {
+        Redactor::default()
+    } else {
+        Redactor::with_defaults()
+    };
+    for pattern in &cli.redact_patterns {
+        redactor = redactor
+            .with_pattern(CUSTOM_LABEL, pattern)
+            .map_err(anyhow::Error::msg)?;
+    }
+    for name in &cli.redact_env {
+        match env::var(name) {
+            Ok(value) => redactor = redactor.with_secret(name, &value),
+            Err(_) => tracing::warn!("Not redacting ${}: it isn't set", name),
+        }
+    }
+
+    let env_policies = match &cli.env_policy_file {
+        Some(path) => EnvPolicies::load(path).map_err(anyhow::Error::msg)?,
+        None => EnvPolicies::default(),
+    };
+
+    let module_lock = match (&cli.tools_lock, &cli.tools_dir) {
+        (Some(path), Some(tools_dir)) => ModuleLock::load(path, tools_dir, cli.tools_lock_warn)?,
+        (Some(_), None) => anyhow::bail!("--tools-lock requires --tools-dir"),
+        (None, _) => ModuleLock::default(),
+    };
+
+    // The server's own files, so an agent can't rewrite its tools or loosen its policy
+    let protected_paths = if cli.no_protect_server_files {
+        Vec::new()
+    } else {
+        let mut paths: Vec<PathBuf> = cli.tools_dir.iter().cloned().collect();
+        paths.extend(cli.policy_file.iter().cloned());
+        paths.extend(cli.env_policy_file.iter().cloned());
+        paths.extend(cli.tools_lock.iter().cloned());
+        paths.extend(cli.safe_patterns_files.iter().cloned());
+        paths.extend(cli.audit_log.iter().flat_map(|path| AuditLog::files(path)));
+        paths
+    };
 
     let config = Config {
         tools_dir: cli.tools_dir,
+        module_lock,
         enable_run_nu: cli.enable_run_nu,
         sandbox_directories,
+        read_only_directories: cli.add_paths_ro,
+        protected_paths,
+        deny_list,
+        safe_patterns,
+        policy,
+        egress,
+        redactor,
+        landlock: cli.landlock,
+        limits: ResourceLimits {
+            cpu_secs: cli.limit_cpu,
+            memory_bytes: cli.limit_memory_mb.map(megabytes),
+            max_processes: cli.limit_processes,
+            open_files: cli.limit_open_files,
+            file_size_bytes: cli.limit_file_size_mb.map(megabytes),
+        },
+        env: env_policies,
+        shell_queue_depth: cli.shell_queue_depth,
+        shell_queue_timeout_secs: cli.shell_queue_timeout,
+        audit_log: cli.audit_log,
+        audit_log_max_bytes: megabytes(cli.audit_log_max_mb),
     };
 
-    run_server(config).await
+    match cli.command {
+        Some(Command::CheckCommand { command }) => {
+            let explanation = explain_command(&config, &PathCache::new(), false, &command, None);
+            println!("{}", explanation);
+            if explanation.outcome != Outcome::Allowed {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::LockTools) => unreachable!("handled before loading the config"),
+        None => run_server(config).await,
+    }
+}
+
+/// Write the hashes of the modules in --tools-dir to --tools-lock
+fn lock_tools(cli: &Cli) -> Result<()> {
+    let (Some(tools_dir), Some(path)) = (&cli.tools_dir, &cli.tools_lock) else {
+        anyhow::bail!("lock-tools requires --tools-dir and --tools-lock");
+    };
+    let changes = write_lock(tools_dir, path)?;
+    for (label, names) in [
+        ("added", &changes.added),
+        ("changed", &changes.changed),
+        ("removed", &changes.removed),
+    ] {
+        for name in names {
+            println!("{:<8} {}", label, name);
+        }
+    }
+    if changes.is_empty() {
+        println!("{} is up to date", path.display());
+    } else {
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn megabytes(mb: u64) -> u64 {
+    mb.saturating_mul(1024 * 1024)
 }
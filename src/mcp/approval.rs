@@ -0,0 +1,160 @@
+//! User approval for commands the sandbox or command policy would reject
+//!
+//! Path validation failures and policy "ask" rules don't have to be final:
+//! the user is asked through MCP elicitation and can approve the command once,
+//! approve it for the rest of the session, or deny it. Clients that don't
+//! support elicitation get the rejection as before.
+
+use rmcp::{
+    Peer, RoleServer,
+    model::{CreateElicitationRequestParams, ElicitationAction, ElicitationSchema, EnumSchema},
+    service::{ElicitationMode, ServiceError},
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// How long the user has to answer before the command is rejected
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const APPROVE_ONCE: &str = "once";
+const APPROVE_SESSION: &str = "session";
+const DENY: &str = "deny";
+
+/// The user's answer to an approval request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    /// Run the command this time
+    Once,
+    /// Run the command, and don't ask again for it in this session
+    Session,
+    Denied,
+}
+
+/// Asks the user whether a rejected command may run anyway
+pub trait Approver: Send + Sync {
+    /// `reasons` explain why the command needs approval. Returns `Err` when
+    /// the user can't be asked (e.g. the client doesn't support elicitation).
+    fn request_approval(
+        &self,
+        command: &str,
+        reasons: &[String],
+    ) -> impl std::future::Future<Output = Result<Approval, String>> + Send;
+}
+
+/// For callers without a client to ask: every request is rejected
+pub struct NoApprover;
+
+impl Approver for NoApprover {
+    async fn request_approval(
+        &self,
+        _command: &str,
+        _reasons: &[String],
+    ) -> Result<Approval, String> {
+        Err("no client is connected to ask".to_string())
+    }
+}
+
+/// Asks the user through an MCP form elicitation on the client connection
+pub struct ElicitationApprover {
+    peer: Peer<RoleServer>,
+}
+
+impl ElicitationApprover {
+    pub fn new(peer: Peer<RoleServer>) -> Self {
+        Self { peer }
+    }
+}
+
+impl Approver for ElicitationApprover {
+    async fn request_approval(
+        &self,
+        command: &str,
+        reasons: &[String],
+    ) -> Result<Approval, String> {
+        if !self
+            .peer
+            .supported_elicitation_modes()
+            .contains(&ElicitationMode::Form)
+        {
+            return Err("the client doesn't support elicitation".to_string());
+        }
+
+        let decision = EnumSchema::builder(vec![
+            APPROVE_ONCE.to_string(),
+            APPROVE_SESSION.to_string(),
+            DENY.to_string(),
+        ])
+        .enum_titles(vec![
+            "Approve once".to_string(),
+            "Approve for this session".to_string(),
+            "Deny".to_string(),
+        ])
+        .map_err(|e| format!("Failed to build approval form: {}", e))?
+        .title("Decision")
+        .build();
+        let schema = ElicitationSchema::builder()
+            .required_enum_schema("decision", decision)
+            .build()
+            .map_err(|e| format!("Failed to build approval form: {}", e))?;
+
+        let params = CreateElicitationRequestParams::FormElicitationParams {
+            meta: None,
+            message: approval_message(command, reasons),
+            requested_schema: schema,
+        };
+        let response = self
+            .peer
+            .create_elicitation_with_timeout(params, Some(APPROVAL_TIMEOUT))
+            .await
+            .map_err(|e| match e {
+                ServiceError::Timeout { .. } => "the user didn't answer in time".to_string(),
+                e => format!("the approval request failed: {}", e),
+            })?;
+
+        let decision = response
+            .content
+            .as_ref()
+            .and_then(|content| content.get("decision"))
+            .and_then(|decision| decision.as_str());
+        Ok(match (response.action, decision) {
+            (ElicitationAction::Accept, Some(APPROVE_ONCE)) => Approval::Once,
+            (ElicitationAction::Accept, Some(APPROVE_SESSION)) => Approval::Session,
+            _ => Approval::Denied,
+        })
+    }
+}
+
+/// The text shown to the user
+pub fn approval_message(command: &str, reasons: &[String]) -> String {
+    let mut message = format!(
+        "The agent wants to run:\n\n    {}\n\nIt needs your approval because:",
+        command
+    );
+    for reason in reasons {
+        message.push_str("\n- ");
+        message.push_str(reason);
+    }
+    message
+}
+
+/// Commands the user approved for the rest of the session
+#[derive(Debug, Clone, Default)]
+pub struct SessionApprovals {
+    commands: Arc<RwLock<HashSet<String>>>,
+}
+
+impl SessionApprovals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn contains(&self, command: &str) -> bool {
+        self.commands.read().await.contains(command)
+    }
+
+    pub async fn insert(&self, command: &str) {
+        self.commands.write().await.insert(command.to_string());
+    }
+}
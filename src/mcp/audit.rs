@@ -0,0 +1,519 @@
+//! JSON Lines audit log of tool calls
+//!
+//! Every call routed through `ToolRouter` is appended as one JSON object per
+//! line: the tool and its arguments, whether validation allowed it and why,
+//! which executor ran it, and how it ended. Argument values under
+//! secret-looking keys are replaced before they are written, and secrets the
+//! output redactor detects in the remaining strings (a token inside a
+//! `command`) are masked the same way as in tool output.
+//!
+//! When a write would take the file past its size limit, the file is rotated
+//! to `<path>.1`, shifting older files up to `<path>.5`.
+
+use crate::security::{RedactionCounts, Redactor};
+use rmcp::model::{CallToolResult, ErrorData};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Rotated files kept next to the log: `<path>.1` (newest) to `<path>.5`
+const ROTATED_FILES: usize = 5;
+
+/// Argument keys whose values are never written, matched case-insensitively
+/// anywhere in the key (`API_TOKEN`, `db_password`)
+const SECRET_KEYS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "access_key",
+    "private_key",
+    "authorization",
+    "credential",
+];
+
+const REDACTED: &str = "[REDACTED]";
+
+/// What validation decided about a call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    /// Passed validation, or needed none
+    #[default]
+    Allowed,
+    /// Needed the user's approval and got it
+    Approved,
+    /// Rejected by path validation, the command policy or the user
+    Rejected,
+}
+
+/// What routing learned about one call. Handlers fill it in as they go.
+#[derive(Debug, Default)]
+pub struct CallRecord {
+    pub decision: Decision,
+    /// Why the call was rejected or needed approval
+    pub reason: Option<String>,
+    /// `stateless`, `persistent` or `tool`; `None` if nothing ran
+    pub executor: Option<&'static str>,
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    timestamp: String,
+    session_id: &'a str,
+    tool: &'a str,
+    arguments: Value,
+    redactions: Vec<String>,
+    decision: Decision,
+    reason: Option<String>,
+    executor: Option<&'a str>,
+    duration_ms: u64,
+    exit_code: Option<i32>,
+    output_bytes: usize,
+    error: Option<String>,
+}
+
+/// Append-only audit log file
+pub struct AuditLog {
+    path: PathBuf,
+    /// Rotate before the file grows past this size (0 = never rotate)
+    max_bytes: u64,
+    session_id: String,
+    /// Masks secrets in argument values, the reason and the error
+    redactor: Redactor,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Open `path` for appending, creating it if needed
+    pub fn open(path: &Path, max_bytes: u64) -> Result<Self, String> {
+        let file = open_append(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            session_id: new_session_id(),
+            redactor: Redactor::default(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Mask what `redactor` detects, as the router does for tool output
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The log at `path` and the rotated files kept next to it
+    pub fn files(path: &Path) -> Vec<PathBuf> {
+        std::iter::once(path.to_path_buf())
+            .chain((1..=ROTATED_FILES).map(|index| rotated_path(path, index)))
+            .collect()
+    }
+
+    /// Identifies this server process in the log
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Append the entry for a finished call. Write failures are logged rather
+    /// than returned, so a full disk doesn't fail the call itself.
+    pub fn record(
+        &self,
+        tool: &str,
+        arguments: Option<&Map<String, Value>>,
+        call: &CallRecord,
+        duration: Duration,
+        result: &Result<CallToolResult, ErrorData>,
+    ) {
+        let (arguments, mut redactions) = redact_arguments(arguments, &self.redactor);
+        let (output_bytes, error) = match result {
+            Ok(result) => (
+                result
+                    .content
+                    .iter()
+                    .filter_map(|content| content.as_text())
+                    .map(|text| text.text.len())
+                    .sum(),
+                None,
+            ),
+            // The reason already says why a rejected call failed
+            Err(_) if call.decision == Decision::Rejected => (0, None),
+            Err(e) => (0, Some(e.message.as_ref())),
+        };
+        let reason = call
+            .reason
+            .as_deref()
+            .map(|reason| redact_text(reason, "reason", &self.redactor, &mut redactions));
+        let error = error.map(|error| redact_text(error, "error", &self.redactor, &mut redactions));
+
+        let entry = Entry {
+            timestamp: format_timestamp(SystemTime::now()),
+            session_id: &self.session_id,
+            tool,
+            arguments,
+            redactions,
+            decision: call.decision,
+            reason,
+            executor: call.executor,
+            duration_ms: duration.as_millis().try_into().unwrap_or(u64::MAX),
+            exit_code: call.exit_code,
+            output_bytes,
+            error,
+        };
+
+        if let Err(e) = self.append(&entry) {
+            warn!("Failed to write audit log {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn append(&self, entry: &Entry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        // A panic while holding the lock leaves the file usable
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let size = file.metadata().map_err(|e| e.to_string())?.len();
+        if self.max_bytes > 0 && size > 0 && size + line.len() as u64 > self.max_bytes {
+            rotate(&self.path)?;
+            *file = open_append(&self.path)?;
+        }
+        file.write_all(line.as_bytes()).map_err(|e| e.to_string())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open audit log {}: {}", path.display(), e))
+}
+
+/// `<path>.N` for rotated files
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// Shift `<path>` to `<path>.1`, `<path>.1` to `<path>.2` and so on,
+/// dropping the oldest file
+fn rotate(path: &Path) -> Result<(), String> {
+    for index in (1..ROTATED_FILES).rev() {
+        let from = rotated_path(path, index);
+        if from.exists() {
+            std::fs::rename(&from, rotated_path(path, index + 1))
+                .map_err(|e| format!("Failed to rotate {}: {}", from.display(), e))?;
+        }
+    }
+    std::fs::rename(path, rotated_path(path, 1))
+        .map_err(|e| format!("Failed to rotate {}: {}", path.display(), e))
+}
+
+/// Copy of the arguments with secret values replaced, and what was replaced:
+/// the dotted path of a value under a secret key, or the path and detector
+/// label of a secret found inside a string (`command (github-token)`)
+fn redact_arguments(
+    arguments: Option<&Map<String, Value>>,
+    redactor: &Redactor,
+) -> (Value, Vec<String>) {
+    let mut value = Value::Object(arguments.cloned().unwrap_or_default());
+    let mut redactions = Vec::new();
+    redact_value(&mut value, "", redactor, &mut redactions);
+    (value, redactions)
+}
+
+fn redact_value(value: &mut Value, path: &str, redactor: &Redactor, redactions: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                if is_secret_key(key) {
+                    *value = Value::String(REDACTED.to_string());
+                    redactions.push(path);
+                } else {
+                    redact_value(value, &path, redactor, redactions);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                let path = format!("{}[{}]", path, index);
+                redact_value(item, &path, redactor, redactions);
+            }
+        }
+        Value::String(text) => *text = redact_text(text, path, redactor, redactions),
+        _ => {}
+    }
+}
+
+/// `text` with what `redactor` detects masked, listing each detector label
+/// next to `path` in `redactions`
+fn redact_text(
+    text: &str,
+    path: &str,
+    redactor: &Redactor,
+    redactions: &mut Vec<String>,
+) -> String {
+    if redactor.is_empty() {
+        return text.to_string();
+    }
+    let mut counts = RedactionCounts::new();
+    let redacted = redactor.redact(text, &mut counts);
+    redactions.extend(counts.keys().map(|label| format!("{} ({})", path, label)));
+    redacted
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase().replace('-', "_");
+    SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+/// Process id and start time, unique enough to tell server runs apart
+fn new_session_id() -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}-{:x}", std::process::id(), started.as_millis())
+}
+
+/// RFC 3339 UTC timestamp with milliseconds, e.g. `2026-01-31T09:05:00.123Z`
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_log(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("audit.jsonl")
+    }
+
+    fn lines(path: &Path) -> Vec<Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_files_include_rotations() {
+        let files = AuditLog::files(Path::new("/var/log/audit.jsonl"));
+        assert_eq!(files.len(), 1 + ROTATED_FILES);
+        assert_eq!(files[0], Path::new("/var/log/audit.jsonl"));
+        assert_eq!(files[5], Path::new("/var/log/audit.jsonl.5"));
+    }
+
+    #[test]
+    fn test_redacts_secret_arguments() {
+        let arguments = json!({
+            "command": "ls",
+            "API_TOKEN": "abc",
+            "env": {"db-password": "hunter2", "user": "me"},
+            "headers": [{"Authorization": "Bearer x"}]
+        });
+        let (value, redactions) = redact_arguments(arguments.as_object(), &Redactor::default());
+        assert_eq!(value["command"], "ls");
+        assert_eq!(value["API_TOKEN"], REDACTED);
+        assert_eq!(value["env"]["db-password"], REDACTED);
+        assert_eq!(value["env"]["user"], "me");
+        assert_eq!(value["headers"][0]["Authorization"], REDACTED);
+        assert_eq!(
+            redactions,
+            vec!["API_TOKEN", "env.db-password", "headers[0].Authorization"]
+        );
+    }
+
+    #[test]
+    fn test_redacts_detected_secrets_in_strings() {
+        let token = format!("ghp_{}", "a1B2".repeat(9));
+        let arguments = json!({
+            "command": format!("curl -H \"Authorization: Bearer {}\" https://api.github.com", token),
+            "env": {"password": "hunter2"},
+            "args": ["ls", token.clone()]
+        });
+        let redactor = Redactor::with_defaults();
+        let (value, redactions) = redact_arguments(arguments.as_object(), &redactor);
+        assert_eq!(
+            value["command"],
+            "curl -H \"Authorization: Bearer [REDACTED:github-token]\" https://api.github.com"
+        );
+        assert_eq!(value["args"][1], "[REDACTED:github-token]");
+        assert_eq!(value["env"]["password"], REDACTED);
+        assert_eq!(
+            redactions,
+            vec![
+                "args[1] (github-token)",
+                "command (github-token)",
+                "env.password"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_redacts_command_reason_and_error() {
+        let path = temp_log("nu_mcp_audit_detected_secrets");
+        let log = AuditLog::open(&path, 0)
+            .unwrap()
+            .with_redactor(Redactor::with_defaults());
+        let token = format!("ghp_{}", "a1B2".repeat(9));
+        let command = format!(
+            "curl -H 'Authorization: Bearer {}' https://api.github.com",
+            token
+        );
+        let arguments = json!({"command": command});
+        let call = CallRecord {
+            decision: Decision::Approved,
+            reason: Some(format!("'{}' needs approval", command)),
+            ..Default::default()
+        };
+        log.record(
+            "run",
+            arguments.as_object(),
+            &call,
+            Duration::ZERO,
+            &Err(ErrorData::internal_error(format!("{} failed", token), None)),
+        );
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(!written.contains(&token), "{}", written);
+        let entries = lines(&path);
+        assert_eq!(
+            entries[0]["redactions"],
+            json!([
+                "command (github-token)",
+                "reason (github-token)",
+                "error (github-token)"
+            ])
+        );
+        assert_eq!(entries[0]["error"], "[REDACTED:github-token] failed");
+    }
+
+    #[test]
+    fn test_writes_one_line_per_call() {
+        let path = temp_log("nu_mcp_audit_lines");
+        let log = AuditLog::open(&path, 0).unwrap();
+        let call = CallRecord {
+            executor: Some("persistent"),
+            exit_code: Some(0),
+            ..Default::default()
+        };
+        let result = Ok(CallToolResult::success(vec![rmcp::model::Content::text(
+            "hello",
+        )]));
+        let arguments = json!({"command": "echo hello"});
+        log.record(
+            "shell",
+            arguments.as_object(),
+            &call,
+            Duration::from_millis(42),
+            &result,
+        );
+        let rejected = CallRecord {
+            decision: Decision::Rejected,
+            reason: Some("outside the sandbox".to_string()),
+            ..Default::default()
+        };
+        log.record(
+            "run",
+            None,
+            &rejected,
+            Duration::ZERO,
+            &Err(ErrorData::invalid_request("outside the sandbox", None)),
+        );
+
+        let entries = lines(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["tool"], "shell");
+        assert_eq!(entries[0]["arguments"]["command"], "echo hello");
+        assert_eq!(entries[0]["decision"], "allowed");
+        assert_eq!(entries[0]["executor"], "persistent");
+        assert_eq!(entries[0]["exit_code"], 0);
+        assert_eq!(entries[0]["duration_ms"], 42);
+        assert_eq!(entries[0]["output_bytes"], 5);
+        assert_eq!(entries[0]["session_id"], log.session_id());
+        assert_eq!(entries[1]["decision"], "rejected");
+        assert_eq!(entries[1]["reason"], "outside the sandbox");
+        assert_eq!(entries[1]["executor"], Value::Null);
+        assert_eq!(entries[1]["error"], Value::Null);
+    }
+
+    #[test]
+    fn test_rotates_when_full() {
+        let path = temp_log("nu_mcp_audit_rotation");
+        let log = AuditLog::open(&path, 400).unwrap();
+        for _ in 0..20 {
+            log.record(
+                "shell_peek",
+                None,
+                &CallRecord::default(),
+                Duration::ZERO,
+                &Ok(CallToolResult::success(vec![])),
+            );
+        }
+
+        assert!(std::fs::metadata(&path).unwrap().len() <= 400);
+        assert!(rotated_path(&path, 1).exists());
+        assert!(!rotated_path(&path, ROTATED_FILES + 1).exists());
+        let total: usize = std::iter::once(path.clone())
+            .chain((1..=ROTATED_FILES).map(|index| rotated_path(&path, index)))
+            .filter(|path| path.exists())
+            .map(|path| lines(&path).len())
+            .sum();
+        assert!(total < 20, "oldest entries should have been dropped");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(format_timestamp(time), "2024-02-29T12:34:56.789Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+}
@@ -0,0 +1,270 @@
+//! Dry runs of command validation
+//!
+//! `check_command` (and `nu-mcp check-command`) runs a command through the
+//! same checks as `run` and `shell` without executing it, and reports what
+//! each check concluded: the policy rule that matched, the network
+//! allowlist, how every segment and word was classified during path
+//! validation, and write targets in read-only directories or server files.
+
+use crate::config::Config;
+use crate::security::{
+    Action, PathCache, Step, explain_path_safety, validate_protected_paths, validate_read_only,
+};
+use std::fmt;
+use std::path::Path;
+
+/// What would happen to the command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Allowed,
+    /// The user would be asked first, for these reasons
+    NeedsApproval(Vec<String>),
+    /// It needs approval, and the user approved it for this session
+    ApprovedForSession,
+    Rejected(String),
+}
+
+/// The result of every check a command goes through before it runs
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub command: String,
+    /// The policy rule that matched, if any
+    pub policy: Result<String, String>,
+    pub network: Result<String, String>,
+    /// How path validation treated each segment and word, in order
+    pub paths: Vec<Step>,
+    pub path_result: Result<(), String>,
+    /// Writes to read-only directories and protected server files
+    pub read_only: Result<(), String>,
+    pub outcome: Outcome,
+}
+
+/// Run `command` through the checks of `ToolRouter::check_command` without
+/// executing it or asking the user. `session_approved` says whether the user
+/// already approved this command for the session. The cache isn't modified.
+/// Relative paths resolve against `working_dir` (the persistent shell's), or
+/// the first sandbox directory.
+pub fn explain_command(
+    config: &Config,
+    cache: &PathCache,
+    session_approved: bool,
+    command: &str,
+    working_dir: Option<&Path>,
+) -> Explanation {
+    let mut reasons = Vec::new();
+    let policy = match config.policy.evaluate(command) {
+        Some(verdict) if verdict.action == Action::Ask => {
+            let reason = format!(
+                "Command '{}' needs approval: {}",
+                verdict.command, verdict.message
+            );
+            reasons.push(reason.clone());
+            Ok(reason)
+        }
+        Some(verdict) => Err(format!(
+            "Command '{}' is denied by policy: {}",
+            verdict.command, verdict.message
+        )),
+        None => Ok("no rule matches".to_string()),
+    };
+
+    let network = config.egress.check(command).map(|()| {
+        if config.egress.is_empty() {
+            "any host (no --allow-host rules)".to_string()
+        } else {
+            "every URL matches an --allow-host rule".to_string()
+        }
+    });
+
+    let (paths, path_result) = explain_path_safety(
+        command,
+        &config.sandbox_directories,
+        &config.deny_list,
+        &config.safe_patterns,
+        cache,
+        working_dir,
+    );
+    if let Err(reason) = &path_result {
+        reasons.push(reason.clone());
+    }
+
+    let read_only = validate_read_only(
+        command,
+        &config.sandbox_directories,
+        &config.read_only_directories,
+        working_dir,
+    )
+    .and_then(|()| {
+        validate_protected_paths(
+            command,
+            &config.sandbox_directories,
+            &config.protected_paths,
+            working_dir,
+        )
+    });
+
+    let outcome = match (&policy, &network, &read_only) {
+        (Err(reason), _, _) | (_, Err(reason), _) | (_, _, Err(reason)) => {
+            Outcome::Rejected(reason.clone())
+        }
+        _ if reasons.is_empty() => Outcome::Allowed,
+        _ if session_approved => Outcome::ApprovedForSession,
+        _ => Outcome::NeedsApproval(reasons),
+    };
+
+    Explanation {
+        command: command.to_string(),
+        policy,
+        network,
+        paths,
+        path_result,
+        read_only,
+        outcome,
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn status<T>(result: &Result<T, String>, ok: impl fmt::Display) -> String {
+            match result {
+                Ok(_) => ok.to_string(),
+                Err(reason) => format!("FAIL - {}", reason),
+            }
+        }
+
+        writeln!(f, "Command: {}", self.command)?;
+        writeln!(
+            f,
+            "Policy: {}",
+            status(&self.policy, self.policy.as_deref().unwrap_or_default())
+        )?;
+        writeln!(
+            f,
+            "Network: {}",
+            status(&self.network, self.network.as_deref().unwrap_or_default())
+        )?;
+        writeln!(f, "Paths: {}", status(&self.path_result, "ok"))?;
+        for step in &self.paths {
+            writeln!(f, "  {}", step)?;
+        }
+        writeln!(
+            f,
+            "Read-only: {}",
+            status(
+                &self.read_only,
+                "no writes to read-only directories or server files"
+            )
+        )?;
+        match &self.outcome {
+            Outcome::Allowed => write!(f, "Decision: allowed"),
+            Outcome::NeedsApproval(reasons) => {
+                write!(f, "Decision: needs approval - {}", reasons.join(" "))
+            }
+            Outcome::ApprovedForSession => {
+                write!(
+                    f,
+                    "Decision: allowed (approved by the user for this session)"
+                )
+            }
+            Outcome::Rejected(reason) => write!(f, "Decision: rejected - {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::{Classification, DenyList, Policy};
+
+    fn config() -> Config {
+        let sandbox = std::env::temp_dir().join("nu_mcp_explain");
+        std::fs::create_dir_all(sandbox.join("docs")).unwrap();
+        Config {
+            sandbox_directories: vec![sandbox.clone()],
+            read_only_directories: vec![sandbox.join("docs")],
+            deny_list: DenyList::with_defaults(),
+            policy: Policy::from_json(
+                r#"{"rules": [
+                  {"action": "deny", "command": "sudo", "message": "no root"},
+                  {"action": "ask", "command": "kubectl", "subcommand": "delete", "message": "confirm"}
+                ]}"#,
+            )
+            .unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn explain(command: &str) -> Explanation {
+        explain_command(&config(), &PathCache::new(), false, command, None)
+    }
+
+    #[test]
+    fn test_classifies_words() {
+        let explanation = explain("kubectl get --raw /metrics | from json");
+        assert_eq!(explanation.outcome, Outcome::Allowed);
+
+        let classification = |subject: &str| {
+            explanation
+                .paths
+                .iter()
+                .find(|step| step.subject == subject)
+                .map(|step| step.classification)
+        };
+        assert_eq!(classification("--raw"), Some(Classification::Flag));
+        assert_eq!(classification("/metrics"), Some(Classification::Path));
+        assert_eq!(classification("from json"), Some(Classification::Segment));
+        assert_eq!(
+            classification("gh api /user"),
+            None,
+            "only segments of the command are reported"
+        );
+
+        let report = explanation.to_string();
+        assert!(report.contains("Policy: no rule matches"));
+        assert!(report.contains("\"/metrics\": resolves to /metrics, outside the sandbox"));
+        assert!(report.ends_with("Decision: allowed"));
+    }
+
+    #[test]
+    fn test_safe_patterns_and_urls() {
+        let explanation = explain("gh api /repos/o/r; echo https://example.com/x");
+        assert_eq!(
+            explanation.paths[0].classification,
+            Classification::SafePattern
+        );
+        assert!(
+            explanation
+                .paths
+                .iter()
+                .any(|step| step.classification == Classification::Url)
+        );
+    }
+
+    #[test]
+    fn test_outcomes() {
+        assert!(matches!(
+            explain("sudo ls").outcome,
+            Outcome::Rejected(reason) if reason.contains("denied by policy: no root")
+        ));
+        assert!(matches!(
+            explain("kubectl delete pod x").outcome,
+            Outcome::NeedsApproval(reasons) if reasons[0].contains("confirm")
+        ));
+        assert!(matches!(
+            explain("open /etc/passwd").outcome,
+            Outcome::NeedsApproval(reasons) if reasons[0].contains("escapes sandbox")
+        ));
+        assert!(matches!(
+            explain("'x' | save docs/new.md").outcome,
+            Outcome::Rejected(reason) if reason.contains("read-only")
+        ));
+        assert!(matches!(
+            explain("open .env").outcome,
+            Outcome::NeedsApproval(reasons) if reasons[0].contains("deny")
+        ));
+
+        let approved =
+            explain_command(&config(), &PathCache::new(), true, "open /etc/passwd", None);
+        assert_eq!(approved.outcome, Outcome::ApprovedForSession);
+    }
+}
@@ -1,4 +1,6 @@
-use rmcp::model::{CallToolResult, Content, ErrorData};
+use crate::security::{RedactionCounts, Redactor};
+use rmcp::model::{CallToolResult, Content, ErrorData, Meta, RawContent};
+use serde_json::json;
 
 pub struct ResultFormatter;
 
@@ -22,4 +24,40 @@ impl ResultFormatter {
     pub fn invalid_request(message: String) -> Result<CallToolResult, ErrorData> {
         Err(ErrorData::invalid_request(message, None))
     }
+
+    /// Replace secrets in the text of `result` with placeholders. The number
+    /// of redactions per detector goes into `_meta.redactions` (or the error's
+    /// `data`), so the agent knows the output was altered.
+    pub fn redact(
+        result: Result<CallToolResult, ErrorData>,
+        redactor: &Redactor,
+    ) -> Result<CallToolResult, ErrorData> {
+        if redactor.is_empty() {
+            return result;
+        }
+        let mut counts = RedactionCounts::new();
+        match result {
+            Ok(mut result) => {
+                for content in &mut result.content {
+                    if let RawContent::Text(text) = &mut content.raw {
+                        text.text = redactor.redact(&text.text, &mut counts);
+                    }
+                }
+                if !counts.is_empty() {
+                    result
+                        .meta
+                        .get_or_insert_with(Meta::new)
+                        .insert("redactions".to_string(), json!(counts));
+                }
+                Ok(result)
+            }
+            Err(mut error) => {
+                error.message = redactor.redact(&error.message, &mut counts).into();
+                if !counts.is_empty() && error.data.is_none() {
+                    error.data = Some(json!({ "redactions": counts }));
+                }
+                Err(error)
+            }
+        }
+    }
 }
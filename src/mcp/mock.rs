@@ -0,0 +1,42 @@
+use super::approval::{Approval, Approver};
+use std::sync::Mutex;
+
+/// Answers every approval request the same way and records what was asked
+pub struct MockApprover {
+    pub answer: Result<Approval, String>,
+    pub requests: Mutex<Vec<(String, Vec<String>)>>,
+}
+
+impl MockApprover {
+    pub fn new(answer: Approval) -> Self {
+        Self {
+            answer: Ok(answer),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn unsupported(error: &str) -> Self {
+        Self {
+            answer: Err(error.to_string()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+impl Approver for MockApprover {
+    async fn request_approval(
+        &self,
+        command: &str,
+        reasons: &[String],
+    ) -> Result<Approval, String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((command.to_string(), reasons.to_vec()));
+        self.answer.clone()
+    }
+}
@@ -12,14 +12,20 @@ use rmcp::{
     transport,
 };
 
+use self::approval::ElicitationApprover;
+use self::audit::AuditLog;
 use crate::{
     config::Config,
-    execution::{CommandExecutor, NushellExecutor, persistent::PersistentNuExecutor},
-    tools::{NushellToolExecutor, ToolExecutor, discover_tools},
+    execution::{ChildConfig, CommandExecutor, NushellExecutor, persistent::PersistentNuExecutor},
+    security::{Landlock, LandlockPaths},
+    tools::{NushellToolExecutor, ToolExecutor, discover_verified_tools},
 };
 
 const RUN_DESCRIPTION: &str = include_str!("../../docs/run_description.txt");
 const SHELL_DESCRIPTION: &str = include_str!("../../docs/shell_description.txt");
+const SHELL_INPUT_DESCRIPTION: &str = include_str!("../../docs/shell_input_description.txt");
+const SHELL_PEEK_DESCRIPTION: &str = include_str!("../../docs/shell_peek_description.txt");
+const CHECK_COMMAND_DESCRIPTION: &str = include_str!("../../docs/check_command_description.txt");
 
 #[derive(Clone)]
 pub struct NushellTool<S, P, T = NushellToolExecutor>
@@ -225,6 +231,103 @@ where
                 Tool::new("shell", shell_description, Arc::new(shell_schema))
                     .with_title("Run Nushell Command (Persistent Shell)"),
             );
+
+            // ===== Register `shell_input` tool (answers a waiting shell command) =====
+            let mut input_schema = Map::new();
+            input_schema.insert("type".to_string(), Value::String("object".to_string()));
+
+            let mut input_properties = Map::new();
+
+            // Text property (optional)
+            let mut text_prop = Map::new();
+            text_prop.insert("type".to_string(), Value::String("string".to_string()));
+            text_prop.insert(
+                "description".to_string(),
+                Value::String(
+                    "Literal text to type. Not followed by Enter unless \"Enter\" is in keys."
+                        .to_string(),
+                ),
+            );
+            input_properties.insert("text".to_string(), Value::Object(text_prop));
+
+            // Keys property (optional)
+            let mut keys_items = Map::new();
+            keys_items.insert("type".to_string(), Value::String("string".to_string()));
+            let mut keys_prop = Map::new();
+            keys_prop.insert("type".to_string(), Value::String("array".to_string()));
+            keys_prop.insert("items".to_string(), Value::Object(keys_items));
+            keys_prop.insert(
+                "description".to_string(),
+                Value::String(
+                    "Named keys sent after text, in order: Enter, Tab, Space, Escape, Backspace, Up, Down, Left, Right, Home, End, PageUp, PageDown, Delete, Ctrl-<letter>, or a single character like q"
+                        .to_string(),
+                ),
+            );
+            input_properties.insert("keys".to_string(), Value::Object(keys_prop));
+
+            // Timeout property (optional)
+            let mut timeout_prop = Map::new();
+            timeout_prop.insert("type".to_string(), Value::String("integer".to_string()));
+            timeout_prop.insert(
+                "description".to_string(),
+                Value::String(
+                    "Timeout in seconds (default: 300, or MCP_NU_MCP_TIMEOUT env var)".to_string(),
+                ),
+            );
+            timeout_prop.insert("minimum".to_string(), Value::Number(1.into()));
+            input_properties.insert("timeout_seconds".to_string(), Value::Object(timeout_prop));
+
+            input_schema.insert("properties".to_string(), Value::Object(input_properties));
+
+            tools.push(
+                Tool::new(
+                    "shell_input",
+                    SHELL_INPUT_DESCRIPTION,
+                    Arc::new(input_schema),
+                )
+                .with_title("Send Input to Waiting Shell Command"),
+            );
+
+            // ===== Register `shell_peek` tool (read-only view of the running command) =====
+            let mut peek_schema = Map::new();
+            peek_schema.insert("type".to_string(), Value::String("object".to_string()));
+            peek_schema.insert("properties".to_string(), Value::Object(Map::new()));
+
+            tools.push(
+                Tool::new("shell_peek", SHELL_PEEK_DESCRIPTION, Arc::new(peek_schema))
+                    .with_title("Peek at Running Shell Command"),
+            );
+
+            // ===== Register `check_command` tool (dry run of validation) =====
+            let mut check_schema = Map::new();
+            check_schema.insert("type".to_string(), Value::String("object".to_string()));
+
+            let mut check_properties = Map::new();
+            let mut command_prop = Map::new();
+            command_prop.insert("type".to_string(), Value::String("string".to_string()));
+            command_prop.insert(
+                "description".to_string(),
+                Value::String(
+                    "The Nushell command to check, as it would be passed to run or shell"
+                        .to_string(),
+                ),
+            );
+            check_properties.insert("command".to_string(), Value::Object(command_prop));
+
+            check_schema.insert("properties".to_string(), Value::Object(check_properties));
+            check_schema.insert(
+                "required".to_string(),
+                Value::Array(vec![Value::String("command".to_string())]),
+            );
+
+            tools.push(
+                Tool::new(
+                    "check_command",
+                    CHECK_COMMAND_DESCRIPTION,
+                    Arc::new(check_schema),
+                )
+                .with_title("Explain Command Validation (Dry Run)"),
+            );
         }
 
         Ok(ListToolsResult {
@@ -237,32 +340,53 @@ where
     async fn call_tool(
         &self,
         request: CallToolRequestParams,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
-        self.router.route_call(request).await
+        let approver = ElicitationApprover::new(context.peer);
+        self.router
+            .route_call_with_approver(request, &approver)
+            .await
     }
 }
 
 pub async fn run_server(config: Config) -> Result<()> {
-    // Discover extension tools if tools_dir is provided
+    let child = child_config(&config)?;
+    let tool_executor = NushellToolExecutor::new(child.with_env(config.env.tools.clone()))
+        .with_module_env(config.env.modules.clone())
+        .with_sandbox(&config.sandbox_directories, &config.read_only_directories)
+        .with_lock(config.module_lock.clone());
+
+    // Discover extension tools if tools_dir is provided, confined like the tools
     let extensions = if let Some(ref tools_dir) = config.tools_dir {
-        discover_tools(tools_dir).await?
+        discover_verified_tools(tools_dir, &tool_executor).await?
     } else {
         Vec::new()
     };
 
-    let tool_executor = NushellToolExecutor;
-
     // Create path cache (session-scoped, lives for server lifetime)
     let path_cache =
         std::sync::Arc::new(tokio::sync::RwLock::new(crate::security::PathCache::new()));
 
     // Create both executors
-    let stateless_executor = NushellExecutor;
-    let persistent_executor = PersistentNuExecutor::new()
-        .map_err(|e| anyhow::anyhow!("Failed to create persistent shell: {}", e))?;
-    
-    let router = ToolRouter::new(
+    let stateless_executor = NushellExecutor::new(child.with_env(config.env.run.clone()));
+    let persistent_executor =
+        PersistentNuExecutor::with_child_config(child.with_env(config.env.shell.clone()))
+            .map_err(|e| anyhow::anyhow!("Failed to create persistent shell: {}", e))?
+            .with_queue(
+                config.shell_queue_depth,
+                std::time::Duration::from_secs(config.shell_queue_timeout_secs),
+            );
+
+    let audit_log = match &config.audit_log {
+        Some(path) => {
+            let audit_log =
+                AuditLog::open(path, config.audit_log_max_bytes).map_err(anyhow::Error::msg)?;
+            Some(audit_log.with_redactor(config.redactor.clone()))
+        }
+        None => None,
+    };
+
+    let mut router = ToolRouter::new(
         config,
         extensions,
         stateless_executor,
@@ -270,6 +394,9 @@ pub async fn run_server(config: Config) -> Result<()> {
         tool_executor,
         path_cache,
     );
+    if let Some(audit_log) = audit_log {
+        router = router.with_audit_log(audit_log);
+    }
     let tool = NushellTool { router };
     let service = tool.serve(transport::stdio()).await?;
     service.waiting().await?;
@@ -277,12 +404,49 @@ pub async fn run_server(config: Config) -> Result<()> {
     Ok(())
 }
 
+/// Confinement for every `nu` process the server starts
+fn child_config(config: &Config) -> Result<ChildConfig> {
+    Ok(ChildConfig {
+        landlock: landlock(config)?,
+        limits: config.limits,
+        // Each executor gets its own environment in run_server
+        env: Default::default(),
+    })
+}
+
+/// The Landlock ruleset for `--landlock`, if the kernel supports it
+fn landlock(config: &Config) -> Result<Option<Landlock>> {
+    if !config.landlock {
+        return Ok(None);
+    }
+
+    let mut paths = LandlockPaths::new(&config.sandbox_directories, &config.read_only_directories);
+    paths.read_only.extend(config.tools_dir.clone());
+    paths.protect(&config.protected_paths);
+    let landlock = Landlock::new(&paths).map_err(anyhow::Error::msg)?;
+    match &landlock {
+        Some(landlock) => tracing::info!(
+            "Landlock (ABI {}) confines commands to the sandbox",
+            landlock.abi()
+        ),
+        None => tracing::warn!(
+            "Landlock is not supported by this kernel; commands run without kernel-enforced sandboxing"
+        ),
+    }
+    Ok(landlock)
+}
+
+pub mod approval;
+pub mod audit;
+pub mod explain;
 pub mod formatter;
 pub mod router;
 
 #[cfg(test)]
 mod formatter_test;
 #[cfg(test)]
+pub mod mock;
+#[cfg(test)]
 mod mod_test;
 #[cfg(test)]
 mod router_test;
@@ -13,8 +13,9 @@ fn test_get_info_includes_sandbox_info() {
         tools_dir: None,
         enable_run_nu: false,
         sandbox_directories: vec![PathBuf::from("/tmp/sandbox")],
+        ..Default::default()
     };
-    let stateless_executor = NushellExecutor;
+    let stateless_executor = NushellExecutor::default();
     let persistent_executor = MockExecutor::new("test".to_string(), "".to_string());
     let tool_executor = MockToolExecutor::new("test".to_string());
     let cache = Arc::new(RwLock::new(PathCache::new()));
@@ -32,8 +33,9 @@ fn test_get_info_default_sandbox() {
         tools_dir: None,
         enable_run_nu: false,
         sandbox_directories: vec![],
+        ..Default::default()
     };
-    let stateless_executor = NushellExecutor;
+    let stateless_executor = NushellExecutor::default();
     let persistent_executor = MockExecutor::new("test".to_string(), "".to_string());
     let tool_executor = MockToolExecutor::new("test".to_string());
     let cache = Arc::new(RwLock::new(PathCache::new()));
@@ -50,8 +52,9 @@ fn test_get_info_basic_fields() {
         tools_dir: None,
         enable_run_nu: false,
         sandbox_directories: vec![],
+        ..Default::default()
     };
-    let stateless_executor = NushellExecutor;
+    let stateless_executor = NushellExecutor::default();
     let persistent_executor = MockExecutor::new("test".to_string(), "".to_string());
     let tool_executor = MockToolExecutor::new("test".to_string());
     let cache = Arc::new(RwLock::new(PathCache::new()));
@@ -71,8 +74,9 @@ fn test_get_info_marks_current_directory() {
         tools_dir: None,
         enable_run_nu: false,
         sandbox_directories: vec![cwd.clone(), PathBuf::from("/tmp")],
+        ..Default::default()
     };
-    let stateless_executor = NushellExecutor;
+    let stateless_executor = NushellExecutor::default();
     let persistent_executor = MockExecutor::new("test".to_string(), "".to_string());
     let tool_executor = MockToolExecutor::new("test".to_string());
     let cache = Arc::new(RwLock::new(PathCache::new()));
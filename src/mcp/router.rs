@@ -1,15 +1,28 @@
+use super::approval::{Approval, Approver, NoApprover, SessionApprovals};
+use super::audit::{AuditLog, CallRecord, Decision};
+use super::explain::explain_command;
 use super::formatter::ResultFormatter;
 use crate::config::Config;
 use crate::execution::CommandExecutor;
-use crate::security::{PathCache, validate_path_safety_with_cache};
-use crate::tools::{ExtensionTool, NushellToolExecutor, ToolExecutor};
+use crate::execution::keys::{TypedLine, encode_key};
+use crate::security::{
+    Action, PathCache, validate_path_argument, validate_path_safety_with_cache,
+    validate_protected_paths, validate_read_only,
+};
+use crate::tools::{ExtensionTool, NushellToolExecutor, ToolExecutor, path_arguments};
 use rmcp::model::CallToolRequestParams;
 use rmcp::{
     model::{CallToolResult, ErrorData},
     serde_json,
 };
-use std::{env, path::PathBuf, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+use tokio::sync::{Mutex, RwLock};
+use tracing::info;
 
 #[derive(Clone)]
 pub struct ToolRouter<S, P, T = NushellToolExecutor>
@@ -25,6 +38,12 @@ where
     pub tool_executor: T,
     /// Path cache injected as dependency (Arc<RwLock> allows concurrent reads)
     path_cache: Arc<RwLock<PathCache>>,
+    /// Commands the user approved for this session
+    approvals: SessionApprovals,
+    /// Where every call is recorded, if auditing is enabled
+    audit: Option<Arc<AuditLog>>,
+    /// The line typed into the shell with `shell_input` and not submitted yet
+    typed_line: Arc<Mutex<TypedLine>>,
 }
 
 impl<S, P, T> ToolRouter<S, P, T>
@@ -48,24 +67,170 @@ where
             persistent_executor,
             tool_executor,
             path_cache,
+            approvals: SessionApprovals::new(),
+            audit: None,
+            typed_line: Arc::default(),
         }
     }
 
+    /// Record every routed call in `audit`
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(Arc::new(audit));
+        self
+    }
+
+    /// Route a call without a client to ask: commands needing approval are rejected
     pub async fn route_call(
         &self,
         request: CallToolRequestParams,
     ) -> Result<CallToolResult, ErrorData> {
-        let tool_name = request.name.clone();
-        match tool_name.as_ref() {
-            "run" => self.handle_run(request).await,
-            "shell" => self.handle_shell(request).await,
-            tool_name => self.handle_extension_tool(request, tool_name).await,
-        }
+        self.route_call_with_approver(request, &NoApprover).await
     }
 
-    async fn handle_run(
+    /// Route a call, asking `approver` about commands that need the user's approval
+    pub async fn route_call_with_approver<A: Approver>(
         &self,
         request: CallToolRequestParams,
+        approver: &A,
+    ) -> Result<CallToolResult, ErrorData> {
+        let started = Instant::now();
+        let mut record = CallRecord::default();
+        let result = match request.name.as_ref() {
+            "run" => self.handle_run(&request, approver, &mut record).await,
+            "shell" => self.handle_shell(&request, approver, &mut record).await,
+            "shell_input" => {
+                self.handle_shell_input(&request, approver, &mut record)
+                    .await
+            }
+            "shell_peek" => Ok(self.handle_shell_peek()),
+            "check_command" => self.handle_check_command(&request).await,
+            tool_name => {
+                self.handle_extension_tool(&request, tool_name, &mut record)
+                    .await
+            }
+        };
+        let result = ResultFormatter::redact(result, &self.config.redactor);
+
+        if let Some(audit) = &self.audit {
+            audit.record(
+                &request.name,
+                request.arguments.as_ref(),
+                &record,
+                started.elapsed(),
+                &result,
+            );
+        }
+        result
+    }
+
+    /// Checks a command must pass before any executor runs it: the command
+    /// policy, the network allowlist, path validation (with the injected cache),
+    /// read-only directories and protected server files.
+    ///
+    /// Path validation failures and policy "ask" rules go to the user through
+    /// `approver`. Approvals for the session are remembered per command.
+    /// The decision and its reason are noted in `record`.
+    async fn validate_command<A: Approver>(
+        &self,
+        command: &str,
+        working_dir: Option<&Path>,
+        approver: &A,
+        record: &mut CallRecord,
+    ) -> Result<(), String> {
+        let result = self
+            .check_command(command, working_dir, approver, record)
+            .await;
+        if let Err(reason) = &result {
+            record.decision = Decision::Rejected;
+            record.reason = Some(reason.clone());
+        }
+        result
+    }
+
+    async fn check_command<A: Approver>(
+        &self,
+        command: &str,
+        working_dir: Option<&Path>,
+        approver: &A,
+        record: &mut CallRecord,
+    ) -> Result<(), String> {
+        let mut reasons = Vec::new();
+        match self.config.policy.evaluate(command) {
+            Some(verdict) if verdict.action == Action::Ask => reasons.push(format!(
+                "Command '{}' needs approval: {}",
+                verdict.command, verdict.message
+            )),
+            Some(verdict) => {
+                return Err(format!(
+                    "Command '{}' is denied by policy: {}",
+                    verdict.command, verdict.message
+                ));
+            }
+            None => {}
+        }
+        self.config.egress.check(command)?;
+
+        // Use write lock - async-aware, no poisoning possible
+        {
+            let mut cache = self.path_cache.write().await;
+            if let Err(reason) = validate_path_safety_with_cache(
+                command,
+                &self.config.sandbox_directories,
+                &self.config.deny_list,
+                &self.config.safe_patterns,
+                &mut cache,
+                working_dir,
+            ) {
+                reasons.push(reason);
+            }
+        }
+
+        if !reasons.is_empty() {
+            record.decision = Decision::Approved;
+            record.reason = Some(reasons.join(" "));
+        }
+        if !reasons.is_empty() && !self.approvals.contains(command).await {
+            match approver.request_approval(command, &reasons).await {
+                Ok(Approval::Once) => info!("User approved command once: {:?}", command),
+                Ok(Approval::Session) => {
+                    info!("User approved command for the session: {:?}", command);
+                    self.approvals.insert(command).await;
+                }
+                Ok(Approval::Denied) => {
+                    return Err(format!(
+                        "The user denied the command. {}",
+                        reasons.join(" ")
+                    ));
+                }
+                Err(e) => {
+                    return Err(format!(
+                        "{} (User approval was not requested: {})",
+                        reasons.join(" "),
+                        e
+                    ));
+                }
+            }
+        }
+
+        validate_read_only(
+            command,
+            &self.config.sandbox_directories,
+            &self.config.read_only_directories,
+            working_dir,
+        )?;
+        validate_protected_paths(
+            command,
+            &self.config.sandbox_directories,
+            &self.config.protected_paths,
+            working_dir,
+        )
+    }
+
+    async fn handle_run<A: Approver>(
+        &self,
+        request: &CallToolRequestParams,
+        approver: &A,
+        record: &mut CallRecord,
     ) -> Result<CallToolResult, ErrorData> {
         let args = request.arguments.as_ref();
 
@@ -83,29 +248,33 @@ where
         let work_dir = determine_working_directory(&self.config.sandbox_directories)
             .map_err(|e| ErrorData::internal_error(e, None))?;
 
-        // Validate command for path safety (with injected cache)
-        let validation_result = {
-            let mut cache = self.path_cache.write().await;
-            validate_path_safety_with_cache(command, &self.config.sandbox_directories, &mut cache)
-        };
+        // Validate command against the policy and for path safety (with injected cache)
+        let validation_result = self.validate_command(command, None, approver, record).await;
 
         if let Err(msg) = validation_result {
             return ResultFormatter::invalid_request(msg);
         }
 
         // Execute using stateless executor (concurrent)
-        let (stdout, stderr) = self
+        record.executor = Some("stateless");
+        let output = self
             .stateless_executor
-            .execute(command, &work_dir, timeout_secs)
+            .execute_with_status(command, &work_dir, timeout_secs)
             .await
             .map_err(|e| ErrorData::internal_error(e, None))?;
+        record.exit_code = output.exit_code;
 
-        Ok(ResultFormatter::success_with_stderr(stdout, stderr))
+        Ok(ResultFormatter::success_with_stderr(
+            output.stdout,
+            output.stderr,
+        ))
     }
 
-    async fn handle_shell(
+    async fn handle_shell<A: Approver>(
         &self,
-        request: CallToolRequestParams,
+        request: &CallToolRequestParams,
+        approver: &A,
+        record: &mut CallRecord,
     ) -> Result<CallToolResult, ErrorData> {
         let args = request.arguments.as_ref();
 
@@ -147,12 +316,12 @@ where
         let work_dir = determine_working_directory(&self.config.sandbox_directories)
             .map_err(|e| ErrorData::internal_error(e, None))?;
 
-        // Validate command for path safety (with injected cache)
-        // Use write lock - async-aware, no poisoning possible
-        let validation_result = {
-            let mut cache = self.path_cache.write().await;
-            validate_path_safety_with_cache(command, &self.config.sandbox_directories, &mut cache)
-        };
+        // Validate command against the policy and for path safety (with injected cache).
+        // Relative paths resolve where the shell is now, after earlier `cd`s.
+        let working_dir = self.persistent_executor.working_dir();
+        let validation_result = self
+            .validate_command(command, working_dir.as_deref(), approver, record)
+            .await;
 
         if let Err(msg) = validation_result {
             if std::env::var("MCP_PTY_TRACE").is_ok()
@@ -167,19 +336,147 @@ where
             return ResultFormatter::invalid_request(msg);
         }
 
+        // Input typed for an earlier command doesn't carry over to this one
+        self.typed_line.lock().await.clear();
+
+        record.executor = Some("persistent");
+        let output = self
+            .persistent_executor
+            .execute_with_status(command, &work_dir, timeout_secs)
+            .await
+            .map_err(|e| ErrorData::internal_error(e, None))?;
+        record.exit_code = output.exit_code;
+
+        Ok(ResultFormatter::success_with_stderr(
+            output.stdout,
+            output.stderr,
+        ))
+    }
+
+    async fn handle_shell_input<A: Approver>(
+        &self,
+        request: &CallToolRequestParams,
+        approver: &A,
+        record: &mut CallRecord,
+    ) -> Result<CallToolResult, ErrorData> {
+        let args = request.arguments.as_ref();
+
+        let text = args
+            .and_then(|args| args.get("text"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+
+        let keys: Vec<&str> = args
+            .and_then(|args| args.get("keys"))
+            .and_then(|v| v.as_array())
+            .map(|keys| keys.iter().filter_map(|k| k.as_str()).collect())
+            .unwrap_or_default();
+
+        if text.is_empty() && keys.is_empty() {
+            return ResultFormatter::invalid_request(
+                "Provide 'text' and/or 'keys' to send".to_string(),
+            );
+        }
+
+        let mut input = text.as_bytes().to_vec();
+        for key in keys {
+            match encode_key(key) {
+                Ok(bytes) => input.extend(bytes),
+                Err(msg) => return ResultFormatter::invalid_request(msg),
+            }
+        }
+
+        // Typed text can reach a nested shell or REPL, so it gets the same checks.
+        // A line typed over several calls is checked as a whole: every line this
+        // input submits, and the unfinished one. Rejected input isn't typed.
+        let mut typed_line = self.typed_line.lock().await;
+        let mut line = typed_line.clone();
+        let mut lines = line.feed(&input);
+        lines.push(line.current());
+        let working_dir = self.persistent_executor.working_dir();
+        for typed in lines.iter().filter(|typed| !typed.trim().is_empty()) {
+            let validation_result = self
+                .validate_command(typed, working_dir.as_deref(), approver, record)
+                .await;
+            if let Err(msg) = validation_result {
+                return ResultFormatter::invalid_request(msg);
+            }
+        }
+        *typed_line = line;
+
+        let timeout_secs = args
+            .and_then(|args| args.get("timeout_seconds"))
+            .and_then(|v| v.as_u64());
+
+        record.executor = Some("persistent");
         let (stdout, stderr) = self
             .persistent_executor
-            .execute(command, &work_dir, timeout_secs)
+            .send_input(&input, timeout_secs)
             .await
             .map_err(|e| ErrorData::internal_error(e, None))?;
 
         Ok(ResultFormatter::success_with_stderr(stdout, stderr))
     }
 
+    fn handle_shell_peek(&self) -> CallToolResult {
+        match self.persistent_executor.peek() {
+            Some(running) => ResultFormatter::success(running.to_string()),
+            None => ResultFormatter::success("Shell is idle: no command is running.".to_string()),
+        }
+    }
+
+    /// Explain what validation would decide about a command, without running it
+    async fn handle_check_command(
+        &self,
+        request: &CallToolRequestParams,
+    ) -> Result<CallToolResult, ErrorData> {
+        let Some(command) = request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get("command"))
+            .and_then(|v| v.as_str())
+        else {
+            return ResultFormatter::invalid_request("Provide the 'command' to check".to_string());
+        };
+
+        let approved = self.approvals.contains(command).await;
+        let cache = self.path_cache.read().await;
+        let working_dir = self.persistent_executor.working_dir();
+        let explanation = explain_command(
+            &self.config,
+            &cache,
+            approved,
+            command,
+            working_dir.as_deref(),
+        );
+        Ok(ResultFormatter::success(explanation.to_string()))
+    }
+
+    /// Check the arguments the tool's schema marks as paths against the sandbox
+    fn validate_tool_paths(
+        &self,
+        extension: &ExtensionTool,
+        request: &CallToolRequestParams,
+    ) -> Result<(), String> {
+        let Some(args) = &request.arguments else {
+            return Ok(());
+        };
+        for (name, path) in path_arguments(&extension.tool_definition.input_schema, args) {
+            validate_path_argument(
+                &path,
+                &self.config.sandbox_directories,
+                &self.config.deny_list,
+            )
+            .map_err(|reason| format!("Argument '{}': {}", name, reason))?;
+        }
+        Ok(())
+    }
+
     async fn handle_extension_tool(
         &self,
-        request: CallToolRequestParams,
+        request: &CallToolRequestParams,
         tool_name: &str,
+        record: &mut CallRecord,
     ) -> Result<CallToolResult, ErrorData> {
         // Look for extension tool
         if let Some(extension) = self
@@ -187,6 +484,12 @@ where
             .iter()
             .find(|e| e.tool_definition.name.as_ref() == tool_name)
         {
+            if let Err(reason) = self.validate_tool_paths(extension, request) {
+                record.decision = Decision::Rejected;
+                record.reason = Some(reason.clone());
+                return ResultFormatter::invalid_request(reason);
+            }
+
             // Convert arguments to JSON string
             let args_json = match &request.arguments {
                 Some(args) => serde_json::to_string(args)
@@ -195,12 +498,17 @@ where
             };
 
             // Execute extension tool (timeout=None for now, tools don't expose it yet)
+            record.executor = Some("tool");
             match self
                 .tool_executor
                 .execute_tool(extension, tool_name, &args_json, None)
                 .await
             {
-                Ok(output) => Ok(ResultFormatter::success(output)),
+                Ok(output) => {
+                    // Tools fail on a non-zero exit, so success means exit code 0
+                    record.exit_code = Some(0);
+                    Ok(ResultFormatter::success(output))
+                }
                 Err(e) => ResultFormatter::error(e.to_string()),
             }
         } else {
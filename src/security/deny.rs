@@ -0,0 +1,163 @@
+//! Deny list for sensitive files inside the sandbox
+//!
+//! Patterns are globs matched against paths relative to the sandbox directory
+//! that contains them, like `.gitignore` entries:
+//! - A pattern without `/` matches a file or directory name at any depth (`.env`, `id_rsa*`)
+//! - A pattern with `/` matches from the sandbox root down (`.git/config`, `secrets/*.json`)
+//! - Matching a directory denies everything inside it (`.aws` covers `.aws/credentials`)
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+
+/// Files that commonly hold credentials and keys, denied unless `--no-default-deny` is given
+pub const DEFAULT_DENY_PATTERNS: &[&str] = &[
+    ".env",
+    ".env.*",
+    ".git/config",
+    ".git-credentials",
+    ".netrc",
+    ".npmrc",
+    ".pypirc",
+    ".pgpass",
+    ".ssh",
+    ".aws",
+    ".gnupg",
+    ".docker/config.json",
+    ".kube/config",
+    "id_rsa*",
+    "id_dsa*",
+    "id_ecdsa*",
+    "id_ed25519*",
+    "*.pem",
+    "*.key",
+];
+
+/// Compiled deny patterns. The default is empty and denies nothing.
+#[derive(Debug, Clone, Default)]
+pub struct DenyList {
+    rules: Vec<String>,
+    set: GlobSet,
+}
+
+impl DenyList {
+    /// Compile deny patterns, failing on the first invalid glob
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        let mut rules = Vec::with_capacity(patterns.len());
+
+        for pattern in patterns {
+            let rule = pattern.as_ref().trim().trim_end_matches('/');
+            if rule.is_empty() {
+                continue;
+            }
+            let anchored = match rule.strip_prefix('/') {
+                Some(rest) => rest.to_string(),
+                None if rule.contains('/') => rule.to_string(),
+                None => format!("**/{}", rule),
+            };
+            let glob = GlobBuilder::new(&anchored)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("Invalid deny pattern '{}': {}", rule, e))?;
+            builder.add(glob);
+            rules.push(rule.to_string());
+        }
+
+        let set = builder
+            .build()
+            .map_err(|e| format!("Invalid deny patterns: {}", e))?;
+        Ok(Self { rules, set })
+    }
+
+    /// The default deny list
+    pub fn with_defaults() -> Self {
+        Self::new(DEFAULT_DENY_PATTERNS).expect("default deny patterns are valid")
+    }
+
+    /// The patterns in this list
+    pub fn rules(&self) -> &[String] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Find the rule denying `path`, which must be inside one of `sandboxes`.
+    /// The path and every directory above it up to the sandbox are checked.
+    pub fn matching_rule(&self, path: &Path, sandboxes: &[PathBuf]) -> Option<&str> {
+        if self.is_empty() {
+            return None;
+        }
+        let relative = sandboxes
+            .iter()
+            .filter_map(|sandbox| path.strip_prefix(sandbox).ok())
+            .min_by_key(|relative| relative.components().count())?;
+
+        let mut prefix = PathBuf::new();
+        for component in relative.components() {
+            prefix.push(component);
+            if let Some(&index) = self.set.matches(&prefix).first() {
+                return Some(&self.rules[index]);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(list: &DenyList, path: &str) -> Option<String> {
+        list.matching_rule(&Path::new("/work").join(path), &[PathBuf::from("/work")])
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn test_defaults_match_credentials() {
+        let list = DenyList::with_defaults();
+        assert_eq!(rule(&list, ".env").as_deref(), Some(".env"));
+        assert_eq!(rule(&list, "app/.env.local").as_deref(), Some(".env.*"));
+        assert_eq!(rule(&list, ".git/config").as_deref(), Some(".git/config"));
+        assert_eq!(rule(&list, "deploy/id_rsa.pub").as_deref(), Some("id_rsa*"));
+        assert_eq!(rule(&list, ".aws/credentials").as_deref(), Some(".aws"));
+        assert_eq!(rule(&list, "certs/server.pem").as_deref(), Some("*.pem"));
+    }
+
+    #[test]
+    fn test_defaults_allow_ordinary_files() {
+        let list = DenyList::with_defaults();
+        assert_eq!(rule(&list, "src/main.rs"), None);
+        assert_eq!(rule(&list, ".git/HEAD"), None);
+        assert_eq!(rule(&list, "docs/environment.md"), None);
+        assert_eq!(rule(&list, "sub/.git/config"), None);
+    }
+
+    #[test]
+    fn test_anchored_and_nested_patterns() {
+        let list = DenyList::new(&["/secrets", "config/*.json"]).unwrap();
+        assert_eq!(rule(&list, "secrets/db.txt").as_deref(), Some("/secrets"));
+        assert_eq!(rule(&list, "app/secrets/db.txt"), None);
+        assert_eq!(
+            rule(&list, "config/prod.json").as_deref(),
+            Some("config/*.json")
+        );
+        assert_eq!(rule(&list, "config/nested/prod.json"), None);
+    }
+
+    #[test]
+    fn test_paths_outside_sandbox_are_not_matched() {
+        let list = DenyList::with_defaults();
+        assert_eq!(
+            list.matching_rule(Path::new("/other/.env"), &[PathBuf::from("/work")]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = DenyList::new(&["a[b"]).unwrap_err();
+        assert!(err.contains("a[b"), "{}", err);
+    }
+}
@@ -0,0 +1,353 @@
+//! Static expansion of home references and path-producing variables
+//!
+//! Resolves what a path argument refers to before the command runs:
+//! - `~`, `~/path`, `~user` and `~user/path`
+//! - Nushell's `$nu.*` paths (`$nu.home-path`, `$nu.config-path`, ...)
+//! - Environment variables holding paths (`$env.HOME`, `$env.PWD`, `$env.TMPDIR`, ...)
+//! - Variables assigned in the same command (`let p = "/etc"; open $p`)
+//! - Nushell's n-dots in paths (`...` is `../..`)
+
+use super::lexer::{self, Block, Command, Part, Word};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variables holding paths, resolved from the server's environment
+const PATH_ENV_VARS: &[&str] = &[
+    "HOME",
+    "USERPROFILE",
+    "TMPDIR",
+    "TMP",
+    "TEMP",
+    "XDG_CONFIG_HOME",
+    "XDG_DATA_HOME",
+    "XDG_CACHE_HOME",
+    "XDG_STATE_HOME",
+    "XDG_RUNTIME_DIR",
+];
+
+/// Limit for variables defined in terms of other variables (also stops cycles)
+const MAX_DEPTH: usize = 8;
+
+/// Variables with a statically known value for one command
+pub struct Scope {
+    pwd: PathBuf,
+    /// Right-hand sides of assignments; `None` if assigned more than once
+    assignments: HashMap<String, Option<Assignment>>,
+}
+
+/// `let name = <value> | <pipeline>...`
+struct Assignment {
+    value: Vec<Word>,
+    pipeline: Vec<Command>,
+}
+
+impl Scope {
+    /// Collect the assignments in a command. `$env.PWD` resolves to `pwd`.
+    pub fn new(block: &Block, pwd: &Path) -> Self {
+        let mut scope = Self {
+            pwd: pwd.to_path_buf(),
+            assignments: HashMap::new(),
+        };
+        scope.collect_assignments(block);
+        scope
+    }
+
+    /// Resolve a variable reference such as `$env.HOME` or `$p`
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        self.resolve_at(name, 0)
+    }
+
+    fn resolve_at(&self, name: &str, depth: usize) -> Option<String> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let name = name.trim_end_matches('?');
+
+        match self.assignments.get(name) {
+            Some(assignment) => {
+                let assignment = assignment.as_ref()?;
+                lexer::fold_pipeline(&assignment.value, &assignment.pipeline, &|name| {
+                    self.resolve_at(name, depth + 1)
+                })
+            }
+            None => builtin_variable(name, &self.pwd),
+        }
+    }
+
+    fn collect_assignments(&mut self, block: &Block) {
+        for pipeline in block {
+            if let Some((first, rest)) = pipeline.split_first() {
+                self.record_assignment(first, rest);
+            }
+            for command in pipeline {
+                for word in &command.words {
+                    self.collect_word_assignments(word);
+                }
+            }
+        }
+    }
+
+    fn collect_word_assignments(&mut self, word: &Word) {
+        for part in &word.parts {
+            match part {
+                Part::Subexpression(block) | Part::Block(block) => self.collect_assignments(block),
+                Part::List(items) => items
+                    .iter()
+                    .for_each(|item| self.collect_word_assignments(item)),
+                Part::Text(_) | Part::Variable(_) => {}
+            }
+        }
+    }
+
+    /// Record `let/mut/const name = ...` and `$name = ...`.
+    /// Compound assignments (`+=`) and reassignments make the variable unknown.
+    fn record_assignment(&mut self, command: &Command, rest: &[Command]) {
+        let words = &command.words;
+        let literal = |i: usize| words.get(i).and_then(Word::literal);
+
+        let (name, operator, value) = match literal(0).as_deref() {
+            Some("let" | "mut" | "const") => match literal(1) {
+                Some(name) => (
+                    format!("${}", name),
+                    literal(2),
+                    &words[3.min(words.len())..],
+                ),
+                None => return,
+            },
+            _ => match words.first().map(|word| word.parts.as_slice()) {
+                Some([Part::Variable(name)]) => {
+                    (name.clone(), literal(1), &words[2.min(words.len())..])
+                }
+                _ => return,
+            },
+        };
+
+        let assignment = match operator.as_deref() {
+            Some("=") if !value.is_empty() => Some(Assignment {
+                value: value.to_vec(),
+                pipeline: rest.to_vec(),
+            }),
+            Some(op) if op.ends_with('=') => None,
+            _ => return,
+        };
+
+        self.assignments
+            .entry(name)
+            .and_modify(|existing| *existing = None)
+            .or_insert(assignment);
+    }
+}
+
+/// Values of Nushell's built-in path variables
+fn builtin_variable(name: &str, pwd: &Path) -> Option<String> {
+    let path = match name {
+        "$env.PWD" => Some(pwd.to_path_buf()),
+        "$nu.home-path" | "$nu.home-dir" => home_dir(),
+        "$nu.temp-path" | "$nu.temp-dir" => Some(std::env::temp_dir()),
+        "$nu.default-config-dir" => nu_config_dir(),
+        "$nu.config-path" => nu_config_dir().map(|dir| dir.join("config.nu")),
+        "$nu.env-path" => nu_config_dir().map(|dir| dir.join("env.nu")),
+        "$nu.history-path" => nu_config_dir().map(|dir| dir.join("history.txt")),
+        "$nu.loginshell-path" => nu_config_dir().map(|dir| dir.join("login.nu")),
+        "$nu.plugin-path" => nu_config_dir().map(|dir| dir.join("plugin.msgpackz")),
+        "$nu.data-dir" => platform_dir(
+            "XDG_DATA_HOME",
+            ".local/share",
+            "Library/Application Support",
+        )
+        .map(|dir| dir.join("nushell")),
+        "$nu.cache-dir" => platform_dir("XDG_CACHE_HOME", ".cache", "Library/Caches")
+            .map(|dir| dir.join("nushell")),
+        _ => name
+            .strip_prefix("$env.")
+            .filter(|var| PATH_ENV_VARS.contains(var))
+            .and_then(std::env::var_os)
+            .map(PathBuf::from),
+    }?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+/// Nushell's config directory: `$XDG_CONFIG_HOME/nushell`, else the platform config dir
+fn nu_config_dir() -> Option<PathBuf> {
+    platform_dir("XDG_CONFIG_HOME", ".config", "Library/Application Support")
+        .map(|dir| dir.join("nushell"))
+}
+
+/// An XDG directory, falling back to the platform default under the home directory
+fn platform_dir(xdg_var: &str, unix_default: &str, macos_default: &str) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(xdg_var).map(PathBuf::from)
+        && dir.is_absolute()
+    {
+        return Some(dir);
+    }
+    let default = if cfg!(target_os = "macos") {
+        macos_default
+    } else {
+        unix_default
+    };
+    home_dir().map(|home| home.join(default))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Expand `~`, `~/path`, `~user` and `~user/path`.
+/// Returns `None` for words without a leading `~` and for unknown users,
+/// which Nushell leaves unexpanded.
+pub fn expand_tilde(word: &str) -> Option<String> {
+    let rest = word.strip_prefix('~')?;
+    let (user, path) = match rest.split_once('/') {
+        Some((user, path)) => (user, Some(path)),
+        None => (rest, None),
+    };
+
+    let home = if user.is_empty() {
+        home_dir()?
+    } else {
+        user_home(user)?
+    };
+    let expanded = match path {
+        Some(path) => home.join(path),
+        None => home,
+    };
+    Some(expanded.to_string_lossy().into_owned())
+}
+
+/// Expand path components made of three or more dots, which Nushell reads
+/// as one level up per extra dot (`...` is `../..`, `....` is `../../..`)
+pub fn expand_ndots(path: &str) -> String {
+    path.split('/')
+        .map(|component| {
+            if component.len() > 2 && component.chars().all(|c| c == '.') {
+                vec![".."; component.len() - 1].join("/")
+            } else {
+                component.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Look up a user's home directory in the password database
+#[cfg(unix)]
+fn user_home(user: &str) -> Option<PathBuf> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = CString::new(user).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: passwd is plain old data; getpwnam_r fills it in
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+
+    // SAFETY: all pointers are valid for the duration of the call and buf.len()
+    // is the real size of buf. On success the strings in pwd point into buf.
+    let rc = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+
+    // SAFETY: pw_dir is a NUL-terminated string inside buf, which is still alive
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+/// `~user` is only expanded on Unix
+#[cfg(not(unix))]
+fn user_home(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(source: &str) -> Scope {
+        Scope::new(&lexer::parse(source), Path::new("/work"))
+    }
+
+    #[test]
+    fn test_expand_tilde_home() {
+        let home = home_dir().unwrap();
+        assert_eq!(expand_tilde("~"), Some(home.to_string_lossy().into_owned()));
+        assert_eq!(
+            expand_tilde("~/.ssh/id_rsa"),
+            Some(home.join(".ssh/id_rsa").to_string_lossy().into_owned())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_tilde_other_user() {
+        assert_eq!(expand_tilde("~root/.ssh"), Some("/root/.ssh".to_string()));
+        assert_eq!(expand_tilde("~no-such-user-nu-mcp/x"), None);
+    }
+
+    #[test]
+    fn test_expand_tilde_ignores_other_words() {
+        assert_eq!(expand_tilde("file~"), None);
+        assert_eq!(expand_tilde("/tmp/~x"), None);
+    }
+
+    #[test]
+    fn test_expand_ndots() {
+        assert_eq!(expand_ndots("..."), "../..");
+        assert_eq!(expand_ndots("..../etc/hostname"), "../../../etc/hostname");
+        assert_eq!(expand_ndots("src/.../x"), "src/../../x");
+        assert_eq!(expand_ndots("../.hidden/...txt"), "../.hidden/...txt");
+        assert_eq!(expand_ndots("/etc/.."), "/etc/..");
+    }
+
+    #[test]
+    fn test_builtin_variables() {
+        let scope = scope("");
+        let home = home_dir().unwrap().to_string_lossy().into_owned();
+        assert_eq!(scope.resolve("$env.PWD"), Some("/work".to_string()));
+        assert_eq!(scope.resolve("$env.HOME"), Some(home.clone()));
+        assert_eq!(scope.resolve("$nu.home-path"), Some(home));
+        assert!(
+            scope
+                .resolve("$nu.config-path")
+                .unwrap()
+                .ends_with("nushell/config.nu")
+        );
+        assert_eq!(scope.resolve("$env.SOME_TOKEN"), None);
+        assert_eq!(scope.resolve("$nu.current-exe"), None);
+    }
+
+    #[test]
+    fn test_assigned_variables() {
+        let scope = scope(r#"let dir = "/etc"; let file = $dir | path join passwd; mut n = 1"#);
+        assert_eq!(scope.resolve("$dir"), Some("/etc".to_string()));
+        assert_eq!(scope.resolve("$file"), Some("/etc/passwd".to_string()));
+    }
+
+    #[test]
+    fn test_assignment_overrides_environment() {
+        let scope = scope(r#"$env.HOME = "/srv/other""#);
+        assert_eq!(scope.resolve("$env.HOME"), Some("/srv/other".to_string()));
+    }
+
+    #[test]
+    fn test_reassigned_variable_is_unknown() {
+        assert_eq!(scope(r#"mut p = "a"; $p = "/etc""#).resolve("$p"), None);
+        assert_eq!(scope(r#"mut p = "/"; $p += "etc""#).resolve("$p"), None);
+    }
+
+    #[test]
+    fn test_command_call_assignment_is_unknown() {
+        assert_eq!(scope("let p = pwd").resolve("$p"), None);
+        assert_eq!(scope("let a = $b; let b = $a").resolve("$a"), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! Explanations of path validation decisions
+//!
+//! A rejection names one path, which rarely says why a command was checked
+//! the way it was. Path validation can record what it did with every command
+//! segment and word it looked at, so a dry run can show how a false positive
+//! like `kubectl get /metrics` came about.
+
+use std::fmt;
+
+/// How path validation treated a command segment or word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Classification {
+    /// A segment matching a safe command pattern; none of its words are checked
+    SafePattern,
+    /// A segment whose words are checked one by one
+    Segment,
+    /// A script passed to a shell (`sh -c "..."`), checked as a command of its own
+    Script,
+    /// A path-like string remembered as not being a filesystem path
+    CacheHit,
+    Flag,
+    CommonCommand,
+    Url,
+    /// A word without path separators outside filesystem position
+    PlainWord,
+    /// A word checked as a filesystem path
+    Path,
+    /// A pattern in filesystem position, checked by the directory it expands in
+    Glob,
+    /// A filesystem argument that can't be resolved before the command runs
+    Unresolved,
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Classification::SafePattern => "safe pattern",
+            Classification::Segment => "segment",
+            Classification::Script => "script",
+            Classification::CacheHit => "cache hit",
+            Classification::Flag => "flag",
+            Classification::CommonCommand => "common command",
+            Classification::Url => "url",
+            Classification::PlainWord => "plain word",
+            Classification::Path => "path",
+            Classification::Glob => "glob",
+            Classification::Unresolved => "unresolved",
+        })
+    }
+}
+
+/// One decision made during path validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// The segment or word the decision is about
+    pub subject: String,
+    pub classification: Classification,
+    /// What was concluded, e.g. the resolved path and whether it is in the sandbox
+    pub detail: String,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<14} {:?}",
+            self.classification.to_string(),
+            self.subject
+        )?;
+        if !self.detail.is_empty() {
+            write!(f, ": {}", self.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where validation records its steps. Disabled during normal validation,
+/// so details are only formatted for dry runs.
+pub(crate) struct Trace {
+    steps: Option<Vec<Step>>,
+}
+
+impl Trace {
+    pub(crate) fn off() -> Self {
+        Self { steps: None }
+    }
+
+    pub(crate) fn on() -> Self {
+        Self {
+            steps: Some(Vec::new()),
+        }
+    }
+
+    pub(crate) fn note(
+        &mut self,
+        subject: &str,
+        classification: Classification,
+        detail: impl FnOnce() -> String,
+    ) {
+        if let Some(steps) = &mut self.steps {
+            steps.push(Step {
+                subject: subject.to_string(),
+                classification,
+                detail: detail(),
+            });
+        }
+    }
+
+    pub(crate) fn into_steps(self) -> Vec<Step> {
+        self.steps.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_records_only_when_on() {
+        let mut trace = Trace::off();
+        trace.note("/metrics", Classification::Path, || {
+            unreachable!("details aren't formatted when tracing is off")
+        });
+        assert!(trace.into_steps().is_empty());
+
+        let mut trace = Trace::on();
+        trace.note("--raw", Classification::Flag, String::new);
+        trace.note("/etc", Classification::Path, || {
+            "outside the sandbox".into()
+        });
+        let steps = trace.into_steps();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].to_string(), "flag           \"--raw\"");
+        assert_eq!(
+            steps[1].to_string(),
+            "path           \"/etc\": outside the sandbox"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Glob patterns in filesystem position
+//!
+//! Nushell expands `*`, `?` and `[...]` in the arguments of commands like
+//! `ls`, `open` and `rm`, and in the arguments of externals. A pattern can't
+//! be resolved like a path, so validation checks the directory it expands in
+//! instead: the pattern with its wildcard components left out (`/etc/host*`
+//! is checked as `/etc`, `src/*/../..` as `src/../..`).
+//!
+//! That directory can hold files the deny list protects, and symlinks out of
+//! the sandbox, so the pattern is also expanded against the filesystem and
+//! every match is checked like a path given literally (`open .e*` is checked
+//! as `open .env`).
+
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::{Component, Path, PathBuf};
+
+/// Most directory entries read while expanding one pattern. Patterns that
+/// need more, like `**` over a large tree, are rejected rather than guessed at.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Characters that make a path component a pattern
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// Whether a path contains wildcards
+pub fn is_glob(path: &str) -> bool {
+    path.contains(GLOB_CHARS)
+}
+
+/// The directory a pattern expands in, or `None` if `path` isn't a pattern.
+/// Wildcard components are dropped rather than counted as a level, so `..`
+/// after them is resolved as far up as the pattern could reach.
+pub fn glob_base(path: &str) -> Option<String> {
+    if !is_glob(path) {
+        return None;
+    }
+    let base = path
+        .split('/')
+        .filter(|component| !is_glob(component))
+        .collect::<Vec<_>>()
+        .join("/");
+    Some(match base.as_str() {
+        "" if path.starts_with('/') => "/".to_string(),
+        "" => ".".to_string(),
+        _ => base,
+    })
+}
+
+/// Expand `pattern`, an absolute path, into the existing paths it matches,
+/// plus the symlinks a wildcard component passed through on the way
+/// (`sys*/passwd` reports the `system` link). Symlinks aren't followed.
+pub fn expand(pattern: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut frontier = vec![PathBuf::new()];
+    let mut links = Vec::new();
+    let mut entries = 0;
+    let components: Vec<Component> = pattern.components().collect();
+
+    for (index, component) in components.iter().enumerate() {
+        let text = component.as_os_str().to_string_lossy();
+        if !is_glob(&text) {
+            frontier.iter_mut().for_each(|path| path.push(component));
+            continue;
+        }
+
+        let last = index + 1 == components.len();
+        let mut next = Vec::new();
+        if text == "**" {
+            // Zero or more directories
+            for dir in &frontier {
+                next.push(dir.clone());
+                descend(dir, &mut next, &mut links, &mut entries)?;
+            }
+        } else {
+            let matcher = component_matcher(&text)?;
+            for dir in &frontier {
+                for entry in read_dir(dir, &mut entries)? {
+                    if !matcher.is_match(entry.file_name()) {
+                        continue;
+                    }
+                    let path = entry.path();
+                    if !last && entry.file_type().is_ok_and(|kind| kind.is_symlink()) {
+                        links.push(path.clone());
+                    }
+                    next.push(path);
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    frontier.retain(|path| path.symlink_metadata().is_ok());
+    frontier.extend(links);
+    Ok(frontier)
+}
+
+/// Add every directory below `dir` to `found`, and symlinks to `links`
+fn descend(
+    dir: &Path,
+    found: &mut Vec<PathBuf>,
+    links: &mut Vec<PathBuf>,
+    entries: &mut usize,
+) -> Result<(), String> {
+    for entry in read_dir(dir, entries)? {
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_symlink() {
+            links.push(entry.path());
+        } else if kind.is_dir() {
+            found.push(entry.path());
+            descend(&entry.path(), found, links, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// The entries of a directory, or none if it can't be read
+fn read_dir(dir: &Path, entries: &mut usize) -> Result<Vec<std::fs::DirEntry>, String> {
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let found: Vec<_> = read.flatten().collect();
+    *entries += found.len();
+    if *entries > MAX_ENTRIES {
+        return Err(format!(
+            "it matches more than {} files under {}, too many to check",
+            MAX_ENTRIES,
+            dir.display()
+        ));
+    }
+    Ok(found)
+}
+
+fn component_matcher(component: &str) -> Result<GlobMatcher, String> {
+    GlobBuilder::new(component)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| format!("'{}' is not a valid glob: {}", component, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("/etc/host*").as_deref(), Some("/etc"));
+        assert_eq!(glob_base("/etc/hostnam?").as_deref(), Some("/etc"));
+        assert_eq!(glob_base("/e*").as_deref(), Some("/"));
+        assert_eq!(glob_base("*.rs").as_deref(), Some("."));
+        assert_eq!(glob_base("src/**/*.rs").as_deref(), Some("src"));
+        assert_eq!(glob_base("src/*/../..").as_deref(), Some("src/../.."));
+        assert_eq!(glob_base("src/[ab].rs").as_deref(), Some("src"));
+        assert_eq!(glob_base("src/main.rs"), None);
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = std::env::temp_dir().join("nu_mcp_glob_expand");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/deep")).unwrap();
+        std::fs::write(dir.join(".env"), "").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::write(dir.join("src/deep/a.rs"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let mut matches = expand(&dir.join(".e*")).unwrap();
+        assert_eq!(matches, [dir.join(".env")]);
+        matches = expand(&dir.join("*.env")).unwrap();
+        assert_eq!(matches, [dir.join(".env")]);
+        matches = expand(&dir.join("**/*.rs")).unwrap();
+        assert_eq!(matches, [dir.join("src/deep/a.rs")]);
+        assert!(expand(&dir.join("*.md")).unwrap().is_empty());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc", dir.join("system")).unwrap();
+            let mut matches = expand(&dir.join("sys*/passwd")).unwrap();
+            matches.sort();
+            assert_eq!(matches, [dir.join("system"), dir.join("system/passwd")]);
+        }
+    }
+}
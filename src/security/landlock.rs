@@ -0,0 +1,493 @@
+//! Kernel-enforced filesystem sandbox for `nu` processes (Linux Landlock)
+//!
+//! Path validation is lexical: a path built at runtime (`glob`, string
+//! operations, `ls | each {}`) gets past it. Landlock closes that gap by
+//! limiting what the spawned processes can open, whatever the command does.
+//!
+//! The ruleset is built once at startup and applied to each child right
+//! before it executes `nu`. Kernels without Landlock run commands unconfined.
+
+use std::path::{Path, PathBuf};
+
+/// System locations every `nu` process and the externals it runs need to read.
+/// `/proc` is left out: it holds every process's environment and memory,
+/// including the server's. A rule for `/proc/self` would not help, since it
+/// is resolved once, in the server.
+const SYSTEM_READ_ONLY: &[&str] = &[
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc",
+    "/opt",
+    "/run",
+    "/sys",
+    "/nix/store",
+];
+
+/// Device nodes that must stay writable: the usual data sinks and sources,
+/// the controlling terminal and the PTYs. Disks and the rest of `/dev` are not.
+const SYSTEM_READ_WRITE: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+    "/dev/ptmx",
+    "/dev/pts",
+    "/dev/shm",
+];
+
+/// Directories `nu` processes may read or write, and those they may only read
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LandlockPaths {
+    pub read_write: Vec<PathBuf>,
+    pub read_only: Vec<PathBuf>,
+}
+
+impl LandlockPaths {
+    /// The sandbox, with `read_only` directories (which may also be listed in
+    /// `sandbox`) limited to reads, plus the system paths and `nu` install.
+    /// A read-only directory nested inside a writable one stays writable for
+    /// the kernel; path validation still rejects writes to it.
+    pub fn new(sandbox: &[PathBuf], read_only: &[PathBuf]) -> Self {
+        let mut paths = Self {
+            read_write: sandbox
+                .iter()
+                .filter(|dir| !read_only.contains(dir))
+                .cloned()
+                .collect(),
+            read_only: read_only.to_vec(),
+        };
+        paths
+            .read_write
+            .extend(SYSTEM_READ_WRITE.iter().map(PathBuf::from));
+        paths
+            .read_only
+            .extend(SYSTEM_READ_ONLY.iter().map(PathBuf::from));
+        paths.read_only.extend(nu_install_dir());
+        paths
+    }
+
+    /// Keep `protected` paths (the server's own files) read-only inside
+    /// writable directories. A rule covers a whole tree, so a writable
+    /// directory holding a protected path is replaced by its entries: the
+    /// directories on the way to the protected path become read-only, and
+    /// everything next to them stays writable. Entries created later directly
+    /// in those directories are therefore not writable.
+    pub fn protect(&mut self, protected: &[PathBuf]) {
+        let protected: Vec<PathBuf> = protected.iter().filter_map(|path| resolve(path)).collect();
+        for dir in std::mem::take(&mut self.read_write) {
+            self.split_writable(&dir, &protected);
+        }
+    }
+
+    fn split_writable(&mut self, path: &Path, protected: &[PathBuf]) {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if !protected
+            .iter()
+            .any(|protected| protected.starts_with(&canonical) || canonical.starts_with(protected))
+        {
+            self.read_write.push(path.to_path_buf());
+            return;
+        }
+
+        self.read_only.push(path.to_path_buf());
+        if protected
+            .iter()
+            .any(|protected| canonical.starts_with(protected))
+        {
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(&canonical) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            // A rule for a symlink would apply to its target, wherever that is
+            if entry.file_type().is_ok_and(|kind| !kind.is_symlink()) {
+                self.split_writable(&entry.path(), protected);
+            }
+        }
+    }
+}
+
+/// A path, canonicalized if it exists, or through its parent directory if not
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(canonical) = path.canonicalize() {
+        return Some(canonical);
+    }
+    let parent = path.parent()?.canonicalize().ok()?;
+    Some(parent.join(path.file_name()?))
+}
+
+/// Directory holding the `nu` binary found on `PATH`
+fn nu_install_dir() -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join("nu"))
+        .find(|candidate| candidate.is_file())
+        .and_then(|nu| nu.canonicalize().ok())
+        .and_then(|nu| nu.parent().map(Path::to_path_buf))
+}
+
+#[cfg(target_os = "linux")]
+pub use self::linux::Landlock;
+
+#[cfg(not(target_os = "linux"))]
+pub use self::unsupported::Landlock;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::LandlockPaths;
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+    const RULE_PATH_BENEATH: libc::c_int = 1;
+
+    const ACCESS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_READ_DIR: u64 = 1 << 3;
+    /// Rights up to MAKE_SYM, all in ABI 1
+    const ACCESS_ABI_1: u64 = (1 << 13) - 1;
+    const ACCESS_REFER: u64 = 1 << 13;
+    const ACCESS_TRUNCATE: u64 = 1 << 14;
+    const ACCESS_IOCTL_DEV: u64 = 1 << 15;
+
+    /// Rights that apply to files; a rule for a file may only grant these
+    const ACCESS_FILE: u64 =
+        ACCESS_EXECUTE | ACCESS_WRITE_FILE | ACCESS_READ_FILE | ACCESS_TRUNCATE | ACCESS_IOCTL_DEV;
+    const ACCESS_READ: u64 = ACCESS_EXECUTE | ACCESS_READ_FILE | ACCESS_READ_DIR;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    /// A Landlock ruleset, shared by every child the server starts
+    #[derive(Debug, Clone)]
+    pub struct Landlock {
+        ruleset: Arc<OwnedFd>,
+        abi: i32,
+    }
+
+    impl Landlock {
+        /// Build the ruleset. `Ok(None)` when the kernel doesn't support Landlock.
+        /// Paths that don't exist are skipped.
+        pub fn new(paths: &LandlockPaths) -> Result<Option<Self>, String> {
+            let Some(abi) = abi_version() else {
+                return Ok(None);
+            };
+            let handled = handled_access(abi);
+
+            let attr = RulesetAttr {
+                handled_access_fs: handled,
+            };
+            // SAFETY: attr is a valid landlock_ruleset_attr prefix of the given size
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_create_ruleset,
+                    &attr as *const RulesetAttr,
+                    std::mem::size_of::<RulesetAttr>(),
+                    0,
+                )
+            };
+            if fd < 0 {
+                return Err(format!(
+                    "Failed to create Landlock ruleset: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            // SAFETY: the syscall returned a new file descriptor that nothing else owns
+            let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+            for dir in &paths.read_write {
+                add_rule(&ruleset, dir, handled)?;
+            }
+            for dir in &paths.read_only {
+                add_rule(&ruleset, dir, handled & ACCESS_READ)?;
+            }
+
+            Ok(Some(Self {
+                ruleset: Arc::new(ruleset),
+                abi,
+            }))
+        }
+
+        /// Landlock ABI version of the running kernel
+        pub fn abi(&self) -> i32 {
+            self.abi
+        }
+
+        /// Confine the calling thread and every process it starts from now on.
+        /// Only makes system calls, so it is safe to use in a pre-exec hook.
+        pub fn restrict_self(&self) -> std::io::Result<()> {
+            // SAFETY: plain system calls with integer arguments
+            unsafe {
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::syscall(
+                    libc::SYS_landlock_restrict_self,
+                    self.ruleset.as_raw_fd(),
+                    0,
+                ) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// The kernel's Landlock ABI version, `None` if Landlock is unavailable
+    fn abi_version() -> Option<i32> {
+        // SAFETY: querying the version takes no attribute pointer
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0,
+                CREATE_RULESET_VERSION,
+            )
+        };
+        (abi > 0).then_some(abi as i32)
+    }
+
+    /// Every filesystem right the kernel knows about, so none is left unrestricted
+    fn handled_access(abi: i32) -> u64 {
+        let mut access = ACCESS_ABI_1;
+        if abi >= 2 {
+            access |= ACCESS_REFER;
+        }
+        if abi >= 3 {
+            access |= ACCESS_TRUNCATE;
+        }
+        if abi >= 5 {
+            access |= ACCESS_IOCTL_DEV;
+        }
+        access
+    }
+
+    fn add_rule(ruleset: &OwnedFd, path: &Path, access: u64) -> Result<(), String> {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return Ok(());
+        };
+        let access = if metadata.is_dir() {
+            access
+        } else {
+            access & ACCESS_FILE
+        };
+
+        let name = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| format!("Invalid path for Landlock: {}", path.display()))?;
+        // SAFETY: name is a valid NUL-terminated string
+        let fd = unsafe { libc::open(name.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(format!(
+                "Failed to open {} for Landlock: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            ));
+        }
+        // SAFETY: open returned a new file descriptor that nothing else owns
+        let parent = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let attr = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: parent.as_raw_fd(),
+        };
+        // SAFETY: attr is a valid landlock_path_beneath_attr for the ruleset fd
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &attr as *const PathBeneathAttr,
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(format!(
+                "Failed to add Landlock rule for {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod unsupported {
+    use super::LandlockPaths;
+
+    /// Landlock only exists on Linux
+    #[derive(Debug, Clone)]
+    pub struct Landlock;
+
+    impl Landlock {
+        pub fn new(_paths: &LandlockPaths) -> Result<Option<Self>, String> {
+            Ok(None)
+        }
+
+        pub fn abi(&self) -> i32 {
+            0
+        }
+
+        pub fn restrict_self(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    /// Run a shell script confined by `landlock`; true if it succeeded
+    fn sh(landlock: &Landlock, script: &str) -> bool {
+        let landlock = landlock.clone();
+        let mut command = Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        // SAFETY: restrict_self only makes system calls
+        unsafe {
+            command.pre_exec(move || landlock.restrict_self());
+        }
+        command.status().unwrap().success()
+    }
+
+    #[test]
+    fn test_confines_children_to_sandbox() {
+        let sandbox = dir("nu_mcp_landlock_sandbox");
+        let read_only = dir("nu_mcp_landlock_ro");
+        let outside = dir("nu_mcp_landlock_outside");
+        std::fs::write(read_only.join("data.txt"), "data").unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+
+        let paths = LandlockPaths::new(
+            &[sandbox.clone(), read_only.clone()],
+            std::slice::from_ref(&read_only),
+        );
+        let Some(landlock) = Landlock::new(&paths).unwrap() else {
+            eprintln!("Landlock is not supported here; skipping");
+            return;
+        };
+
+        assert!(sh(
+            &landlock,
+            &format!("echo x > {}/out.txt", sandbox.display())
+        ));
+        assert!(sh(
+            &landlock,
+            &format!("cat {}/data.txt", read_only.display())
+        ));
+        assert!(!sh(
+            &landlock,
+            &format!("echo x > {}/out.txt", read_only.display())
+        ));
+        assert!(!sh(
+            &landlock,
+            &format!("cat {}/secret.txt", outside.display())
+        ));
+        assert!(!sh(&landlock, &format!("ls {}", outside.display())));
+
+        // The server's environment and other devices are out of reach
+        assert!(!sh(&landlock, "cat /proc/$PPID/environ"));
+        assert!(!sh(&landlock, "ls /dev"));
+        assert!(sh(
+            &landlock,
+            "echo x > /dev/null && head -c 1 /dev/urandom"
+        ));
+    }
+
+    #[test]
+    fn test_protected_paths_stay_read_only_in_writable_directories() {
+        let sandbox = dir("nu_mcp_landlock_protected");
+        std::fs::create_dir_all(sandbox.join("tools/k8s")).unwrap();
+        std::fs::create_dir_all(sandbox.join("src")).unwrap();
+        std::fs::write(sandbox.join("tools/k8s/mod.nu"), "def main [] {}").unwrap();
+        std::fs::write(sandbox.join("notes.txt"), "notes").unwrap();
+
+        let mut paths = LandlockPaths::new(std::slice::from_ref(&sandbox), &[]);
+        paths.protect(&[sandbox.join("tools"), sandbox.join("audit.jsonl")]);
+        assert!(paths.read_only.contains(&sandbox));
+        assert!(paths.read_write.contains(&sandbox.join("src")));
+        assert!(
+            !paths
+                .read_write
+                .iter()
+                .any(|path| path.starts_with(sandbox.join("tools")))
+        );
+        let Some(landlock) = Landlock::new(&paths).unwrap() else {
+            eprintln!("Landlock is not supported here; skipping");
+            return;
+        };
+
+        let tools = sandbox.join("tools");
+        assert!(sh(
+            &landlock,
+            &format!("cat {}/k8s/mod.nu", tools.display())
+        ));
+        assert!(!sh(
+            &landlock,
+            &format!("echo x > {}/k8s/mod.nu", tools.display())
+        ));
+        assert!(!sh(
+            &landlock,
+            &format!("rm {}/k8s/mod.nu", tools.display())
+        ));
+        assert!(!sh(&landlock, &format!("mkdir {}/evil", tools.display())));
+        assert!(!sh(
+            &landlock,
+            &format!("echo x > {}/audit.jsonl", sandbox.display())
+        ));
+        assert!(sh(
+            &landlock,
+            &format!("echo x > {}/src/new.rs", sandbox.display())
+        ));
+        assert!(sh(
+            &landlock,
+            &format!("echo x >> {}/notes.txt", sandbox.display())
+        ));
+    }
+
+    #[test]
+    fn test_read_only_directories_are_not_writable() {
+        let paths = LandlockPaths::new(
+            &[PathBuf::from("/work"), PathBuf::from("/ref")],
+            &[PathBuf::from("/ref")],
+        );
+        assert!(paths.read_write.contains(&PathBuf::from("/work")));
+        assert!(!paths.read_write.contains(&PathBuf::from("/ref")));
+        assert!(paths.read_only.contains(&PathBuf::from("/ref")));
+        assert!(paths.read_write.contains(&PathBuf::from("/dev/null")));
+        assert!(!paths.read_write.contains(&PathBuf::from("/dev")));
+    }
+}
@@ -0,0 +1,1055 @@
+//! Nushell-aware tokenizer for sandbox path validation
+//!
+//! Splits a command into the commands it runs and each of those into words,
+//! following Nushell's syntax:
+//! - Statements (`;`, newlines) and pipeline stages (`|`)
+//! - Quoting: `"..."` (with escapes), `'...'`, `` `...` ``, raw strings `r#'...'#`
+//! - Interpolation: `$"..."` / `$'...'` with `(...)` subexpressions
+//! - Subexpressions `(...)`, blocks and closures `{...}`, lists `[...]`
+//! - Variables (`$env.HOME`) and comments (`# ...`)
+//!
+//! Nested subexpressions and blocks are kept on the word they belong to, so
+//! simple string expressions can be folded into the path they produce
+//! (e.g. `open ("/etc" | path join passwd)`).
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Nushell commands whose positional arguments are filesystem paths
+const FILESYSTEM_COMMANDS: &[&str] = &[
+    "open",
+    "save",
+    "cd",
+    "rm",
+    "cp",
+    "mv",
+    "ls",
+    "mkdir",
+    "touch",
+    "glob",
+    "du",
+    "watch",
+    "source",
+    "source-env",
+    "use",
+    "start",
+    "ucp",
+    "umv",
+    "umkdir",
+];
+
+/// Common external programs that take file arguments.
+/// Other externals are only recognized when called with `^` or by path.
+const FILESYSTEM_EXTERNALS: &[&str] = &[
+    "cat", "head", "tail", "less", "more", "grep", "find", "chmod", "chown", "ln", "stat", "file",
+    "tar", "zip", "unzip", "diff", "sed", "awk", "cut", "wc", "tee", "dd", "rsync", "scp",
+];
+
+/// Commands that create, modify or delete every positional argument
+const WRITE_ALL_COMMANDS: &[&str] = &[
+    "save", "rm", "mv", "umv", "mkdir", "umkdir", "touch", "rmdir", "chmod", "chown", "chgrp",
+    "shred", "unlink", "truncate", "tee",
+];
+
+/// Commands that write only their last positional argument (the destination)
+const WRITE_LAST_COMMANDS: &[&str] = &["cp", "ucp", "ln", "install", "rsync", "scp"];
+
+/// Commands that take their destination as `-t DIR`/`--target-directory=DIR`
+const TARGET_DIRECTORY_COMMANDS: &[&str] = &["cp", "mv", "ln", "install"];
+
+/// Commands that edit their file arguments in place when given `-i`/`--in-place`
+const IN_PLACE_EDITORS: &[&str] = &["sed", "perl"];
+
+/// Git subcommands that change the work tree or the repository
+const GIT_WRITE_SUBCOMMANDS: &[&str] = &[
+    "add",
+    "am",
+    "apply",
+    "branch",
+    "checkout",
+    "cherry-pick",
+    "clean",
+    "clone",
+    "commit",
+    "config",
+    "fetch",
+    "gc",
+    "init",
+    "merge",
+    "mv",
+    "notes",
+    "prune",
+    "pull",
+    "rebase",
+    "reset",
+    "restore",
+    "revert",
+    "rm",
+    "stash",
+    "submodule",
+    "switch",
+    "tag",
+    "worktree",
+];
+
+/// Redirection operators; the following word (or the rest of the word) is a file
+const REDIRECTIONS: &[&str] = &[
+    "out+err>>",
+    "err+out>>",
+    "out+err>",
+    "err+out>",
+    "o+e>>",
+    "e+o>>",
+    "o+e>",
+    "e+o>",
+    "out>>",
+    "err>>",
+    "out>",
+    "err>",
+    "o>>",
+    "e>>",
+    "o>",
+    "e>",
+];
+
+/// Resolves a variable reference (e.g. `$env.HOME`) to its value, if known
+pub type Variables<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// A sequence of pipelines (the whole command, or the body of a block)
+pub type Block = Vec<Pipeline>;
+
+/// Commands connected with `|`
+pub type Pipeline = Vec<Command>;
+
+/// One command: a pipeline element or statement
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Command {
+    /// Source text, with nested subexpressions and blocks replaced by `$()` and `{}`
+    pub text: String,
+    /// The command name followed by its arguments
+    pub words: Vec<Word>,
+}
+
+/// One whitespace-separated word, made of adjacent parts (e.g. `(pwd)/file`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Word {
+    pub parts: Vec<Part>,
+    /// Contains a quoted string, so in command position it is a value, not a command call
+    pub quoted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part {
+    /// Literal text with quotes removed and escapes decoded
+    Text(String),
+    /// Variable reference including its cell path, e.g. `$env.HOME`
+    Variable(String),
+    /// `(...)`
+    Subexpression(Block),
+    /// `{...}`: blocks, closures and records
+    Block(Block),
+    /// `[...]`
+    List(Vec<Word>),
+}
+
+impl Part {
+    /// Statically evaluate the part (see `Word::value`)
+    pub fn value(&self, variables: Variables) -> Option<String> {
+        match self {
+            Part::Text(text) => Some(text.clone()),
+            Part::Variable(name) => variables(name),
+            Part::Subexpression(block) => fold_block(block, variables),
+            Part::Block(_) | Part::List(_) => None,
+        }
+    }
+}
+
+/// Shows dynamic parts in short form, e.g. `$env.HOME/(...)`
+impl std::fmt::Display for Word {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => f.write_str(text)?,
+                Part::Variable(name) => f.write_str(name)?,
+                Part::Subexpression(_) => f.write_str("(...)")?,
+                Part::Block(_) => f.write_str("{...}")?,
+                Part::List(_) => f.write_str("[...]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tokenize a Nushell command
+pub fn parse(source: &str) -> Block {
+    Lexer {
+        chars: source.chars().peekable(),
+    }
+    .block(None)
+}
+
+/// All commands in a block, including those nested in subexpressions, blocks and lists
+pub fn commands(block: &Block) -> Vec<&Command> {
+    let mut found = Vec::new();
+    for command in block.iter().flatten() {
+        found.push(command);
+        for word in &command.words {
+            collect_word_commands(word, &mut found);
+        }
+    }
+    found
+}
+
+fn collect_word_commands<'a>(word: &'a Word, found: &mut Vec<&'a Command>) {
+    for part in &word.parts {
+        match part {
+            Part::Subexpression(block) | Part::Block(block) => found.extend(commands(block)),
+            Part::List(items) => items
+                .iter()
+                .for_each(|item| collect_word_commands(item, found)),
+            Part::Text(_) | Part::Variable(_) => {}
+        }
+    }
+}
+
+impl Command {
+    /// Command name without the `^` external prefix
+    pub fn name(&self) -> Option<String> {
+        let name = self.words.first()?.literal()?;
+        Some(name.strip_prefix('^').unwrap_or(&name).to_string())
+    }
+
+    /// Whether the command runs an external program that may take file arguments
+    fn is_filesystem_external(&self) -> bool {
+        let Some(first) = self.words.first().and_then(Word::literal) else {
+            return false;
+        };
+        first.starts_with('^')
+            || first.contains('/')
+            || FILESYSTEM_EXTERNALS.contains(&first.as_str())
+    }
+
+    /// Arguments that are used as filesystem paths, with redirection operators removed
+    pub fn filesystem_args(&self) -> Vec<Word> {
+        let positional = self.is_filesystem_external()
+            || self
+                .name()
+                .is_some_and(|name| FILESYSTEM_COMMANDS.contains(&name.as_str()));
+
+        let (args, mut targets) = self.split_redirections();
+        if positional {
+            targets.extend(args.into_iter().filter(|word| !word.is_flag()));
+        }
+        targets
+    }
+
+    /// Arguments the command creates, modifies or deletes, and redirection targets
+    pub fn write_targets(&self) -> Vec<Word> {
+        let (mut args, mut targets) = self.split_redirections();
+        let name = self.name().unwrap_or_default();
+        if TARGET_DIRECTORY_COMMANDS.contains(&name.as_str()) {
+            let directories;
+            (directories, args) = take_option(args, "-t", "--target-directory");
+            if !directories.is_empty() && name != "mv" {
+                // The remaining positional arguments are only sources
+                targets.extend(directories);
+                return targets;
+            }
+            targets.extend(directories);
+        }
+
+        let flags: Vec<String> = args
+            .iter()
+            .filter(|word| word.is_flag())
+            .filter_map(Word::literal)
+            .collect();
+        let mut positional: Vec<Word> = args
+            .iter()
+            .filter(|word| !word.is_flag())
+            .cloned()
+            .collect();
+
+        if WRITE_ALL_COMMANDS.contains(&name.as_str()) {
+            targets.append(&mut positional);
+        } else if WRITE_LAST_COMMANDS.contains(&name.as_str()) {
+            targets.extend(positional.pop());
+        } else if IN_PLACE_EDITORS.contains(&name.as_str())
+            && flags
+                .iter()
+                .any(|flag| flag.starts_with("-i") || flag.starts_with("--in-place"))
+        {
+            targets.append(&mut positional);
+        } else if name == "dd" {
+            targets.extend(
+                positional
+                    .iter()
+                    .filter(|word| word.literal_prefix().starts_with("of="))
+                    .map(|word| word.strip_text_prefix(3)),
+            );
+        } else if name == "tar" {
+            targets.extend(tar_targets(args));
+        } else if name == "patch" {
+            targets.extend(patch_targets(args));
+        } else if name == "git" {
+            targets.extend(git_targets(args));
+        }
+        targets
+    }
+
+    /// Separate arguments from redirection targets
+    fn split_redirections(&self) -> (Vec<Word>, Vec<Word>) {
+        let mut args = Vec::new();
+        let mut targets = Vec::new();
+        let mut redirect_target = false;
+        for word in self.words.iter().skip(1) {
+            if redirect_target {
+                targets.push(word.clone());
+                redirect_target = false;
+                continue;
+            }
+            match word.strip_redirection() {
+                Some(Some(target)) => targets.push(target),
+                Some(None) => redirect_target = true,
+                None => args.push(word.clone()),
+            }
+        }
+        (args, targets)
+    }
+}
+
+/// Split out the values of an option given as `-o value`, `-ovalue`,
+/// `--option value` or `--option=value`; returns them and the other arguments
+fn take_option(args: Vec<Word>, short: &str, long: &str) -> (Vec<Word>, Vec<Word>) {
+    let long_equals = format!("{long}=");
+    let mut values = Vec::new();
+    let mut rest = Vec::new();
+    let mut words = args.into_iter();
+    while let Some(word) = words.next() {
+        let prefix = word.literal_prefix();
+        if word
+            .literal()
+            .is_some_and(|text| text == short || text == long)
+        {
+            values.extend(words.next());
+        } else if prefix.starts_with(&long_equals) {
+            values.push(word.strip_text_prefix(long_equals.len()));
+        } else if prefix.starts_with(short) && !prefix.starts_with("--") {
+            values.push(word.strip_text_prefix(short.len()));
+        } else {
+            rest.push(word);
+        }
+    }
+    (values, rest)
+}
+
+/// What `tar` writes: the archive when creating or updating one, and the
+/// directory it extracts into (`-C`, or the working directory)
+fn tar_targets(args: Vec<Word>) -> Vec<Word> {
+    let (mut directories, args) = take_option(args, "-C", "--directory");
+    let (mut archives, args) = take_option(args, "-f", "--file");
+    let (mut extract, mut create) = (false, false);
+    let mut words = args.iter();
+    let mut first = true;
+    while let Some(word) = words.next() {
+        let text = word.literal().unwrap_or_default();
+        if let Some(long) = text.strip_prefix("--") {
+            extract |= matches!(long, "extract" | "get");
+            create |= matches!(
+                long,
+                "create" | "append" | "update" | "concatenate" | "delete"
+            );
+        } else if text.starts_with('-')
+            || (first && !text.is_empty() && text.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            // Bundled short options, with or without a dash: `xzf in.tar`, `-cf out.tar`
+            for letter in text.trim_start_matches('-').chars() {
+                extract |= letter == 'x';
+                create |= matches!(letter, 'c' | 'r' | 'u' | 'A');
+                match letter {
+                    'f' => archives.extend(words.next().cloned()),
+                    'C' => directories.extend(words.next().cloned()),
+                    _ => {}
+                }
+            }
+        }
+        first = false;
+    }
+
+    let mut targets = Vec::new();
+    if create {
+        targets.append(&mut archives);
+    }
+    if extract {
+        if directories.is_empty() {
+            directories.push(Word::text("."));
+        }
+        targets.append(&mut directories);
+    }
+    targets
+}
+
+/// What `patch` writes: the file it patches (its first positional argument, or
+/// the files named in the patch under `-d` or the working directory), and its
+/// output and reject files
+fn patch_targets(args: Vec<Word>) -> Vec<Word> {
+    let (mut targets, args) = take_option(args, "-o", "--output");
+    let (rejects, args) = take_option(args, "-r", "--reject-file");
+    let (mut directories, args) = take_option(args, "-d", "--directory");
+    let (_, args) = take_option(args, "-i", "--input");
+    let (_, args) = take_option(args, "-p", "--strip");
+    targets.extend(rejects);
+    match args.into_iter().find(|word| !word.is_flag()) {
+        Some(file) => targets.push(file),
+        None if directories.is_empty() => targets.push(Word::text(".")),
+        None => {}
+    }
+    targets.append(&mut directories);
+    targets
+}
+
+/// What `git` writes for subcommands that change the repository: the `-C`,
+/// `--git-dir` and `--work-tree` directories (or the working directory), and
+/// the destination of `git clone`
+fn git_targets(args: Vec<Word>) -> Vec<Word> {
+    let mut directories = Vec::new();
+    let mut words = args.into_iter();
+    let subcommand = loop {
+        let Some(word) = words.next() else {
+            return Vec::new();
+        };
+        let text = word.literal_prefix();
+        match text.as_str() {
+            "-C" | "--git-dir" | "--work-tree" => directories.extend(words.next()),
+            "-c" | "--namespace" | "--exec-path" | "--config-env" => {
+                words.next();
+            }
+            _ if text.starts_with("--git-dir=") || text.starts_with("--work-tree=") => {
+                let equals = text.find('=').unwrap_or_default();
+                directories.push(word.strip_text_prefix(equals + 1));
+            }
+            _ if word.is_flag() => {}
+            _ => break word.literal().unwrap_or_default(),
+        }
+    };
+    if !GIT_WRITE_SUBCOMMANDS.contains(&subcommand.as_str()) {
+        return Vec::new();
+    }
+
+    let mut targets = Vec::new();
+    let positional: Vec<Word> = words.filter(|word| !word.is_flag()).collect();
+    if subcommand == "clone" && positional.len() > 1 {
+        targets.extend(positional.last().cloned());
+    }
+    if directories.is_empty() {
+        directories.push(Word::text("."));
+    }
+    targets.append(&mut directories);
+    targets
+}
+
+impl Word {
+    fn text(text: &str) -> Word {
+        Word {
+            parts: vec![Part::Text(text.to_string())],
+            quoted: false,
+        }
+    }
+
+    /// The word without its first `len` bytes of literal text
+    fn strip_text_prefix(&self, len: usize) -> Word {
+        let mut parts = self.parts.clone();
+        if let Some(Part::Text(text)) = parts.first_mut() {
+            text.drain(..len.min(text.len()));
+            if text.is_empty() {
+                parts.remove(0);
+            }
+        }
+        Word {
+            parts,
+            quoted: self.quoted,
+        }
+    }
+
+    /// The word's text if it is fully literal
+    pub fn literal(&self) -> Option<String> {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Literal text before the first dynamic part
+    pub fn literal_prefix(&self) -> String {
+        self.parts
+            .iter()
+            .map_while(|part| match part {
+                Part::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Statically evaluate the word: literals, variables known to `variables`,
+    /// and subexpressions that only concatenate or join such values
+    pub fn value(&self, variables: Variables) -> Option<String> {
+        self.parts
+            .iter()
+            .map(|part| part.value(variables))
+            .collect()
+    }
+
+    /// Whether the word is a value in command position (`"a" + "b"`) rather than a command call
+    pub fn is_value(&self) -> bool {
+        self.quoted
+            || matches!(
+                self.parts.first(),
+                Some(Part::Subexpression(_) | Part::Variable(_) | Part::List(_))
+            )
+    }
+
+    pub fn is_flag(&self) -> bool {
+        self.literal().is_some_and(|text| text.starts_with('-'))
+    }
+
+    /// For a redirection operator, returns `Some(None)` when the target is the
+    /// next word and `Some(Some(target))` when it is glued on (`o>file`)
+    fn strip_redirection(&self) -> Option<Option<Word>> {
+        let Some(Part::Text(first)) = self.parts.first() else {
+            return None;
+        };
+        let operator = REDIRECTIONS.iter().find(|op| first.starts_with(*op))?;
+        let rest = &first[operator.len()..];
+
+        let mut parts = self.parts[1..].to_vec();
+        if !rest.is_empty() {
+            parts.insert(0, Part::Text(rest.to_string()));
+        }
+        Some((!parts.is_empty()).then_some(Word {
+            parts,
+            quoted: self.quoted,
+        }))
+    }
+
+    fn push_text(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(Part::Text(text)) => text.push(c),
+            _ => self.parts.push(Part::Text(c.to_string())),
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        s.chars().for_each(|c| self.push_text(c));
+    }
+}
+
+/// Fold a subexpression built only from string values, `+`/`++` and `path join`
+fn fold_block(block: &Block, variables: Variables) -> Option<String> {
+    let [pipeline] = block.as_slice() else {
+        return None;
+    };
+    let (first, rest) = pipeline.split_first()?;
+    fold_pipeline(&first.words, rest, variables)
+}
+
+/// Fold a pipeline whose first element is the expression `first`
+/// (e.g. the right-hand side of `let p = "/etc" | path join passwd`)
+pub fn fold_pipeline(first: &[Word], rest: &[Command], variables: Variables) -> Option<String> {
+    let mut values = fold_concatenation(first, variables)?;
+    for command in rest {
+        let words: Vec<String> = command
+            .words
+            .iter()
+            .map(|word| word.value(variables))
+            .collect::<Option<_>>()?;
+        match words
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            ["path", "join", segments @ ..] => {
+                let mut joined = std::path::PathBuf::from_iter(&values);
+                joined.extend(segments);
+                values = vec![joined.to_string_lossy().into_owned()];
+            }
+            ["path", "expand"] => {}
+            _ => return None,
+        }
+    }
+
+    match values.as_slice() {
+        [value] => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Evaluate `a + b ++ c` over value words. A lone list yields its items.
+fn fold_concatenation(words: &[Word], variables: Variables) -> Option<Vec<String>> {
+    let first = words.first()?;
+    if let [Part::List(items)] = first.parts.as_slice() {
+        return (words.len() == 1)
+            .then(|| items.iter().map(|item| item.value(variables)).collect())
+            .flatten();
+    }
+    // A bare word in command position is a command call, not a string
+    if !first.is_value() {
+        return None;
+    }
+
+    let mut value = String::new();
+    for (i, word) in words.iter().enumerate() {
+        let text = word.value(variables)?;
+        if i % 2 == 1 {
+            if text != "+" && text != "++" {
+                return None;
+            }
+        } else {
+            value.push_str(&text);
+        }
+    }
+    (words.len() % 2 == 1).then_some(vec![value])
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+/// Words and source text of the command being built
+#[derive(Default)]
+struct CommandBuilder {
+    command: Command,
+    word: Word,
+}
+
+impl CommandBuilder {
+    fn finish_word(&mut self) {
+        if !self.word.parts.is_empty() {
+            self.command.words.push(std::mem::take(&mut self.word));
+        }
+    }
+
+    fn finish(&mut self) -> Option<Command> {
+        self.finish_word();
+        let mut command = std::mem::take(&mut self.command);
+        command.text = command.text.trim().to_string();
+        (!command.words.is_empty()).then_some(command)
+    }
+}
+
+impl Lexer<'_> {
+    /// Read pipelines until the closing delimiter (or end of input)
+    fn block(&mut self, close: Option<char>) -> Block {
+        let mut block = Block::new();
+        let mut pipeline = Pipeline::new();
+        let mut builder = CommandBuilder::default();
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                _ if Some(c) == close => break,
+                ';' | '\n' | '\r' => {
+                    pipeline.extend(builder.finish());
+                    if !pipeline.is_empty() {
+                        block.push(std::mem::take(&mut pipeline));
+                    }
+                }
+                '|' => pipeline.extend(builder.finish()),
+                '#' if builder.word.parts.is_empty() => self.skip_comment(),
+                c if c.is_whitespace() => {
+                    builder.finish_word();
+                    builder.command.text.push(' ');
+                }
+                c => self.word_char(c, &mut builder.word, &mut builder.command.text),
+            }
+        }
+
+        pipeline.extend(builder.finish());
+        if !pipeline.is_empty() {
+            block.push(pipeline);
+        }
+        block
+    }
+
+    /// Read list items until `]`
+    fn list(&mut self) -> Vec<Word> {
+        let mut items = Vec::new();
+        let mut word = Word::default();
+        let mut text = String::new();
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                ']' => break,
+                ',' => items.push(std::mem::take(&mut word)),
+                '#' if word.parts.is_empty() => self.skip_comment(),
+                c if c.is_whitespace() => items.push(std::mem::take(&mut word)),
+                c => self.word_char(c, &mut word, &mut text),
+            }
+        }
+
+        items.push(word);
+        items.retain(|item| !item.parts.is_empty());
+        items
+    }
+
+    /// Handle a character that belongs to a word
+    fn word_char(&mut self, c: char, word: &mut Word, text: &mut String) {
+        match c {
+            '(' => {
+                word.parts.push(Part::Subexpression(self.block(Some(')'))));
+                text.push_str("$()");
+            }
+            '{' => {
+                word.parts.push(Part::Block(self.block(Some('}'))));
+                text.push_str("{}");
+            }
+            '[' => {
+                word.parts.push(Part::List(self.list()));
+                text.push_str("[]");
+            }
+            '"' | '\'' | '`' => {
+                text.push(c);
+                word.quoted = true;
+                self.quoted(c, false, word, text);
+            }
+            '$' => match self.chars.peek() {
+                Some(&quote @ ('"' | '\'')) => {
+                    self.chars.next();
+                    text.push('$');
+                    text.push(quote);
+                    word.quoted = true;
+                    self.quoted(quote, true, word, text);
+                }
+                _ => {
+                    let name = self.variable();
+                    text.push_str(&name);
+                    word.parts.push(Part::Variable(name));
+                }
+            },
+            'r' if word.parts.is_empty() && self.chars.peek() == Some(&'#') => {
+                self.raw_string(word, text)
+            }
+            c => {
+                text.push(c);
+                word.push_text(c);
+            }
+        }
+    }
+
+    /// Read a quoted string after its opening quote
+    fn quoted(&mut self, quote: char, interpolated: bool, word: &mut Word, text: &mut String) {
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' if quote == '"' => {
+                    text.push(c);
+                    let Some(escaped) = self.chars.next() else {
+                        break;
+                    };
+                    text.push(escaped);
+                    match escaped {
+                        'n' => word.push_text('\n'),
+                        't' => word.push_text('\t'),
+                        'r' => word.push_text('\r'),
+                        '"' | '\\' | '/' | '\'' | '(' | ')' => word.push_text(escaped),
+                        other => {
+                            word.push_text('\\');
+                            word.push_text(other);
+                        }
+                    }
+                }
+                '(' if interpolated => {
+                    word.parts.push(Part::Subexpression(self.block(Some(')'))));
+                    text.push_str("()");
+                }
+                c if c == quote => {
+                    text.push(c);
+                    return;
+                }
+                c => {
+                    text.push(c);
+                    word.push_text(c);
+                }
+            }
+        }
+    }
+
+    /// Read a raw string `r#'...'#` after the `r`
+    fn raw_string(&mut self, word: &mut Word, text: &mut String) {
+        let mut hashes = 0;
+        while self.chars.next_if_eq(&'#').is_some() {
+            hashes += 1;
+        }
+        text.push('r');
+        text.push_str(&"#".repeat(hashes));
+
+        if self.chars.next_if_eq(&'\'').is_none() {
+            // Not a raw string after all, just a word starting with r#
+            word.push_text('r');
+            word.push_str(&"#".repeat(hashes));
+            return;
+        }
+        text.push('\'');
+        word.quoted = true;
+
+        let terminator = format!("'{}", "#".repeat(hashes));
+        let mut content = String::new();
+        for c in self.chars.by_ref() {
+            content.push(c);
+            text.push(c);
+            if content.ends_with(&terminator) {
+                content.truncate(content.len() - terminator.len());
+                break;
+            }
+        }
+        word.push_str(&content);
+    }
+
+    /// Read a variable name and cell path after the `$`
+    fn variable(&mut self) -> String {
+        let mut name = String::from("$");
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '?'))
+        {
+            name.push(c);
+        }
+        name
+    }
+
+    fn skip_comment(&mut self) {
+        while self.chars.next_if(|c| *c != '\n').is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(source: &str) -> Vec<String> {
+        commands(&parse(source))
+            .iter()
+            .map(|command| command.text.clone())
+            .collect()
+    }
+
+    fn no_variables(_: &str) -> Option<String> {
+        None
+    }
+
+    fn words(source: &str) -> Vec<Option<String>> {
+        parse(source)[0][0]
+            .words
+            .iter()
+            .map(|word| word.value(&no_variables))
+            .collect()
+    }
+
+    fn text(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    #[test]
+    fn test_statements_and_pipelines() {
+        assert_eq!(
+            texts("gh api /user; ls | save out.txt\nprint done"),
+            vec!["gh api /user", "ls", "save out.txt", "print done"]
+        );
+    }
+
+    #[test]
+    fn test_nested_commands() {
+        assert_eq!(
+            texts("ls (pwd) | each {|f| open $f.name }"),
+            vec!["ls $()", "pwd", "each {}", "f", "open $f.name"]
+        );
+    }
+
+    #[test]
+    fn test_separators_inside_quotes() {
+        assert_eq!(
+            texts(r#"echo "a; b | (c)" 'd; e'"#),
+            vec![r#"echo "a; b | (c)" 'd; e'"#]
+        );
+    }
+
+    #[test]
+    fn test_interpolation_subexpression() {
+        assert_eq!(
+            texts(r#"gh api $"/repos/(open /etc/passwd)""#),
+            vec![r#"gh api $"/repos/()""#, "open /etc/passwd"]
+        );
+    }
+
+    #[test]
+    fn test_quoted_word_with_spaces() {
+        assert_eq!(
+            words(r#"open "/etc/my file" '/tmp/other file'"#),
+            vec![text("open"), text("/etc/my file"), text("/tmp/other file")]
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_raw_strings() {
+        assert_eq!(
+            words(r#"print "a\"b" r#'/etc/x'#"#),
+            vec![text("print"), text("a\"b"), text("/etc/x")]
+        );
+    }
+
+    #[test]
+    fn test_comment_is_skipped() {
+        assert_eq!(texts("ls # open /etc/passwd\npwd"), vec!["ls", "pwd"]);
+        assert_eq!(words("ls a#b"), vec![text("ls"), text("a#b")]);
+    }
+
+    #[test]
+    fn test_fold_path_join() {
+        assert_eq!(
+            words(r#"open ("/etc" | path join passwd)"#),
+            vec![text("open"), text("/etc/passwd")]
+        );
+        assert_eq!(
+            words("open ([/etc passwd] | path join)"),
+            vec![text("open"), text("/etc/passwd")]
+        );
+    }
+
+    #[test]
+    fn test_fold_concatenation() {
+        assert_eq!(
+            words(r#"open ("/et" + "c/passwd")"#),
+            vec![text("open"), text("/etc/passwd")]
+        );
+        assert_eq!(
+            words(r#"open $"/etc/("pass" ++ "wd")""#),
+            vec![text("open"), text("/etc/passwd")]
+        );
+    }
+
+    #[test]
+    fn test_fold_with_variables() {
+        let variables = |name: &str| (name == "$env.HOME").then(|| "/home/me".to_string());
+        let block = parse("open ($env.HOME | path join .ssh) $env.HOME/.config $other/x");
+        let values: Vec<_> = block[0][0]
+            .words
+            .iter()
+            .map(|word| word.value(&variables))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                text("open"),
+                text("/home/me/.ssh"),
+                text("/home/me/.config"),
+                None
+            ]
+        );
+        assert_eq!(block[0][0].words[3].to_string(), "$other/x");
+    }
+
+    #[test]
+    fn test_dynamic_words_do_not_fold() {
+        let block = parse("open $env.HOME/.ssh (pwd)/file");
+        let words = &block[0][0].words;
+        assert_eq!(words[1].value(&no_variables), None);
+        assert_eq!(words[1].parts[0], Part::Variable("$env.HOME".to_string()));
+        assert_eq!(words[2].value(&no_variables), None);
+        assert_eq!(words[2].literal_prefix(), "");
+    }
+
+    #[test]
+    fn test_filesystem_args_of_builtins() {
+        let block = parse("cp -r src /tmp/dest");
+        let args: Vec<_> = block[0][0]
+            .filesystem_args()
+            .iter()
+            .map(|word| word.value(&no_variables))
+            .collect();
+        assert_eq!(args, vec![text("src"), text("/tmp/dest")]);
+    }
+
+    #[test]
+    fn test_filesystem_args_of_externals() {
+        let block = parse("^mytool --in a.txt; cat notes.md; gh issue list");
+        let args: Vec<Vec<_>> = block
+            .iter()
+            .map(|pipeline| {
+                pipeline[0]
+                    .filesystem_args()
+                    .iter()
+                    .map(|word| word.value(&no_variables))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            args,
+            vec![vec![text("a.txt")], vec![text("notes.md")], vec![]]
+        );
+    }
+
+    fn write_targets(source: &str) -> Vec<Option<String>> {
+        parse(source)[0][0]
+            .write_targets()
+            .iter()
+            .map(|word| word.value(&no_variables))
+            .collect()
+    }
+
+    #[test]
+    fn test_write_targets() {
+        assert_eq!(write_targets("save -f out.txt"), vec![text("out.txt")]);
+        assert_eq!(write_targets("rm -r a b"), vec![text("a"), text("b")]);
+        assert_eq!(write_targets("cp -r src dest"), vec![text("dest")]);
+        assert_eq!(
+            write_targets("sed -i 's/a/b/' notes.md"),
+            vec![text("s/a/b/"), text("notes.md")]
+        );
+        assert_eq!(write_targets("dd if=in of=out"), vec![text("out")]);
+        assert_eq!(write_targets("print x o> log.txt"), vec![text("log.txt")]);
+    }
+
+    #[test]
+    fn test_destination_options() {
+        assert_eq!(write_targets("cp -t /ro a b"), vec![text("/ro")]);
+        assert_eq!(
+            write_targets("cp --target-directory=/ro a"),
+            vec![text("/ro")]
+        );
+        assert_eq!(write_targets("mv -t /ro a"), vec![text("/ro"), text("a")]);
+        assert_eq!(write_targets("tar xf a.tar -C /ro"), vec![text("/ro")]);
+        assert_eq!(write_targets("tar -xzf a.tgz"), vec![text(".")]);
+        assert_eq!(
+            write_targets("tar -czf /ro/a.tgz src"),
+            vec![text("/ro/a.tgz")]
+        );
+        assert_eq!(write_targets("patch /ro/f p"), vec![text("/ro/f")]);
+        assert_eq!(write_targets("patch -p1 -d /ro"), vec![text("/ro")]);
+        assert_eq!(write_targets("git -C /ro commit -m x"), vec![text("/ro")]);
+        assert_eq!(write_targets("git commit -m x"), vec![text(".")]);
+        assert_eq!(
+            write_targets("git clone https://x/y.git /ro/y"),
+            vec![text("/ro/y"), text(".")]
+        );
+    }
+
+    #[test]
+    fn test_reads_are_not_write_targets() {
+        assert!(write_targets("open notes.md").is_empty());
+        assert!(write_targets("sed 's/a/b/' notes.md").is_empty());
+        assert!(write_targets("cat a b").is_empty());
+        assert!(write_targets("tar tf a.tar -C /ro").is_empty());
+        assert!(write_targets("git -C /ro log").is_empty());
+    }
+
+    #[test]
+    fn test_redirection_targets() {
+        let block = parse("print x o> /etc/a e>/etc/b");
+        let args: Vec<_> = block[0][0]
+            .filesystem_args()
+            .iter()
+            .map(|word| word.value(&no_variables))
+            .collect();
+        assert_eq!(args, vec![text("/etc/a"), text("/etc/b")]);
+    }
+}
@@ -4,7 +4,10 @@
 //!
 //! ## Validation Strategy
 //!
-//! The module uses a two-tier validation approach:
+//! Commands are first tokenized (see `lexer`) into the commands they run:
+//! pipeline stages, statements, and the bodies of blocks, closures and
+//! subexpressions. Each of those then goes through a two-tier validation
+//! approach on its own:
 //!
 //! 1. **Whitelist Check**: Commands matching safe patterns bypass path validation
 //!    - API commands (gh api, kubectl get /apis, argocd app, etc.)
@@ -12,29 +15,61 @@
 //!    - Other tools with non-filesystem path arguments
 //!
 //! 2. **Path Validation**: Remaining commands undergo filesystem path checks
-//!    - Extract non-quoted tokens (respects Nushell quoting)
-//!    - Identify potential filesystem paths
+//!    - Words follow Nushell quoting, so `"/etc/my file"` is one path
+//!    - Fold simple string expressions (`("/etc" | path join passwd)`)
+//!    - Expand `~`, `~user`, n-dots and path variables (`$env.HOME`, `$nu.config-path`)
+//!    - Check globs in filesystem position by the directory they expand in (see `glob`)
+//!    - Identify potential filesystem paths, and arguments in filesystem position
+//!    - Reject filesystem arguments that can't be resolved statically
 //!    - Verify paths don't escape sandbox directory
+//!    - Reject paths inside the sandbox that match the deny list (see `deny`)
 //!
 //! ## Adding Whitelist Patterns
 //!
-//! To add new safe patterns, edit `get_safe_command_patterns()` and add a regex.
+//! To add new safe patterns, edit `safe_command_patterns.txt`, or load more at
+//! runtime with `--safe-patterns-file` (see `patterns`).
 //! See `docs/security.md` for detailed instructions.
 
-use regex::Regex;
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+mod deny;
+mod egress;
+mod expand;
+mod explain;
+mod glob;
+mod landlock;
+mod lexer;
+mod patterns;
+mod policy;
+mod redact;
+mod wrappers;
+
+pub use deny::{DEFAULT_DENY_PATTERNS, DenyList};
+pub use egress::EgressAllowlist;
+use expand::{Scope, expand_ndots, expand_tilde};
+use explain::Trace;
+pub use explain::{Classification, Step};
+pub use landlock::{Landlock, LandlockPaths};
+use lexer::{Block, Part, Word};
+pub use patterns::{SafePattern, SafePatterns};
+pub use policy::{Action, Policy, Rule, Verdict};
+pub use redact::{CUSTOM_LABEL, DEFAULT_REDACT_PATTERNS, RedactionCounts, Redactor};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
 use tracing::{debug, trace};
 
-/// Load safe command patterns from file at compile time
-const SAFE_PATTERNS_FILE: &str = include_str!("safe_command_patterns.txt");
+/// How long a cached entry stays valid before the path is validated again
+const PATH_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of cached entries; the least recently used is evicted first
+const PATH_CACHE_CAPACITY: usize = 1024;
+
+/// Most working directories a command's `cd`s are followed into
+const MAX_WORKING_DIRS: usize = 64;
 
 /// Path cache for remembering strings that look like paths but aren't filesystem paths
 ///
 /// This cache stores path-like strings (starting with `/`) that don't exist on the
-/// filesystem and are outside the sandbox. These are typically API endpoints or
-/// other non-filesystem arguments.
+/// filesystem, are outside the sandbox, and have the shape of an API endpoint.
 ///
 /// # Examples
 /// - `/metrics` - Kubernetes API endpoint
@@ -42,30 +77,106 @@ const SAFE_PATTERNS_FILE: &str = include_str!("safe_command_patterns.txt");
 /// - `/healthz` - Health check endpoint
 ///
 /// # Lifecycle
-/// - Session-scoped (in-memory)
-/// - Cleared on server restart
-/// - No TTL needed (sandbox dirs are static)
+/// - Session-scoped (in-memory), cleared on server restart
+/// - Entries expire after a TTL (10 minutes by default)
+/// - Existence is re-checked on every hit, so a path that appears later is validated again
+/// - Bounded, evicting the least recently used entry (1024 entries by default)
+#[derive(Clone)]
 pub struct PathCache {
     /// Strings that look like paths but aren't filesystem paths
-    not_filesystem_paths: HashSet<String>,
+    not_filesystem_paths: HashMap<String, CacheEntry>,
+    ttl: Duration,
+    capacity: usize,
+    /// Monotonic counter for least-recently-used ordering
+    clock: u64,
+}
+
+#[derive(Clone)]
+struct CacheEntry {
+    inserted: Instant,
+    last_used: u64,
 }
 
 impl PathCache {
-    /// Create a new empty cache
+    /// Create a new empty cache with the default TTL and capacity
     pub fn new() -> Self {
+        Self::with_limits(PATH_CACHE_TTL, PATH_CACHE_CAPACITY)
+    }
+
+    /// Create a new empty cache with a custom TTL and capacity
+    pub fn with_limits(ttl: Duration, capacity: usize) -> Self {
         Self {
-            not_filesystem_paths: HashSet::new(),
+            not_filesystem_paths: HashMap::new(),
+            ttl,
+            capacity,
+            clock: 0,
         }
     }
 
-    /// Check if a path-like string is cached as "not a filesystem path"
+    /// Check if a path-like string is cached (and not expired) as "not a filesystem path"
     pub fn contains(&self, path: &str) -> bool {
-        self.not_filesystem_paths.contains(path)
+        self.not_filesystem_paths
+            .get(path)
+            .is_some_and(|entry| entry.inserted.elapsed() < self.ttl)
+    }
+
+    /// Look up a path during validation. Expired entries and paths that exist
+    /// on the filesystem by now are dropped, so they are validated again.
+    pub fn hit(&mut self, path: &str) -> bool {
+        if !self.contains(path) || Path::new(path).exists() {
+            self.not_filesystem_paths.remove(path);
+            return false;
+        }
+
+        self.clock += 1;
+        if let Some(entry) = self.not_filesystem_paths.get_mut(path) {
+            entry.last_used = self.clock;
+        }
+        true
     }
 
-    /// Remember that this string is not a filesystem path
-    pub fn remember(&mut self, path: String) {
-        self.not_filesystem_paths.insert(path);
+    /// Remember that this string is not a filesystem path.
+    /// Only strings shaped like API endpoints are cached; returns whether it was.
+    pub fn remember(&mut self, path: String) -> bool {
+        if !looks_like_endpoint(&path) {
+            return false;
+        }
+        self.insert(path);
+        true
+    }
+
+    fn insert(&mut self, path: String) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if !self.not_filesystem_paths.contains_key(&path)
+            && self.not_filesystem_paths.len() >= self.capacity
+        {
+            // Drop expired entries first, then the least recently used one
+            let ttl = self.ttl;
+            self.not_filesystem_paths
+                .retain(|_, entry| entry.inserted.elapsed() < ttl);
+
+            if self.not_filesystem_paths.len() >= self.capacity
+                && let Some(oldest) = self
+                    .not_filesystem_paths
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(path, _)| path.clone())
+            {
+                self.not_filesystem_paths.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.not_filesystem_paths.insert(
+            path,
+            CacheEntry {
+                inserted: Instant::now(),
+                last_used: self.clock,
+            },
+        );
     }
 
     /// Check if cache is empty
@@ -85,35 +196,259 @@ impl Default for PathCache {
     }
 }
 
-/// Parse pattern file and compile regexes
-/// Lines starting with # are comments, empty lines are ignored
-fn parse_pattern_file(content: &str) -> Vec<Regex> {
-    content
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty() && !line.starts_with('#'))
-        .map(|pattern| {
-            Regex::new(pattern)
-                .unwrap_or_else(|e| panic!("Invalid regex pattern '{}': {}", pattern, e))
-        })
-        .collect()
+/// Whether a path-like string has the shape of an API endpoint rather than a file:
+/// absolute, URL-safe characters only, no `.`/`..` components, and its first
+/// component is not an existing top-level directory (`/etc/...`, `/home/...`).
+fn looks_like_endpoint(path: &str) -> bool {
+    let Some(rest) = path.strip_prefix('/') else {
+        return false;
+    };
+    let url_safe = |c: char| c.is_ascii_alphanumeric() || "-._~:@!$&'*+,;=%".contains(c);
+    if rest.is_empty() || !rest.chars().all(|c| c == '/' || url_safe(c)) {
+        return false;
+    }
+
+    let mut components = rest.split('/');
+    if components.clone().any(|part| part == "." || part == "..") {
+        return false;
+    }
+    components
+        .next()
+        .is_some_and(|first| !Path::new("/").join(first).exists())
 }
 
-/// Safe command patterns that bypass path validation
-/// These patterns match commands that use path-like arguments but are NOT filesystem paths
-/// Examples: API endpoints, resource identifiers, URL paths, etc.
+/// Collect the words to validate from every command that isn't whitelisted,
+/// paired with whether the word is used as a filesystem argument.
+///
+/// Safe patterns are matched per command (statement, pipeline stage, or the
+/// body of a block, closure or subexpression) so they can't whitelist chained
+/// commands (e.g. `gh api /user; open /etc/shadow`).
 ///
-/// Patterns are loaded from safe_command_patterns.txt at compile time
-fn get_safe_command_patterns() -> &'static Vec<Regex> {
-    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
-    PATTERNS.get_or_init(|| parse_pattern_file(SAFE_PATTERNS_FILE))
+/// Variables and `~` references are expanded (see `expand`). A filesystem
+/// argument that can't be resolved statically is rejected, since it could
+/// point anywhere once the command runs. Write targets count as filesystem
+/// arguments, whichever command they belong to.
+///
+/// Relative paths that may be files are also added as resolved against every
+/// other working directory in `dirs` (see `working_dirs`).
+///
+/// Scripts passed to a shell (`sh -c "..."`, also through wrappers such as
+/// `timeout` or `run-external`) are checked the same way, as commands of
+/// their own.
+fn extract_path_candidates(
+    command: &str,
+    cwd: &Path,
+    patterns: &SafePatterns,
+    steps: &mut Trace,
+) -> Result<Vec<(String, bool)>, String> {
+    let mut candidates = Vec::new();
+    collect_path_candidates(
+        command,
+        &[cwd.to_path_buf()],
+        patterns,
+        steps,
+        &mut candidates,
+    )?;
+    Ok(candidates)
+}
+
+/// `extract_path_candidates` for `source`, which runs in any of `start_dirs`
+fn collect_path_candidates(
+    source: &str,
+    start_dirs: &[PathBuf],
+    patterns: &SafePatterns,
+    steps: &mut Trace,
+    candidates: &mut Vec<(String, bool)>,
+) -> Result<(), String> {
+    let block = lexer::parse(source);
+    let scope = Scope::new(&block, &start_dirs[0]);
+    let dirs = working_dirs(&block, &scope, start_dirs)?;
+    let first = candidates.len();
+
+    for command in lexer::commands(&block) {
+        if let Some(pattern) = patterns.find(&command.text) {
+            debug!("Command matches safe pattern, skipping: {:?}", command.text);
+            steps.note(&command.text, Classification::SafePattern, || {
+                format!(
+                    "matches {} ({}), not checked for paths",
+                    pattern.as_str(),
+                    pattern.location()
+                )
+            });
+            continue;
+        }
+        steps.note(&command.text, Classification::Segment, || {
+            "checked word by word".to_string()
+        });
+        let (_, script) = wrappers::unwrap(&command.words);
+        if let Some(script) = script {
+            let Some(source) = script.value(&|name| scope.resolve(name)) else {
+                let reason = unresolved_reason(script, &scope);
+                steps.note(&script.to_string(), Classification::Unresolved, || {
+                    reason.clone()
+                });
+                return Err(format!(
+                    "Cannot check the script '{}' of '{}' against the sandbox: {}. Write the script literally.",
+                    script,
+                    command.name().unwrap_or_default(),
+                    reason
+                ));
+            };
+            steps.note(&source, Classification::Script, || {
+                "run by a shell, checked as a command of its own".to_string()
+            });
+            collect_path_candidates(&source, &dirs, patterns, steps, candidates)?;
+        }
+        for word in &command.words {
+            if !script.is_some_and(|script| std::ptr::eq(word, script)) {
+                push_word_candidates(word, &scope, candidates);
+            }
+        }
+        let mut filesystem_args = command.filesystem_args();
+        filesystem_args.extend(command.write_targets());
+        for word in &filesystem_args {
+            let Some(value) = word.value(&|name| scope.resolve(name)) else {
+                let reason = unresolved_reason(word, &scope);
+                steps.note(&word.to_string(), Classification::Unresolved, || {
+                    reason.clone()
+                });
+                return Err(format!(
+                    "Cannot check argument '{}' of '{}' against the sandbox: {}. Use a literal path instead.",
+                    word,
+                    command.name().unwrap_or_default(),
+                    reason
+                ));
+            };
+            let value = expand_path(value);
+            let Some(base) = glob::glob_base(&value) else {
+                candidates.push((value, true));
+                continue;
+            };
+            // Check the directory the pattern expands in, and every match
+            let mut matches = Vec::new();
+            for dir in &dirs {
+                matches.extend(glob::expand(&dir.join(&value)).map_err(|reason| {
+                    format!(
+                        "Cannot check argument '{}' of '{}' against the sandbox: {}. Use a narrower pattern instead.",
+                        value,
+                        command.name().unwrap_or_default(),
+                        reason
+                    )
+                })?);
+            }
+            steps.note(&value, Classification::Glob, || {
+                format!(
+                    "checked by the directory it expands in, {}, and its {} match(es)",
+                    base,
+                    matches.len()
+                )
+            });
+            candidates.push((base, true));
+            candidates.extend(
+                matches
+                    .into_iter()
+                    .map(|path| (path.to_string_lossy().into_owned(), true)),
+            );
+        }
+    }
+
+    // Relative paths as seen from the directories a `cd` moves to
+    let relative: Vec<(String, bool)> = candidates[first..]
+        .iter()
+        .filter(|(word, filesystem_arg)| {
+            !Path::new(word).is_absolute()
+                && !word.starts_with('-')
+                && !is_url(word)
+                && (*filesystem_arg || word.contains('/') || word.contains('\\'))
+        })
+        .cloned()
+        .collect();
+    for dir in &dirs[1..] {
+        candidates.extend(relative.iter().map(|(word, filesystem_arg)| {
+            (
+                dir.join(word).to_string_lossy().into_owned(),
+                *filesystem_arg,
+            )
+        }));
+    }
+
+    Ok(())
+}
+
+/// Directories relative paths in a command may resolve against: `start`
+/// (the working directory, or those a script may run in) first, then every
+/// directory a `cd` in the command changes to. A `cd` may be conditional or
+/// inside a block, so each one adds a directory rather than replacing the
+/// previous ones.
+fn working_dirs(block: &Block, scope: &Scope, start: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut dirs = start.to_vec();
+    for command in lexer::commands(block) {
+        if command.name().as_deref() != Some("cd") {
+            continue;
+        }
+        let value = match command.words.iter().skip(1).find(|word| !word.is_flag()) {
+            None => "~".to_string(),
+            Some(word) => match word.value(&|name| scope.resolve(name)) {
+                Some(value) => value,
+                // Rejected as an unresolved filesystem argument
+                None => continue,
+            },
+        };
+        let value = expand_path(value);
+        for dir in dirs.clone() {
+            let next = resolve_missing_path(&dir.join(&value));
+            if !dirs.contains(&next) {
+                dirs.push(next);
+            }
+        }
+        if dirs.len() > MAX_WORKING_DIRS {
+            return Err(format!(
+                "Cannot check the command against the sandbox: its `cd`s lead to more than {} directories. Split it into separate commands.",
+                MAX_WORKING_DIRS
+            ));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Expand `~` and n-dots in a filesystem argument
+fn expand_path(value: String) -> String {
+    expand_ndots(&expand_tilde(&value).unwrap_or(value))
+}
+
+/// Add a word's static value (or the literal text before its first dynamic part)
+/// and the items of any list it contains
+fn push_word_candidates(word: &Word, scope: &Scope, candidates: &mut Vec<(String, bool)>) {
+    let value = word
+        .value(&|name| scope.resolve(name))
+        .unwrap_or_else(|| word.literal_prefix());
+    if !value.is_empty() {
+        candidates.push((expand_tilde(&value).unwrap_or(value), false));
+    }
+
+    for part in &word.parts {
+        if let Part::List(items) = part {
+            for item in items {
+                push_word_candidates(item, scope, candidates);
+            }
+        }
+    }
 }
 
-/// Check if a command matches a safe pattern and should bypass path validation
-fn matches_safe_pattern(command: &str) -> bool {
-    get_safe_command_patterns()
+/// Explain which part of a word can't be resolved before the command runs
+fn unresolved_reason(word: &Word, scope: &Scope) -> String {
+    let variables = |name: &str| scope.resolve(name);
+    word.parts
         .iter()
-        .any(|pattern| pattern.is_match(command))
+        .find(|part| part.value(&variables).is_none())
+        .map(|part| match part {
+            Part::Variable(name) => format!("{} is only known when the command runs", name),
+            Part::Subexpression(_) => {
+                "the subexpression is only evaluated when the command runs".to_string()
+            }
+            _ => "blocks and lists are not paths".to_string(),
+        })
+        .unwrap_or_else(|| "it can't be resolved statically".to_string())
 }
 
 /// Manually resolve a relative path with .. components
@@ -141,6 +476,16 @@ fn resolve_relative_path(base: &Path, relative: &str) -> Option<PathBuf> {
     Some(result)
 }
 
+/// Base for resolving `..` manually: the root for absolute paths
+/// (e.g. an expanded `$env.HOME/../..`), otherwise the first sandbox
+fn traversal_base<'a>(word: &str, first_sandbox: &'a Path) -> &'a Path {
+    if Path::new(word).is_absolute() {
+        Path::new("/")
+    } else {
+        first_sandbox
+    }
+}
+
 /// Check if a path is within any of the sandbox directories
 fn is_path_in_any_sandbox(path: &Path, sandboxes: &[PathBuf]) -> bool {
     sandboxes.iter().any(|sandbox| path.starts_with(sandbox))
@@ -155,32 +500,6 @@ fn format_sandbox_list(sandboxes: &[PathBuf]) -> String {
         .join(", ")
 }
 
-/// Extract all words from a command by splitting on whitespace
-/// NOTE: This does NOT skip quoted strings because quotes don't prevent filesystem access!
-/// Commands like `cat "/etc/passwd"` will still read the file even though it's quoted.
-fn extract_words(command: &str) -> Vec<String> {
-    command
-        .split_whitespace()
-        .map(|s| {
-            // Strip surrounding quotes to get the actual argument value
-            let s = s.trim();
-            let s = if s.len() >= 2
-                && ((s.starts_with('"') && s.ends_with('"'))
-                    || (s.starts_with('\'') && s.ends_with('\''))
-                    || (s.starts_with('`') && s.ends_with('`')))
-            {
-                &s[1..s.len() - 1]
-            } else {
-                s
-            };
-
-            // Strip trailing shell metacharacters (;, &, |, etc.)
-            let s = s.trim_end_matches([';', '&', '|', '>', '<']);
-            s.to_string()
-        })
-        .collect()
-}
-
 /// Check if a word is a URL (has a protocol scheme)
 fn is_url(word: &str) -> bool {
     word.starts_with("http://")
@@ -227,12 +546,6 @@ pub fn validate_path_safety(
     command: &str,
     sandbox_dirs: &[std::path::PathBuf],
 ) -> Result<(), String> {
-    // Check if command matches a safe pattern (commands with path-like args that aren't filesystem paths)
-    // Examples: gh api /repos/..., kubectl get /apis/..., etc.
-    if matches_safe_pattern(command) {
-        return Ok(());
-    }
-
     // Get canonical sandbox directories (only those that exist)
     let canonical_sandboxes: Vec<std::path::PathBuf> = sandbox_dirs
         .iter()
@@ -247,13 +560,18 @@ pub fn validate_path_safety(
     // Get first sandbox for relative path resolution
     let first_sandbox = &canonical_sandboxes[0];
 
-    // Extract all words from command (including quoted strings)
-    let words = extract_words(command);
+    // Tokenize the command and collect the words of commands that aren't whitelisted
+    let candidates = extract_path_candidates(
+        command,
+        first_sandbox,
+        &SafePatterns::default(),
+        &mut Trace::off(),
+    )?;
 
     // Check if command contains absolute paths or home directory paths that would escape the sandbox
-    for word in words {
+    for (word, filesystem_arg) in candidates {
         // Skip common commands and flags - only check things that look like paths
-        if word.starts_with('-') || is_common_command(&word) {
+        if word.starts_with('-') || (!filesystem_arg && is_common_command(&word)) {
             continue;
         }
 
@@ -263,26 +581,17 @@ pub fn validate_path_safety(
         }
 
         // Determine the path to check based on word type
-        let path_to_check = if word == "~" || word.starts_with("~/") {
-            // Home directory paths
-            if let Some(home_dir) = std::env::var_os("HOME") {
-                if word == "~" {
-                    Path::new(&home_dir).to_path_buf()
-                } else {
-                    Path::new(&home_dir).join(&word[2..])
-                }
-            } else {
-                continue; // Skip if HOME is not set
-            }
-        } else if word.contains("..") {
+        // Home directory references (`~`, `~user`) were already expanded
+        let path_to_check = if word.contains("..") {
             // Path with traversal - resolve relative to first sandbox directory
             // This allows cd ../ when inside the sandbox, but blocks escaping
             first_sandbox.join(&word)
         } else if is_likely_filesystem_path(&word) {
             // Absolute path
             Path::new(&word).to_path_buf()
-        } else if word.contains('/') || word.contains('\\') {
-            // Relative path with slashes (e.g., "subdir/file.txt")
+        } else if word.contains('/') || word.contains('\\') || filesystem_arg {
+            // Relative path with slashes (e.g., "subdir/file.txt"), or any argument
+            // of a command that takes files (a plain name may be a symlink)
             first_sandbox.join(&word)
         } else {
             // Plain word without path separators - not a path, skip
@@ -294,7 +603,7 @@ pub fn validate_path_safety(
             Ok(canonical) => canonical,
             Err(_) if word.contains("..") => {
                 // For non-existent paths with .., manually resolve components
-                match resolve_relative_path(first_sandbox, &word) {
+                match resolve_relative_path(traversal_base(&word, first_sandbox), &word) {
                     Some(resolved) => resolved,
                     None => continue, // Can't resolve, skip
                 }
@@ -327,28 +636,70 @@ pub fn validate_path_safety(
 /// This is the same as `validate_path_safety` but with an additional cache parameter
 /// that remembers non-filesystem path-like strings to avoid repeated validation.
 ///
+/// Paths inside the sandbox are also checked against `deny_list`, so sensitive
+/// files in the project tree (`.env`, `.aws/`, `id_rsa`) stay inaccessible.
+///
 /// # Cache Behavior
-/// - Checks cache first - if path is cached, skip all validation
-/// - After sandbox check, caches non-existent paths outside sandbox
+/// - Checks cache first - if path is cached (not expired, still missing), skip all validation
+/// - After sandbox check, caches non-existent endpoint-shaped paths outside sandbox
 /// - Never caches paths inside sandbox (handled by sandbox check)
 /// - Never caches existing files outside sandbox (blocked)
 pub fn validate_path_safety_with_cache(
     command: &str,
     sandbox_dirs: &[std::path::PathBuf],
+    deny_list: &DenyList,
+    patterns: &SafePatterns,
     cache: &mut PathCache,
+    cwd: Option<&Path>,
 ) -> Result<(), String> {
     debug!(
         "validate_path_safety_with_cache called: command={:?}",
         command
     );
+    check_paths(
+        command,
+        sandbox_dirs,
+        deny_list,
+        patterns,
+        cache,
+        cwd,
+        &mut Trace::off(),
+    )
+}
 
-    // Check if command matches a safe pattern (commands with path-like args that aren't filesystem paths)
-    // Examples: gh api /repos/..., kubectl get /apis/..., etc.
-    if matches_safe_pattern(command) {
-        debug!("Command matches safe pattern, allowing");
-        return Ok(());
-    }
+/// Run `validate_path_safety_with_cache` without side effects, recording how
+/// each command segment and word was classified. The cache is consulted but
+/// not updated.
+pub fn explain_path_safety(
+    command: &str,
+    sandbox_dirs: &[PathBuf],
+    deny_list: &DenyList,
+    patterns: &SafePatterns,
+    cache: &PathCache,
+    cwd: Option<&Path>,
+) -> (Vec<Step>, Result<(), String>) {
+    let mut trace = Trace::on();
+    let result = check_paths(
+        command,
+        sandbox_dirs,
+        deny_list,
+        patterns,
+        &mut cache.clone(),
+        cwd,
+        &mut trace,
+    );
+    (trace.into_steps(), result)
+}
 
+fn check_paths(
+    command: &str,
+    sandbox_dirs: &[PathBuf],
+    deny_list: &DenyList,
+    patterns: &SafePatterns,
+    cache: &mut PathCache,
+    cwd: Option<&Path>,
+    steps: &mut Trace,
+) -> Result<(), String> {
     // Get canonical sandbox directories (only those that exist)
     let canonical_sandboxes: Vec<std::path::PathBuf> = sandbox_dirs
         .iter()
@@ -360,51 +711,53 @@ pub fn validate_path_safety_with_cache(
         return Ok(());
     }
 
-    // Get first sandbox for relative path resolution
-    let first_sandbox = &canonical_sandboxes[0];
+    // Relative paths resolve against the shell's working directory, or the first sandbox
+    let first_sandbox = &cwd
+        .and_then(|dir| dir.canonicalize().ok())
+        .unwrap_or_else(|| canonical_sandboxes[0].clone());
 
-    // Extract all words from command (including quoted strings)
-    let words = extract_words(command);
+    // Tokenize the command and collect the words of commands that aren't whitelisted
+    let candidates = extract_path_candidates(command, first_sandbox, patterns, steps)?;
 
     // Check if command contains absolute paths or home directory paths that would escape the sandbox
     debug!("Starting word-by-word validation loop");
-    for word in words {
+    for (word, filesystem_arg) in candidates {
         trace!("Checking word: {:?}", word);
 
         // 1. CHECK CACHE FIRST - short circuit if we've seen this before
-        if cache.contains(&word) {
+        // (expired entries and paths that exist by now are validated again).
+        // Filesystem arguments are always files, so the cache doesn't apply.
+        if !filesystem_arg && cache.hit(&word) {
             trace!("Cache hit for: {:?}, skipping validation", word);
+            steps.note(&word, Classification::CacheHit, || {
+                "seen before as a missing path outside the sandbox".to_string()
+            });
             continue; // We know this isn't a filesystem path
         }
 
         // Skip common commands and flags - only check things that look like paths
-        if word.starts_with('-') || is_common_command(&word) {
-            trace!("Word is flag or common command, skipping: {:?}", word);
+        if word.starts_with('-') {
+            trace!("Word is flag, skipping: {:?}", word);
+            steps.note(&word, Classification::Flag, String::new);
+            continue;
+        }
+        if !filesystem_arg && is_common_command(&word) {
+            trace!("Word is common command, skipping: {:?}", word);
+            steps.note(&word, Classification::CommonCommand, String::new);
             continue;
         }
 
         // Skip URLs - they're not filesystem paths
         if is_url(&word) {
             trace!("Word is URL, skipping: {:?}", word);
+            steps.note(&word, Classification::Url, String::new);
             continue;
         }
 
         // Determine the path to check based on word type
         trace!("Determining path type for word: {:?}", word);
-        let path_to_check = if word == "~" || word.starts_with("~/") {
-            // Home directory paths
-            trace!("Word is home directory path");
-            if let Some(home_dir) = std::env::var_os("HOME") {
-                if word == "~" {
-                    Path::new(&home_dir).to_path_buf()
-                } else {
-                    Path::new(&home_dir).join(&word[2..])
-                }
-            } else {
-                trace!("HOME not set, skipping");
-                continue; // Skip if HOME is not set
-            }
-        } else if word.contains("..") {
+        // Home directory references (`~`, `~user`) were already expanded
+        let path_to_check = if word.contains("..") {
             // Path with traversal - resolve relative to first sandbox directory
             // This allows cd ../ when inside the sandbox, but blocks escaping
             trace!("Word contains path traversal (..)");
@@ -413,17 +766,26 @@ pub fn validate_path_safety_with_cache(
             // Absolute path
             trace!("Word is likely filesystem path");
             Path::new(&word).to_path_buf()
-        } else if word.contains('/') || word.contains('\\') {
-            // Relative path with slashes (e.g., "subdir/file.txt")
-            trace!("Word is relative path with slashes");
+        } else if word.contains('/') || word.contains('\\') || filesystem_arg {
+            // Relative path with slashes (e.g., "subdir/file.txt"), or any argument
+            // of a command that takes files (a plain name may be a symlink)
+            trace!("Word is relative path");
             first_sandbox.join(&word)
         } else {
             // Plain word without path separators - not a path, skip
             trace!("Word is plain word, not a path, skipping");
+            steps.note(&word, Classification::PlainWord, String::new);
             continue;
         };
         trace!("Path to check: {:?}", path_to_check);
 
+        // Check the path as written against the deny list too: a symlink named
+        // `.env` is denied even if it points to an ordinary file
+        if let Some(lexical) = resolve_relative_path(traversal_base(&word, first_sandbox), &word) {
+            check_deny_list(&word, &lexical, &canonical_sandboxes, deny_list)
+                .inspect_err(|_| note_denied(steps, &word, &lexical))?;
+        }
+
         // Try to canonicalize the path if it exists, otherwise use manual resolution
         trace!("Attempting to canonicalize: {:?}", path_to_check);
         let canonical_path = match path_to_check.canonicalize() {
@@ -431,10 +793,16 @@ pub fn validate_path_safety_with_cache(
                 trace!("Successfully canonicalized to: {:?}", canonical);
                 canonical
             }
+            Err(_) if filesystem_arg => {
+                // A file the command may create: resolve through its deepest existing
+                // directory, so a symlink in the sandbox can't point the write elsewhere
+                trace!("Non-existent filesystem argument, resolving its parent");
+                resolve_missing_path(&path_to_check)
+            }
             Err(_) if word.contains("..") => {
                 trace!("Canonicalization failed, manually resolving path with ..");
                 // For non-existent paths with .., manually resolve components
-                match resolve_relative_path(first_sandbox, &word) {
+                match resolve_relative_path(traversal_base(&word, first_sandbox), &word) {
                     Some(resolved) => {
                         trace!("Manually resolved to: {:?}", resolved);
                         resolved
@@ -453,6 +821,12 @@ pub fn validate_path_safety_with_cache(
             Err(_) => {
                 trace!("Non-existent relative path, skipping validation");
                 // Non-existent relative path - skip validation (Nushell will handle)
+                steps.note(&word, Classification::Path, || {
+                    format!(
+                        "{} doesn't exist; relative paths that don't exist aren't checked",
+                        path_to_check.display()
+                    )
+                });
                 continue;
             }
         };
@@ -460,26 +834,62 @@ pub fn validate_path_safety_with_cache(
         // 2. Check if the canonical/resolved path is within any sandbox
         trace!("Checking if path is in sandbox: {:?}", canonical_path);
         if is_path_in_any_sandbox(&canonical_path, &canonical_sandboxes) {
+            check_deny_list(&word, &canonical_path, &canonical_sandboxes, deny_list)
+                .inspect_err(|_| note_denied(steps, &word, &canonical_path))?;
             trace!("Path is in sandbox, allowing");
+            steps.note(&word, Classification::Path, || {
+                format!(
+                    "resolves to {}, inside the sandbox",
+                    canonical_path.display()
+                )
+            });
             continue; // In sandbox - allow (don't cache, handled by sandbox check)
         }
         trace!("Path is outside sandbox");
 
-        // 3. Path is outside sandbox - does it exist?
+        // 3. Path is outside sandbox - does it exist? Filesystem arguments are
+        // blocked either way, since the command may create them.
         trace!("Checking if path exists: {:?}", canonical_path);
-        if !canonical_path.exists() {
+        if !filesystem_arg && !canonical_path.exists() {
             trace!(
                 "Path does not exist, caching as non-filesystem path: {:?}",
                 word
             );
             // Non-existent path outside sandbox - cache it as "not a filesystem path"
-            cache.remember(word.clone());
-            trace!("Successfully cached, continuing");
+            // if it looks like an API endpoint
+            let cached = cache.remember(word.clone());
+            if cached {
+                trace!("Successfully cached, continuing");
+            }
+            steps.note(&word, Classification::Path, || {
+                format!(
+                    "resolves to {}, outside the sandbox but doesn't exist{}",
+                    canonical_path.display(),
+                    if cached {
+                        "; looks like an API endpoint"
+                    } else {
+                        ""
+                    }
+                )
+            });
             continue; // Allow
         }
 
-        // 4. Path exists AND outside sandbox - BLOCK
-        debug!("Path exists outside sandbox, blocking: {:?}", word);
+        // 4. Path exists (or is a filesystem argument) AND outside sandbox - BLOCK
+        debug!("Path outside sandbox, blocking: {:?}", word);
+        steps.note(&word, Classification::Path, || {
+            if canonical_path.exists() {
+                format!(
+                    "resolves to {}, which exists outside the sandbox",
+                    canonical_path.display()
+                )
+            } else {
+                format!(
+                    "resolves to {}, a file argument outside the sandbox",
+                    canonical_path.display()
+                )
+            }
+        });
         return Err(format!(
             "Path '{}' escapes sandbox directories. Allowed: {}",
             &word,
@@ -491,6 +901,273 @@ pub fn validate_path_safety_with_cache(
     Ok(())
 }
 
+fn note_denied(steps: &mut Trace, word: &str, path: &Path) {
+    steps.note(word, Classification::Path, || {
+        format!("resolves to {}, which is on the deny list", path.display())
+    });
+}
+
+/// Check a value that is known to be a path, such as an extension tool
+/// argument declared with `format: "path"`. Unlike the words of a command,
+/// it must resolve inside the sandbox whether or not it exists yet.
+pub fn validate_path_argument(
+    value: &str,
+    sandbox_dirs: &[PathBuf],
+    deny_list: &DenyList,
+) -> Result<(), String> {
+    let canonical_sandboxes: Vec<PathBuf> = sandbox_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    let Some(first_sandbox) = canonical_sandboxes.first() else {
+        return Ok(());
+    };
+
+    let expanded = expand_path(value.to_string());
+    if let Some(lexical) =
+        resolve_relative_path(traversal_base(&expanded, first_sandbox), &expanded)
+    {
+        check_deny_list(value, &lexical, &canonical_sandboxes, deny_list)?;
+    }
+
+    let path = resolve_write_target(first_sandbox, &expanded);
+    if !is_path_in_any_sandbox(&path, &canonical_sandboxes) {
+        debug!("Path argument outside sandbox, blocking: {:?}", path);
+        return Err(format!(
+            "Path '{}' escapes sandbox directories. Allowed: {}",
+            value,
+            format_sandbox_list(&canonical_sandboxes)
+        ));
+    }
+    check_deny_list(value, &path, &canonical_sandboxes, deny_list)
+}
+
+/// Reject a path inside the sandbox that matches a deny rule
+fn check_deny_list(
+    word: &str,
+    path: &Path,
+    sandboxes: &[PathBuf],
+    deny_list: &DenyList,
+) -> Result<(), String> {
+    match deny_list.matching_rule(path, sandboxes) {
+        Some(rule) => {
+            debug!("Path matches deny rule {:?}, blocking: {:?}", rule, path);
+            Err(format!(
+                "Path '{}' is denied by rule '{}'. Files matching the deny list can't be accessed, even inside the sandbox.",
+                word, rule
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Reject commands that create, modify or delete files in read-only directories
+///
+/// `sandbox_dirs` are all accessible directories (including the read-only ones);
+/// `read_only_dirs` is the subset that may only be read. When a path is inside
+/// both, the most specific directory wins, so a writable project inside a
+/// read-only tree stays writable.
+///
+/// Write targets are the destinations of `save`, `rm`, `mv`, `cp`, `mkdir`,
+/// `touch` and similar commands, redirections (`o>`, `e>`), and in-place edits
+/// (`sed -i`). Reads are always allowed.
+///
+/// Relative targets resolve against `cwd` (the persistent shell's working
+/// directory) and every directory a `cd` in the command moves to; without a
+/// `cwd`, against the first writable directory.
+pub fn validate_read_only(
+    command: &str,
+    sandbox_dirs: &[PathBuf],
+    read_only_dirs: &[PathBuf],
+    cwd: Option<&Path>,
+) -> Result<(), String> {
+    let read_only: Vec<PathBuf> = read_only_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    if read_only.is_empty() {
+        return Ok(());
+    }
+    let writable: Vec<PathBuf> = sandbox_dirs
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .filter(|dir| !read_only.contains(dir))
+        .collect();
+    let base = cwd
+        .and_then(|dir| dir.canonicalize().ok())
+        .or_else(|| writable.first().or(read_only.first()).cloned())
+        .unwrap_or_default();
+
+    check_write_targets(command, &base, |path, value, name| {
+        let deepest = |dirs: &[PathBuf]| {
+            dirs.iter()
+                .filter(|dir| path.starts_with(dir))
+                .map(|dir| dir.components().count())
+                .max()
+        };
+        if deepest(&read_only) > deepest(&writable) {
+            debug!("Write to read-only directory blocked: {:?}", path);
+            return Err(format!(
+                "Path '{}' is in a read-only directory and '{}' would modify it. Read-only: {}",
+                value,
+                name,
+                format_sandbox_list(&read_only)
+            ));
+        }
+        Ok(())
+    })
+}
+
+/// Reject commands that create, modify or delete the server's own files:
+/// the tools directory, config files and the audit log. Unlike read-only
+/// directories, these stay protected inside writable sandbox directories,
+/// and a file that doesn't exist yet (a rotated audit log) is protected too.
+/// Relative targets resolve like in `validate_read_only`.
+pub fn validate_protected_paths(
+    command: &str,
+    sandbox_dirs: &[PathBuf],
+    protected: &[PathBuf],
+    cwd: Option<&Path>,
+) -> Result<(), String> {
+    let protected: Vec<PathBuf> = protected
+        .iter()
+        .filter_map(|path| resolve_protected_path(path))
+        .collect();
+    if protected.is_empty() {
+        return Ok(());
+    }
+    let base = cwd
+        .and_then(|dir| dir.canonicalize().ok())
+        .or_else(|| sandbox_dirs.iter().find_map(|dir| dir.canonicalize().ok()))
+        .unwrap_or_default();
+
+    check_write_targets(command, &base, |path, value, name| {
+        match protected
+            .iter()
+            .find(|protected| path.starts_with(protected))
+        {
+            Some(protected) => {
+                debug!("Write to server file blocked: {:?}", path);
+                Err(format!(
+                    "Path '{}' is protected and '{}' would modify it: {} is part of the server's configuration (tools directory, config file or audit log).",
+                    value,
+                    name,
+                    protected.display()
+                ))
+            }
+            None => Ok(()),
+        }
+    })
+}
+
+/// Call `check` with the resolved path, the value as written and the command
+/// name of every write target in `command`, including those of scripts passed
+/// to a shell. Relative targets resolve against `cwd` and every directory a
+/// `cd` in the command changes to.
+fn check_write_targets(
+    command: &str,
+    cwd: &Path,
+    mut check: impl FnMut(&Path, &str, &str) -> Result<(), String>,
+) -> Result<(), String> {
+    check_write_targets_in(command, &[cwd.to_path_buf()], &mut check)
+}
+
+/// `check_write_targets` for `source`, which runs in any of `start_dirs`
+fn check_write_targets_in(
+    source: &str,
+    start_dirs: &[PathBuf],
+    check: &mut dyn FnMut(&Path, &str, &str) -> Result<(), String>,
+) -> Result<(), String> {
+    let block = lexer::parse(source);
+    let scope = Scope::new(&block, &start_dirs[0]);
+    let dirs = working_dirs(&block, &scope, start_dirs)?;
+
+    for command in lexer::commands(&block) {
+        if let (_, Some(script)) = wrappers::unwrap(&command.words) {
+            let Some(source) = script.value(&|name| scope.resolve(name)) else {
+                return Err(format!(
+                    "Cannot check the script '{}' of '{}' for write targets: {}. Write the script literally.",
+                    script,
+                    command.name().unwrap_or_default(),
+                    unresolved_reason(script, &scope)
+                ));
+            };
+            check_write_targets_in(&source, &dirs, check)?;
+        }
+        for target in command.write_targets() {
+            let name = command.name().unwrap_or_default();
+            let Some(value) = target.value(&|name| scope.resolve(name)) else {
+                return Err(format!(
+                    "Cannot check write target '{}' of '{}' against read-only directories: {}. Use a literal path instead.",
+                    target,
+                    name,
+                    unresolved_reason(&target, &scope)
+                ));
+            };
+            let value = expand_path(value);
+            for dir in &dirs {
+                let Some(target) = glob::glob_base(&value) else {
+                    check(&resolve_write_target(dir, &value), &value, &name)?;
+                    continue;
+                };
+                check(&resolve_write_target(dir, &target), &value, &name)?;
+                for path in glob::expand(&dir.join(&value)).map_err(|reason| {
+                    format!(
+                        "Cannot check write target '{}' of '{}': {}. Use a narrower pattern instead.",
+                        value, name, reason
+                    )
+                })? {
+                    let matched = path.to_string_lossy();
+                    check(&resolve_write_target(dir, &matched), &matched, &name)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A protected path, canonicalized if it exists, or through its parent directory if not
+fn resolve_protected_path(path: &Path) -> Option<PathBuf> {
+    if let Ok(canonical) = path.canonicalize() {
+        return Some(canonical);
+    }
+    let parent = path.parent()?.canonicalize().ok()?;
+    Some(parent.join(path.file_name()?))
+}
+
+/// Resolve a write target that may not exist yet (see `resolve_missing_path`)
+fn resolve_write_target(base: &Path, target: &str) -> PathBuf {
+    resolve_missing_path(&base.join(target))
+}
+
+/// Resolve a path that may not exist yet: canonicalize its deepest existing
+/// ancestor, following symlinks like the kernel would, then apply the
+/// remaining components lexically
+fn resolve_missing_path(path: &Path) -> PathBuf {
+    let components: Vec<_> = path.components().collect();
+    for existing in (1..=components.len()).rev() {
+        let Ok(mut resolved) = components[..existing]
+            .iter()
+            .collect::<PathBuf>()
+            .canonicalize()
+        else {
+            continue;
+        };
+        for component in &components[existing..] {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::CurDir => {}
+                other => resolved.push(other),
+            }
+        }
+        return resolved;
+    }
+    path.to_path_buf()
+}
+
 fn is_common_command(word: &str) -> bool {
     matches!(
         word,
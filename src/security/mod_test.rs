@@ -1,4 +1,5 @@
-use super::validate_path_safety;
+use super::{validate_path_safety, validate_read_only};
+use std::env::current_dir;
 use std::path::{Path, PathBuf};
 
 fn default_sandbox_dir() -> &'static Path {
@@ -58,11 +59,588 @@ fn test_semicolon_in_sandbox_path() {
     );
 }
 
+// --- Safe pattern bypass regressions ---
+// A safe pattern must only whitelist the segment it matches, never the whole command.
+
+/// Validate like the router does: with a path cache and the default safe patterns
+fn validate(command: &str, sandbox_dirs: &[PathBuf]) -> Result<(), String> {
+    crate::security::validate_path_safety_with_cache(
+        command,
+        sandbox_dirs,
+        &crate::security::DenyList::default(),
+        &crate::security::SafePatterns::default(),
+        &mut crate::security::PathCache::new(),
+        None,
+    )
+}
+
+fn assert_blocked(command: &str) {
+    let sandbox_dir = current_dir().unwrap();
+    let result = validate(command, &[sandbox_dir]);
+    assert!(
+        result.is_err(),
+        "Chained command should not bypass path validation: {}",
+        command
+    );
+}
+
+#[test]
+fn test_safe_pattern_does_not_whitelist_next_statement() {
+    assert_blocked("gh api /user; open /etc/passwd");
+    assert_blocked("gh api /user\nopen /etc/passwd");
+}
+
+#[test]
+fn test_safe_pattern_does_not_whitelist_pipeline_stage() {
+    assert_blocked("http get https://example.com | save /etc/foo");
+    assert_blocked("curl https://example.com | save -f /etc/passwd");
+}
+
+#[test]
+fn test_missing_files_outside_sandbox_are_blocked() {
+    // Commands may create the files they are given, so existence doesn't matter
+    assert_blocked("http get https://x | save /etc/foo");
+    assert_blocked("'x' | save /etc/newfile");
+    assert_blocked("cp a /etc/newfile");
+    assert_blocked("touch /tmp/evil");
+    assert_blocked("print x o> /no/such/dir/file");
+    assert_blocked("open /nu-mcp-missing/endpoint");
+}
+
+#[test]
+fn test_safe_pattern_does_not_whitelist_subexpression() {
+    assert_blocked("gh api /user (open /etc/passwd)");
+    assert_blocked(r#"gh api $"/repos/(open /etc/passwd)""#);
+}
+
+#[test]
+fn test_safe_pattern_does_not_whitelist_closure() {
+    assert_blocked("gh api /user | each {|x| open /etc/passwd }");
+    assert_blocked("http get https://example.com | do { cat /etc/passwd }");
+}
+
+#[test]
+fn test_safe_pattern_in_later_segment_still_applies() {
+    let sandbox_dir = current_dir().unwrap();
+    for command in [
+        "gh api /repos/owner/repo | get name",
+        "ls; kubectl get /apis/apps/v1",
+        "print start; gh api /user | from json",
+    ] {
+        let result = validate(command, std::slice::from_ref(&sandbox_dir));
+        assert!(
+            result.is_ok(),
+            "Safe segment should still be allowed: {} ({:?})",
+            command,
+            result
+        );
+    }
+}
+
+// --- Nushell-aware tokenizing ---
+
+#[test]
+fn test_quoted_text_is_one_word() {
+    let sandbox_dir = current_dir().unwrap();
+    for command in [
+        r#"echo "The file /etc/passwd is important""#,
+        r#"gh pr create --body "Fixed /etc/config""#,
+    ] {
+        let result = validate_path_safety(command, std::slice::from_ref(&sandbox_dir));
+        assert!(
+            result.is_ok(),
+            "Quoted text should be allowed: {} ({:?})",
+            command,
+            result
+        );
+    }
+}
+
+#[test]
+fn test_quoted_path_with_spaces_is_validated() {
+    let outside = std::env::temp_dir().join("nu mcp outside dir");
+    std::fs::create_dir_all(&outside).unwrap();
+
+    let command = format!(r#"ls "{}""#, outside.display());
+    let result = validate_path_safety(&command, &[current_dir().unwrap()]);
+    assert!(result.is_err(), "Quoted path with spaces should be blocked");
+
+    std::fs::remove_dir_all(&outside).ok();
+}
+
+#[test]
+fn test_string_expressions_are_folded() {
+    assert_blocked(r#"open ("/etc" | path join passwd)"#);
+    assert_blocked(r#"open ("/et" + "c/passwd")"#);
+    assert_blocked(r#"open $"/etc/("pass" ++ "wd")""#);
+    assert_blocked("open ([/etc passwd] | path join)");
+}
+
+#[test]
+fn test_paths_glued_to_operators() {
+    assert_blocked("open /etc/passwd|lines");
+    assert_blocked("print x o>/etc/passwd");
+    assert_blocked("ls [/etc/passwd]");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_argument_in_filesystem_position() {
+    let sandbox_dir = std::env::temp_dir().join("nu_mcp_symlink_sandbox");
+    std::fs::create_dir_all(&sandbox_dir).unwrap();
+    let link = sandbox_dir.join("system");
+    std::fs::remove_file(&link).ok();
+    std::os::unix::fs::symlink("/etc", &link).unwrap();
+
+    let sandbox = std::slice::from_ref(&sandbox_dir);
+    assert!(
+        validate("cd system", sandbox).is_err(),
+        "Symlink out of the sandbox should be blocked for cd"
+    );
+    assert!(
+        validate("cat system/passwd", sandbox).is_err(),
+        "Symlinked path should be blocked for externals"
+    );
+    assert!(
+        validate("print system", sandbox).is_ok(),
+        "Plain word outside a filesystem position should be allowed"
+    );
+    assert!(
+        validate("'x' | save system/new.conf", sandbox).is_err(),
+        "New file behind a symlink out of the sandbox should be blocked"
+    );
+    assert!(
+        validate("cat sys*/passwd", sandbox).is_err(),
+        "Glob through a symlink out of the sandbox should be blocked"
+    );
+    assert!(
+        validate("mkdir system/a/b", sandbox).is_err(),
+        "New directories behind a symlink out of the sandbox should be blocked"
+    );
+
+    std::fs::remove_dir_all(&sandbox_dir).ok();
+}
+
+// --- Globs and n-dots ---
+
+#[test]
+fn test_globs_are_checked_by_their_directory() {
+    assert_blocked("ls /etc/host*");
+    assert_blocked("open /etc/hostnam?");
+    assert_blocked("glob /etc/*");
+    assert_blocked("^cat /etc/[ph]*");
+    assert_blocked("ls src/*/../../..");
+
+    let sandbox_dir = current_dir().unwrap();
+    for command in [
+        "ls *.toml",
+        "ls src/*.rs",
+        "glob **/*.rs",
+        "rm -r target/debug/*.d",
+    ] {
+        let result = validate(command, std::slice::from_ref(&sandbox_dir));
+        assert!(
+            result.is_ok(),
+            "Glob in the sandbox should be allowed: {} ({:?})",
+            command,
+            result
+        );
+    }
+}
+
+#[test]
+fn test_ndots_are_expanded() {
+    assert_blocked("open ..../etc/hostname");
+    assert_blocked("ls ....");
+    assert_blocked("cd ...");
+    assert!(validate("ls src/tools/...", &[current_dir().unwrap()]).is_ok());
+}
+
+// --- Variable and home expansion ---
+
+#[test]
+fn test_home_variables_are_expanded() {
+    assert_blocked("cat $env.HOME/.ssh/id_rsa");
+    assert_blocked("open $nu.home-path");
+    assert_blocked("open $nu.config-path");
+    assert_blocked("cd $env.PWD/../..");
+    assert_blocked(r#"let dir = "/etc"; open ($dir | path join passwd)"#);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_other_user_home_is_expanded() {
+    assert_blocked("ls ~root/");
+}
+
+#[test]
+fn test_unresolvable_filesystem_argument_rejected() {
+    let sandbox_dir = current_dir().unwrap();
+    let err = validate_path_safety("open $file", std::slice::from_ref(&sandbox_dir))
+        .expect_err("Unknown variable in filesystem position should be rejected");
+    assert!(
+        err.contains("$file is only known when the command runs"),
+        "{}",
+        err
+    );
+
+    let err = validate_path_safety("ls | each {|f| rm $f.name }", &[sandbox_dir])
+        .expect_err("Closure parameter in filesystem position should be rejected");
+    assert!(err.contains("'rm'"), "{}", err);
+}
+
+#[test]
+fn test_shell_scripts_are_checked() {
+    let sandbox_dir = current_dir().unwrap();
+    let sandbox = std::slice::from_ref(&sandbox_dir);
+    for command in [
+        "nu -c \"open /etc/shadow\"",
+        "sh -c \"cat /etc/shadow\"",
+        "bash -c 'cat /etc/shadow'",
+        "^bash -lc \"cat /etc/shadow\"",
+        "timeout 5 sh -c \"cat /etc/shadow\"",
+        "env FOO=1 nu --commands 'open /etc/shadow'",
+        "run-external sh -c \"cat /etc/shadow\"",
+        "exec bash -c \"cat /etc/shadow\"",
+        "sh -c \"sh -c 'cat /etc/shadow'\"",
+        "let script = 'cat /etc/shadow'; sh -c $script",
+    ] {
+        let err = validate(command, sandbox).expect_err(command);
+        assert!(err.contains("/etc/shadow"), "{}: {}", command, err);
+    }
+
+    let err = validate("sh -c $env.SCRIPT", sandbox).expect_err("dynamic script");
+    assert!(err.contains("Write the script literally"), "{}", err);
+
+    for command in [
+        "sh -c \"cat Cargo.toml\"",
+        "nu -c 'open Cargo.toml | get package.name'",
+        "timeout 5 bash -c 'ls src'",
+    ] {
+        assert!(
+            validate(command, sandbox).is_ok(),
+            "should be allowed: {}",
+            command
+        );
+    }
+}
+
+#[test]
+fn test_resolvable_variables_allowed() {
+    let sandbox_dir = current_dir().unwrap();
+    for command in [
+        r#"let f = "Cargo.toml"; open $f"#,
+        "open $env.PWD/Cargo.toml",
+        "print $env.SOME_TOKEN",
+        "let total = (ls | length)",
+    ] {
+        let result = validate_path_safety(command, std::slice::from_ref(&sandbox_dir));
+        assert!(
+            result.is_ok(),
+            "Resolvable command should be allowed: {} ({:?})",
+            command,
+            result
+        );
+    }
+}
+
+// --- Read-only directories ---
+
+/// A read-only tree with one file, next to the writable current directory
+fn read_only_fixture(name: &str) -> (PathBuf, Vec<PathBuf>) {
+    let read_only = std::env::temp_dir().join(name);
+    std::fs::create_dir_all(&read_only).unwrap();
+    std::fs::write(read_only.join("file.txt"), "reference").unwrap();
+    let sandbox_dirs = vec![current_dir().unwrap(), read_only.clone()];
+    (read_only, sandbox_dirs)
+}
+
+#[test]
+fn test_read_only_allows_reads() {
+    let (read_only, sandbox_dirs) = read_only_fixture("nu_mcp_ro_reads");
+    let ro = read_only.display();
+    for command in [
+        format!("open {ro}/file.txt"),
+        format!("cp {ro}/file.txt copy.txt"),
+        format!("sed 's/a/b/' {ro}/file.txt"),
+        format!("ls {ro}"),
+    ] {
+        let result = validate_read_only(
+            &command,
+            &sandbox_dirs,
+            std::slice::from_ref(&read_only),
+            None,
+        );
+        assert!(
+            result.is_ok(),
+            "Read should be allowed: {} ({:?})",
+            command,
+            result
+        );
+    }
+}
+
+#[test]
+fn test_read_only_blocks_writes() {
+    let (read_only, sandbox_dirs) = read_only_fixture("nu_mcp_ro_writes");
+    let ro = read_only.display();
+    for command in [
+        format!("'x' | save {ro}/new.txt"),
+        format!("rm {ro}/file.txt"),
+        format!("mv {ro}/file.txt moved.txt"),
+        format!("cp notes.txt {ro}/"),
+        format!("mkdir {ro}/sub"),
+        format!("touch {ro}/file.txt"),
+        format!("print hi o> {ro}/log.txt"),
+        format!("^sed -i 's/a/b/' {ro}/file.txt"),
+    ] {
+        let result = validate_read_only(
+            &command,
+            &sandbox_dirs,
+            std::slice::from_ref(&read_only),
+            None,
+        );
+        let err = result.expect_err(&format!("Write should be blocked: {}", command));
+        assert!(err.contains("read-only"), "{}", err);
+    }
+}
+
+#[test]
+fn test_writable_directory_inside_read_only_tree() {
+    let (read_only, _) = read_only_fixture("nu_mcp_ro_nested");
+    let work = read_only.join("work");
+    std::fs::create_dir_all(&work).unwrap();
+    let sandbox_dirs = vec![work.clone(), read_only.clone()];
+
+    let save_work = format!("'x' | save {}/out.txt", work.display());
+    assert!(
+        validate_read_only(
+            &save_work,
+            &sandbox_dirs,
+            std::slice::from_ref(&read_only),
+            None
+        )
+        .is_ok()
+    );
+
+    let save_ro = format!("'x' | save {}/out.txt", read_only.display());
+    assert!(validate_read_only(&save_ro, &sandbox_dirs, &[read_only], None).is_err());
+}
+
+#[test]
+fn test_read_only_follows_cd_and_destination_options() {
+    let (read_only, sandbox_dirs) = read_only_fixture("nu_mcp_ro_cd");
+    let ro = read_only.display();
+    let check = |command: &str, cwd: Option<&Path>| {
+        validate_read_only(
+            command,
+            &sandbox_dirs,
+            std::slice::from_ref(&read_only),
+            cwd,
+        )
+    };
+
+    for command in [
+        format!("cd {ro}; rm file.txt"),
+        format!(
+            "cd {ro}/..; cd {}; 'x' | save new.txt",
+            read_only.file_name().unwrap().display()
+        ),
+        format!("cp -t {ro} notes.txt"),
+        format!("cp --target-directory={ro} notes.txt"),
+        format!("tar xf a.tar -C {ro}"),
+        format!("patch {ro}/file.txt fix.patch"),
+        format!("git -C {ro} commit -m x"),
+    ] {
+        let err = check(&command, None).expect_err(&command);
+        assert!(err.contains("read-only"), "{}", err);
+    }
+    assert!(check("cd src; rm notes.txt", None).is_ok());
+    assert!(check(&format!("git -C {ro} log"), None).is_ok());
+
+    // Relative targets resolve against the shell's working directory
+    assert!(check("rm file.txt", Some(&read_only)).is_err());
+    assert!(check("rm file.txt", None).is_ok());
+}
+
+#[test]
+fn test_relative_paths_resolve_in_every_working_directory() {
+    let root = std::env::temp_dir().join("nu_mcp_cwd");
+    let (a, b) = (root.join("a"), root.join("b/c"));
+    std::fs::create_dir_all(&a).unwrap();
+    std::fs::create_dir_all(root.join("b/a")).unwrap();
+    std::fs::create_dir_all(&b).unwrap();
+    std::fs::write(a.join("x"), "inside").unwrap();
+    std::fs::write(root.join("b/a/x"), "outside").unwrap();
+    let sandbox_dirs = [a.clone(), b.clone()];
+    let validate = |command: &str, cwd: Option<&Path>| {
+        crate::security::validate_path_safety_with_cache(
+            command,
+            &sandbox_dirs,
+            &crate::security::DenyList::default(),
+            &crate::security::SafePatterns::default(),
+            &mut crate::security::PathCache::new(),
+            cwd,
+        )
+    };
+
+    // `../a/x` is a/x from a, but b/a/x (outside the sandbox) from b/c
+    assert!(validate("open ../a/x", None).is_ok());
+    assert!(validate("open ../a/x", Some(&b)).is_err());
+    assert!(validate(&format!("cd {}; open ../a/x", b.display()), None).is_err());
+}
+
+// --- Protected server files ---
+
+#[test]
+fn test_protected_paths_block_writes_inside_sandbox() {
+    use crate::security::validate_protected_paths;
+
+    let sandbox = std::env::temp_dir().join("nu_mcp_protected");
+    std::fs::create_dir_all(sandbox.join("tools/k8s")).unwrap();
+    std::fs::write(sandbox.join("tools/k8s/mod.nu"), "def main [] {}").unwrap();
+    std::fs::write(sandbox.join("policy.json"), "{}").unwrap();
+    std::fs::write(sandbox.join("audit.jsonl"), "").unwrap();
+    let sandbox = sandbox.canonicalize().unwrap();
+    let sandbox_dirs = [sandbox.clone()];
+    let protected = [
+        sandbox.join("tools"),
+        sandbox.join("policy.json"),
+        sandbox.join("audit.jsonl"),
+        // Rotated audit logs don't exist until the log is rotated
+        sandbox.join("audit.jsonl.1"),
+    ];
+    let check = |command: &str| validate_protected_paths(command, &sandbox_dirs, &protected, None);
+
+    for command in [
+        "open tools/k8s/mod.nu",
+        "cp tools/k8s/mod.nu copy.nu",
+        "open policy.json | to yaml | save policy.yaml",
+        "'x' | save notes.txt",
+    ] {
+        assert!(check(command).is_ok(), "should be allowed: {}", command);
+    }
+    for command in [
+        "'def main [] {}' | save -f tools/k8s/mod.nu",
+        "mkdir tools/evil",
+        "rm -r tools",
+        "'{}' | save -f policy.json",
+        "print x o>> audit.jsonl",
+        "'' | save audit.jsonl.1",
+        "rm -r too*",
+        "rm tools/*/mod.nu",
+        "sh -c \"rm tools/k8s/mod.nu\"",
+        "timeout 5 nu -c \"'x' | save -f policy.json\"",
+        "cd tools; bash -c 'rm k8s/mod.nu'",
+    ] {
+        let err = check(command).expect_err(command);
+        assert!(err.contains("is protected"), "{}", err);
+    }
+    assert!(validate_protected_paths("rm -r tools", &sandbox_dirs, &[], None).is_ok());
+
+    // Relative targets resolve after a `cd`, and in the shell's directory
+    for command in [
+        "cd tools; rm k8s/mod.nu",
+        "cd tools/k8s; 'x' | save -f mod.nu",
+        "cd tools; cd k8s; rm mod.nu",
+    ] {
+        let err = check(command).expect_err(command);
+        assert!(err.contains("is protected"), "{}", err);
+    }
+    let tools = sandbox.join("tools");
+    let err = validate_protected_paths("rm k8s/mod.nu", &sandbox_dirs, &protected, Some(&tools))
+        .expect_err("relative to the shell's directory");
+    assert!(err.contains("is protected"), "{}", err);
+}
+
+// --- Deny list ---
+
+/// A sandbox holding credential files next to ordinary ones
+fn deny_fixture(name: &str) -> PathBuf {
+    let sandbox = std::env::temp_dir().join(name);
+    std::fs::create_dir_all(sandbox.join(".aws")).unwrap();
+    std::fs::write(sandbox.join(".env"), "TOKEN=secret").unwrap();
+    std::fs::write(sandbox.join(".aws/credentials"), "[default]").unwrap();
+    std::fs::write(sandbox.join("notes.txt"), "notes").unwrap();
+    sandbox.canonicalize().unwrap()
+}
+
+fn validate_with_deny_list(command: &str, sandbox: &Path) -> Result<(), String> {
+    crate::security::validate_path_safety_with_cache(
+        command,
+        &[sandbox.to_path_buf()],
+        &crate::security::DenyList::with_defaults(),
+        &crate::security::SafePatterns::default(),
+        &mut crate::security::PathCache::new(),
+        None,
+    )
+}
+
+#[test]
+fn test_deny_list_blocks_paths_inside_sandbox() {
+    let sandbox = deny_fixture("nu_mcp_deny_blocks");
+    for (command, rule) in [
+        ("open .env".to_string(), ".env"),
+        (format!("open {}/.env", sandbox.display()), ".env"),
+        ("cat .aws/credentials".to_string(), ".aws"),
+        ("ls .aws".to_string(), ".aws"),
+        ("open sub/../.env".to_string(), ".env"),
+        ("'k' | save keys/id_rsa".to_string(), "id_rsa*"),
+        // Globs are expanded, and every match is checked
+        ("open .en?".to_string(), ".env"),
+        ("^cat .e*".to_string(), ".env"),
+        ("open *.env".to_string(), ".env"),
+        ("ls .a*/*".to_string(), ".aws"),
+    ] {
+        let err = validate_with_deny_list(&command, &sandbox)
+            .expect_err(&format!("Should be denied: {}", command));
+        assert!(
+            err.contains(&format!("rule '{}'", rule)),
+            "Error should name rule {}: {}",
+            rule,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_deny_list_allows_other_files() {
+    let sandbox = deny_fixture("nu_mcp_deny_allows");
+    for command in [
+        "open notes.txt",
+        "ls",
+        "open environment.md",
+        "echo .env",
+        "open *.txt",
+        "ls n*",
+    ] {
+        let result = validate_with_deny_list(command, &sandbox);
+        assert!(
+            result.is_ok(),
+            "Should be allowed: {} ({:?})",
+            command,
+            result
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_deny_list_follows_symlinks() {
+    let sandbox = deny_fixture("nu_mcp_deny_symlink");
+    let link = sandbox.join("innocent.txt");
+    let _ = std::fs::remove_file(&link);
+    std::os::unix::fs::symlink(sandbox.join(".env"), &link).unwrap();
+
+    let err = validate_with_deny_list("open innocent.txt", &sandbox).unwrap_err();
+    assert!(err.contains("rule '.env'"), "{}", err);
+}
+
 #[cfg(test)]
 mod cache_tests {
     use super::*;
     use crate::security::PathCache;
-    use std::env::current_dir;
+    use std::time::Duration;
 
     // Helper to create cache and validate with it
     fn validate_with_cache(
@@ -70,7 +648,14 @@ mod cache_tests {
         sandbox_dirs: &[PathBuf],
         cache: &mut PathCache,
     ) -> Result<(), String> {
-        crate::security::validate_path_safety_with_cache(command, sandbox_dirs, cache)
+        crate::security::validate_path_safety_with_cache(
+            command,
+            sandbox_dirs,
+            &crate::security::DenyList::default(),
+            &crate::security::SafePatterns::default(),
+            cache,
+            None,
+        )
     }
 
     #[test]
@@ -214,7 +799,11 @@ mod cache_tests {
         let mut cache = PathCache::new();
 
         // Typo: /ect instead of /etc (doesn't exist)
-        let result = validate_with_cache("cat /ect/passwd", &[sandbox_dir], &mut cache);
+        let result = validate_with_cache(
+            "kubectl get --raw /ect/passwd",
+            std::slice::from_ref(&sandbox_dir),
+            &mut cache,
+        );
         assert!(result.is_ok(), "Non-existent typo path should be allowed");
 
         // Verify typo was cached
@@ -222,6 +811,10 @@ mod cache_tests {
             cache.contains("/ect/passwd"),
             "Typo path should be cached as non-filesystem"
         );
+
+        // A file argument is checked even when cached
+        let result = validate_with_cache("cat /ect/passwd", &[sandbox_dir], &mut cache);
+        assert!(result.is_err(), "File argument outside sandbox is blocked");
     }
 
     #[test]
@@ -287,4 +880,176 @@ mod cache_tests {
         validate_with_cache("tool /path1", &[sandbox_dir], &mut cache).ok();
         assert_eq!(cache.len(), 2, "Cache size should not change for duplicate");
     }
+
+    #[test]
+    fn test_cache_skips_filesystem_shaped_paths() {
+        let sandbox_dir = current_dir().unwrap();
+        let mut cache = PathCache::new();
+
+        // Doesn't exist yet, but something could create it later
+        let result = validate_with_cache(
+            "kubectl get --raw /etc/nu-mcp-newthing",
+            &[sandbox_dir],
+            &mut cache,
+        );
+        assert!(result.is_ok(), "Non-existent path should be allowed");
+        assert!(
+            !cache.contains("/etc/nu-mcp-newthing"),
+            "Path under an existing top-level directory should NOT be cached"
+        );
+
+        assert!(!cache.remember("/tmp/../metrics".to_string()));
+        assert!(!cache.remember("/api/with space".to_string()));
+        assert!(cache.remember("/api/v1/pods".to_string()));
+    }
+
+    #[test]
+    fn test_cache_rechecks_existence_on_hit() {
+        let path = std::env::temp_dir().join("nu_mcp_cache_recheck.txt");
+        std::fs::remove_file(&path).ok();
+        let path_str = path.display().to_string();
+
+        let mut cache = PathCache::new();
+        cache.insert(path_str.clone());
+        assert!(cache.hit(&path_str), "Missing path should hit");
+
+        std::fs::write(&path, "created later").unwrap();
+        assert!(!cache.hit(&path_str), "Path that exists now should miss");
+        assert!(!cache.contains(&path_str), "Entry should be dropped");
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_cache_entries_expire() {
+        let mut cache = PathCache::with_limits(Duration::ZERO, 16);
+        cache.remember("/metrics".to_string());
+        assert!(
+            !cache.contains("/metrics"),
+            "Expired entry should not count"
+        );
+        assert!(!cache.hit("/metrics"), "Expired entry should miss");
+        assert!(cache.is_empty(), "Expired entry should be dropped on hit");
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = PathCache::with_limits(Duration::from_secs(60), 2);
+        cache.remember("/metrics".to_string());
+        cache.remember("/healthz".to_string());
+
+        // Use /metrics so /healthz becomes the least recently used
+        assert!(cache.hit("/metrics"));
+        cache.remember("/readyz".to_string());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains("/metrics"));
+        assert!(cache.contains("/readyz"));
+        assert!(!cache.contains("/healthz"), "LRU entry should be evicted");
+    }
+}
+
+// --- Path arguments ---
+
+#[test]
+fn test_validate_path_argument() {
+    use crate::security::{DenyList, validate_path_argument};
+
+    let sandbox = deny_fixture("nu_mcp_path_argument");
+    let sandboxes = [sandbox.clone()];
+    let deny_list = DenyList::with_defaults();
+    let check = |value: &str| validate_path_argument(value, &sandboxes, &deny_list);
+
+    assert!(check("notes.txt").is_ok());
+    assert!(check("new/file.txt").is_ok());
+    assert!(check(&sandbox.join("notes.txt").display().to_string()).is_ok());
+
+    // Unlike command words, a path argument outside the sandbox is rejected even if it doesn't exist
+    assert!(
+        check("/etc/passwd")
+            .unwrap_err()
+            .contains("escapes sandbox")
+    );
+    assert!(
+        check("/no/such/dir/file")
+            .unwrap_err()
+            .contains("escapes sandbox")
+    );
+    assert!(
+        check("../outside.txt")
+            .unwrap_err()
+            .contains("escapes sandbox")
+    );
+    assert!(check(".env").unwrap_err().contains("denied by rule '.env'"));
+    assert!(check(".aws/credentials").unwrap_err().contains("denied"));
+}
+
+// --- Dry runs ---
+
+#[test]
+fn test_explain_path_safety_leaves_cache_alone() {
+    use crate::security::{
+        Classification, DenyList, PathCache, SafePatterns, explain_path_safety,
+        validate_path_safety_with_cache,
+    };
+
+    let sandbox = std::env::current_dir().unwrap();
+    let mut cache = PathCache::new();
+    let (steps, result) = explain_path_safety(
+        "kubectl get --raw /metrics",
+        std::slice::from_ref(&sandbox),
+        &DenyList::default(),
+        &SafePatterns::default(),
+        &cache,
+        None,
+    );
+    assert!(result.is_ok());
+    assert!(cache.is_empty(), "a dry run must not fill the cache");
+    let metrics = steps
+        .iter()
+        .find(|step| step.subject == "/metrics")
+        .unwrap();
+    assert_eq!(metrics.classification, Classification::Path);
+    assert!(metrics.detail.contains("looks like an API endpoint"));
+
+    // The same verdict as the real validator, which does cache the endpoint
+    assert!(
+        validate_path_safety_with_cache(
+            "kubectl get --raw /metrics",
+            std::slice::from_ref(&sandbox),
+            &DenyList::default(),
+            &SafePatterns::default(),
+            &mut cache,
+            None,
+        )
+        .is_ok()
+    );
+    let (steps, _) = explain_path_safety(
+        "kubectl get --raw /metrics",
+        std::slice::from_ref(&sandbox),
+        &DenyList::default(),
+        &SafePatterns::default(),
+        &cache,
+        None,
+    );
+    assert!(
+        steps
+            .iter()
+            .any(|step| step.subject == "/metrics"
+                && step.classification == Classification::CacheHit)
+    );
+
+    let (steps, result) = explain_path_safety(
+        "ls $dir",
+        std::slice::from_ref(&sandbox),
+        &DenyList::default(),
+        &SafePatterns::default(),
+        &cache,
+        None,
+    );
+    assert!(result.unwrap_err().contains("Cannot check argument"));
+    assert_eq!(
+        steps.last().unwrap().classification,
+        Classification::Unresolved
+    );
 }
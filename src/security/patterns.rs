@@ -0,0 +1,193 @@
+//! Safe command patterns
+//!
+//! Regexes matching command segments whose path-like arguments aren't
+//! filesystem paths (`gh api /repos/...`). Matching segments bypass path
+//! validation. The built-in patterns come from `safe_command_patterns.txt`;
+//! `--safe-patterns-file` adds more in the same format, or replaces them with
+//! `--replace-safe-patterns`.
+
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Built-in patterns, embedded at compile time
+const SAFE_PATTERNS_FILE: &str = include_str!("safe_command_patterns.txt");
+
+/// Where the built-in patterns are reported to come from
+const BUILTIN_SOURCE: &str = "built-in";
+
+/// One compiled pattern and where it was defined
+#[derive(Debug, Clone)]
+pub struct SafePattern {
+    regex: Regex,
+    /// `built-in` or the file the pattern was loaded from
+    source: String,
+    line: usize,
+}
+
+impl SafePattern {
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// `source:line`, e.g. `built-in:11`
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.source, self.line)
+    }
+}
+
+impl fmt::Display for SafePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location(), self.regex.as_str())
+    }
+}
+
+/// The safe command patterns in effect. The default has the built-in ones.
+#[derive(Debug, Clone)]
+pub struct SafePatterns {
+    patterns: Vec<SafePattern>,
+}
+
+impl Default for SafePatterns {
+    fn default() -> Self {
+        static BUILTIN: OnceLock<SafePatterns> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| {
+                Self::parse(SAFE_PATTERNS_FILE, BUILTIN_SOURCE)
+                    .expect("built-in safe command patterns are valid")
+            })
+            .clone()
+    }
+}
+
+impl SafePatterns {
+    /// No patterns: every segment is validated
+    pub fn empty() -> Self {
+        Self {
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Read a pattern file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Failed to read safe patterns file '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        Self::parse(&content, &path.display().to_string())
+    }
+
+    /// Compile one regex per line. Lines starting with `#` are comments and
+    /// empty lines are ignored. Every invalid line is reported, with its number.
+    pub fn parse(content: &str, source: &str) -> Result<Self, String> {
+        let mut patterns = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let pattern = line.trim();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+            match Regex::new(pattern) {
+                Ok(regex) => patterns.push(SafePattern {
+                    regex,
+                    source: source.to_string(),
+                    line: index + 1,
+                }),
+                Err(e) => {
+                    // Syntax errors draw a caret under the pattern; keep the summary line
+                    let message = e.to_string();
+                    let reason = message.lines().last().unwrap_or_default();
+                    errors.push(format!(
+                        "{}:{}: invalid safe command pattern '{}': {}",
+                        source,
+                        index + 1,
+                        pattern,
+                        reason.trim_start_matches("error: ")
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Self { patterns })
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Add the patterns of `other` after these
+    pub fn extend(&mut self, other: SafePatterns) {
+        self.patterns.extend(other.patterns);
+    }
+
+    /// The first pattern matching a command segment
+    pub fn find(&self, command: &str) -> Option<&SafePattern> {
+        self.patterns
+            .iter()
+            .find(|pattern| pattern.regex.is_match(command))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SafePattern> {
+        self.patterns.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_patterns() {
+        let patterns = SafePatterns::default();
+        assert!(!patterns.is_empty());
+        let matched = patterns.find("gh api /repos/owner/repo").unwrap();
+        assert_eq!(matched.as_str(), r"^gh\s+api\s+");
+        assert!(matched.to_string().starts_with("built-in:"));
+        assert!(patterns.find("cat /etc/passwd").is_none());
+    }
+
+    #[test]
+    fn test_parse_reports_every_invalid_line() {
+        let err = SafePatterns::parse(
+            "# internal tools\n^acme\\s+api\\s+/\n\n^broken(\n^also[bad\n",
+            "extra.txt",
+        )
+        .unwrap_err();
+        let lines: Vec<&str> = err.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "extra.txt:4: invalid safe command pattern '^broken(': unclosed group"
+        );
+        assert_eq!(
+            lines[1],
+            "extra.txt:5: invalid safe command pattern '^also[bad': unclosed character class"
+        );
+    }
+
+    #[test]
+    fn test_extend_keeps_sources() {
+        let mut patterns = SafePatterns::empty();
+        assert!(patterns.find("acme api /v1/users").is_none());
+
+        patterns.extend(SafePatterns::parse("# comment\n^acme\\s+api\\s+/", "extra.txt").unwrap());
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(
+            patterns.find("acme api /v1/users").unwrap().to_string(),
+            r"extra.txt:2: ^acme\s+api\s+/"
+        );
+    }
+}
@@ -0,0 +1,541 @@
+//! Command policy: allow, deny and ask rules for the commands a call runs
+//!
+//! Path validation only looks at arguments. The policy looks at what is run:
+//! command names, subcommands and flags, so `sudo`, `rm -rf` or
+//! `git push --force` can be stopped even with sandbox-relative arguments.
+//!
+//! Rules are loaded from a JSON file at startup:
+//!
+//! ```json
+//! {
+//!   "rules": [
+//!     { "action": "allow", "command": "git", "subcommand": "push", "flags": ["--dry-run"],
+//!       "message": "Dry runs are fine" },
+//!     { "action": "deny", "command": "git", "subcommand": "push", "flags": ["--force", "-f"],
+//!       "message": "Force pushes rewrite shared history; push normally instead" },
+//!     { "action": "ask", "command": "kubectl", "subcommand": "delete",
+//!       "message": "Deleting cluster resources needs a human to confirm" }
+//!   ]
+//! }
+//! ```
+//!
+//! Every command in a call (statements, pipeline stages, and the bodies of
+//! blocks, closures and subexpressions) is checked on its own. For each one
+//! the first matching rule applies. A call is denied if any command is denied,
+//! otherwise it needs approval if any command does.
+//!
+//! Commands started through a wrapper (`timeout 5 sudo ls`, `env X=1 cmd`)
+//! and scripts passed to a shell (`nu -c "..."`, `sh -c "..."`) are checked
+//! too, in addition to the wrapper itself.
+
+use super::lexer::{self, Command, Word};
+use super::wrappers::{RUNNERS, Wrapped, program_name, wrapped};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Options that take the next word as their value, so it isn't a subcommand
+const VALUE_FLAGS: &[(&str, &[&str])] = &[
+    (
+        "git",
+        &[
+            "-C",
+            "-c",
+            "--git-dir",
+            "--work-tree",
+            "--namespace",
+            "--exec-path",
+            "--config-env",
+        ],
+    ),
+    (
+        "kubectl",
+        &[
+            "-n",
+            "--namespace",
+            "--context",
+            "--cluster",
+            "--user",
+            "--kubeconfig",
+            "-s",
+            "--server",
+            "--token",
+            "--as",
+            "--as-group",
+            "--request-timeout",
+            "-v",
+        ],
+    ),
+    (
+        "helm",
+        &["-n", "--namespace", "--kube-context", "--kubeconfig"],
+    ),
+    (
+        "docker",
+        &[
+            "-H",
+            "--host",
+            "-c",
+            "--context",
+            "--config",
+            "-l",
+            "--log-level",
+        ],
+    ),
+    ("cargo", &["-C", "--config", "-Z", "--color"]),
+    ("gh", &["-R", "--repo"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Allow,
+    Deny,
+    Ask,
+}
+
+/// One policy rule. `subcommand` and `flags` narrow the match; a rule with
+/// neither matches every use of the command.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub action: Action,
+    /// Command name, e.g. `sudo` or `git`. Matches `^git` and `/usr/bin/git` too.
+    pub command: String,
+    /// Leading positional words, e.g. `push` or `rollout restart`
+    #[serde(default)]
+    pub subcommand: Option<String>,
+    /// Matches when any of these flags is given. Short flags also match
+    /// inside a cluster (`-f` matches `-rf`), long flags match `--flag=value`.
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// Returned to the agent when the rule denies or asks
+    pub message: String,
+}
+
+/// A rule that matched one command of a call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub action: Action,
+    /// Source text of the command that matched
+    pub command: String,
+    pub message: String,
+}
+
+/// Loaded policy. The default has no rules and allows everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Load a rule file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read policy file {}: {}", path.display(), e))?;
+        Self::from_json(&content)
+            .map_err(|e| format!("Invalid policy file {}: {}", path.display(), e))
+    }
+
+    /// Parse and check rules from JSON
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        let policy: Self = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        for (index, rule) in policy.rules.iter().enumerate() {
+            if rule.command.trim().is_empty() {
+                return Err(format!("rule {} has an empty command", index + 1));
+            }
+            if rule.message.trim().is_empty() {
+                return Err(format!(
+                    "rule {} ({}) has no message for the agent",
+                    index + 1,
+                    rule.command
+                ));
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Check every command in `source`. Returns the deny or ask verdict that
+    /// applies, or `None` when the call is allowed.
+    pub fn evaluate(&self, source: &str) -> Option<Verdict> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let block = lexer::parse(source);
+        let mut ask = None;
+        for command in lexer::commands(&block) {
+            let verdict = match self.evaluate_command(command) {
+                Some(verdict) => verdict,
+                None => continue,
+            };
+            match verdict.action {
+                Action::Deny => return Some(verdict),
+                Action::Ask if ask.is_none() => ask = Some(verdict),
+                _ => {}
+            }
+        }
+        ask
+    }
+
+    /// The first rule matching one command, or a stricter one matching a
+    /// command it runs through a wrapper or shell
+    fn evaluate_command(&self, command: &Command) -> Option<Verdict> {
+        let verdict = |action, message: &str| Verdict {
+            action,
+            command: command.text.trim().to_string(),
+            message: message.to_string(),
+        };
+
+        let dynamic = || {
+            verdict(
+                Action::Deny,
+                "The command name is only known when it runs, so it can't be checked against the command policy. Name the command literally.",
+            )
+        };
+
+        let words = match invocation(&command.words) {
+            Some(words) => words,
+            None if is_dynamic_call(&command.words) => return Some(dynamic()),
+            None => return None,
+        };
+
+        // The command, then whatever it runs through wrappers
+        let mut words = words.as_slice();
+        let mut found: Option<Verdict> = None;
+        loop {
+            let current = match words[0].is_empty() {
+                true => Some(dynamic()),
+                false => self
+                    .rules
+                    .iter()
+                    .find(|rule| rule.matches(words))
+                    .map(|rule| verdict(rule.action, &rule.message)),
+            };
+            found = stricter(found, current);
+            match wrapped(words) {
+                Some(Wrapped::Command(inner)) => words = inner,
+                Some(Wrapped::Script(source)) if source.is_empty() => {
+                    return stricter(found, Some(dynamic()));
+                }
+                Some(Wrapped::Script(source)) => return stricter(found, self.evaluate(source)),
+                None => return found,
+            }
+        }
+    }
+}
+
+/// The verdict that restricts more: deny, then ask, then allow
+fn stricter(a: Option<Verdict>, b: Option<Verdict>) -> Option<Verdict> {
+    let rank = |verdict: &Option<Verdict>| match verdict.as_ref().map(|v| v.action) {
+        Some(Action::Deny) => 3,
+        Some(Action::Ask) => 2,
+        Some(Action::Allow) => 1,
+        None => 0,
+    };
+    if rank(&b) > rank(&a) { b } else { a }
+}
+
+impl Rule {
+    /// `words` is the command name followed by the arguments, with an empty
+    /// string for each argument that is only known when the command runs
+    fn matches(&self, words: &[String]) -> bool {
+        let Some((name, args)) = words.split_first() else {
+            return false;
+        };
+        let name = program_name(name);
+        if name != self.command {
+            return false;
+        }
+
+        if let Some(subcommand) = &self.subcommand {
+            let expected: Vec<&str> = subcommand.split_whitespace().collect();
+            // Deny and ask rules also try each unknown flag as taking a value,
+            // so `tool --unknown value delete` can't hide the subcommand
+            let lenient = self.action != Action::Allow;
+            if !starts_with_subcommand(name, args, &expected, lenient) {
+                return false;
+            }
+        }
+
+        self.flags.is_empty()
+            || self
+                .flags
+                .iter()
+                .any(|flag| args.iter().any(|arg| flag_matches(flag, arg)))
+    }
+}
+
+/// Whether the positional arguments in `args` start with `expected`. The
+/// value of a known option (`git -C dir push`) isn't positional; with
+/// `lenient`, any other option without `=value` may take one too.
+fn starts_with_subcommand(
+    program: &str,
+    args: &[String],
+    expected: &[&str],
+    lenient: bool,
+) -> bool {
+    let value_flags = VALUE_FLAGS
+        .iter()
+        .find(|(name, _)| *name == program)
+        .map_or(&[][..], |(_, flags)| *flags);
+
+    // Positions in `args` reachable with `matched` subcommand words matched
+    let mut states = vec![(0, 0)];
+    let mut seen = std::collections::HashSet::new();
+    while let Some((index, matched)) = states.pop() {
+        if matched == expected.len() {
+            return true;
+        }
+        if !seen.insert((index, matched)) {
+            continue;
+        }
+        let Some(arg) = args.get(index) else {
+            continue;
+        };
+        if !arg.starts_with('-') {
+            if arg == expected[matched] {
+                states.push((index + 1, matched + 1));
+            }
+        } else if value_flags.contains(&arg.as_str()) {
+            states.push((index + 2, matched));
+        } else {
+            states.push((index + 1, matched));
+            if lenient && !arg.contains('=') {
+                states.push((index + 2, matched));
+            }
+        }
+    }
+    false
+}
+
+/// The program and arguments a command runs, looking through `run-external`
+/// and `exec`. Arguments only known when it runs are empty strings.
+/// `None` for values and dynamic command names.
+fn invocation(words: &[Word]) -> Option<Vec<String>> {
+    let first = words.first()?;
+    if first.is_value() {
+        return None;
+    }
+    let name = first.literal()?;
+    let name = name.strip_prefix('^').unwrap_or(&name).to_string();
+    let literal = |word: &Word| word.literal().unwrap_or_default();
+
+    if RUNNERS.contains(&name.as_str()) {
+        let mut rest = words[1..].iter().skip_while(|word| word.is_flag());
+        let program = rest.next()?.literal()?;
+        return Some(std::iter::once(program).chain(rest.map(literal)).collect());
+    }
+    Some(
+        std::iter::once(name)
+            .chain(words[1..].iter().map(literal))
+            .collect(),
+    )
+}
+
+/// A call whose command name is computed: `^$cmd`, `run-external $cmd`
+fn is_dynamic_call(words: &[Word]) -> bool {
+    let Some(first) = words.first() else {
+        return false;
+    };
+    if first.is_value() {
+        return false;
+    }
+    match first.literal() {
+        None => true,
+        Some(name) => {
+            RUNNERS.contains(&name.trim_start_matches('^'))
+                && words[1..]
+                    .iter()
+                    .find(|word| !word.is_flag())
+                    .is_some_and(|program| program.literal().is_none())
+        }
+    }
+}
+
+fn flag_matches(flag: &str, arg: &str) -> bool {
+    if arg == flag {
+        return true;
+    }
+    if flag.starts_with("--") {
+        return arg
+            .strip_prefix(flag)
+            .is_some_and(|rest| rest.starts_with('='));
+    }
+    // Short flag inside a cluster: -f in -rf
+    match (flag.strip_prefix('-'), arg.strip_prefix('-')) {
+        (Some(short), Some(cluster)) if short.len() == 1 && !cluster.starts_with('-') => {
+            cluster.chars().all(|c| c.is_ascii_alphabetic()) && cluster.contains(short)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy::from_json(
+            r#"{
+              "rules": [
+                { "action": "deny", "command": "sudo", "message": "no root" },
+                { "action": "deny", "command": "rm", "flags": ["-r", "--recursive"], "message": "no recursive rm" },
+                { "action": "allow", "command": "git", "subcommand": "push", "flags": ["--dry-run"], "message": "dry run" },
+                { "action": "deny", "command": "git", "subcommand": "push", "flags": ["--force", "-f"], "message": "no force push" },
+                { "action": "ask", "command": "kubectl", "subcommand": "delete", "message": "confirm delete" },
+                { "action": "ask", "command": "kubectl", "subcommand": "rollout restart", "message": "confirm restart" }
+              ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn action(source: &str) -> Option<Action> {
+        policy().evaluate(source).map(|verdict| verdict.action)
+    }
+
+    #[test]
+    fn test_deny_command() {
+        let verdict = policy().evaluate("sudo ls").unwrap();
+        assert_eq!(verdict.action, Action::Deny);
+        assert_eq!(verdict.command, "sudo ls");
+        assert_eq!(verdict.message, "no root");
+        assert_eq!(action("^sudo ls"), Some(Action::Deny));
+        assert_eq!(action("/usr/bin/sudo ls"), Some(Action::Deny));
+        assert_eq!(action("run-external sudo ls"), Some(Action::Deny));
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(action("rm -rf ."), Some(Action::Deny));
+        assert_eq!(action("rm --recursive build"), Some(Action::Deny));
+        assert_eq!(action("rm file.txt"), None);
+        assert_eq!(action("git push --force-with-lease"), None);
+        assert_eq!(action("git push --force=true"), Some(Action::Deny));
+    }
+
+    #[test]
+    fn test_subcommand() {
+        assert_eq!(action("git push -f origin main"), Some(Action::Deny));
+        assert_eq!(action("git log -f"), None);
+        assert_eq!(action("kubectl delete pod x"), Some(Action::Ask));
+        assert_eq!(
+            action("kubectl rollout restart deploy/x"),
+            Some(Action::Ask)
+        );
+        assert_eq!(action("kubectl rollout status deploy/x"), None);
+    }
+
+    #[test]
+    fn test_flag_values_are_not_subcommands() {
+        assert_eq!(action("kubectl -n prod delete pod x"), Some(Action::Ask));
+        assert_eq!(
+            action("kubectl --namespace=prod delete pod x"),
+            Some(Action::Ask)
+        );
+        assert_eq!(action("git -C . push --force"), Some(Action::Deny));
+        assert_eq!(action("git -c user.name=x push -f"), Some(Action::Deny));
+        // An unknown option may take a value, so deny and ask rules look past it
+        assert_eq!(
+            action("kubectl --made-up prod delete pod x"),
+            Some(Action::Ask)
+        );
+        assert_eq!(action("kubectl -n prod get pods"), None);
+    }
+
+    #[test]
+    fn test_wrapped_commands() {
+        assert_eq!(action("timeout 5 sudo ls"), Some(Action::Deny));
+        assert_eq!(action("timeout -s KILL 5s sudo ls"), Some(Action::Deny));
+        assert_eq!(action("env X=1 sudo ls"), Some(Action::Deny));
+        assert_eq!(action("env -u HOME -- rm -rf ."), Some(Action::Deny));
+        assert_eq!(action("nice -n 10 git push -f"), Some(Action::Deny));
+        assert_eq!(action("env -S 'sudo ls'"), Some(Action::Deny));
+        assert_eq!(action("timeout 5 ls"), None);
+        assert_eq!(action("env X=1"), None);
+        assert_eq!(action("timeout 5 ^$cmd"), Some(Action::Deny));
+    }
+
+    #[test]
+    fn test_shell_scripts() {
+        assert_eq!(action(r#"nu -c "sudo ls""#), Some(Action::Deny));
+        assert_eq!(
+            action("^nu --commands 'ls; kubectl delete pod x'"),
+            Some(Action::Ask)
+        );
+        assert_eq!(action("bash -lc 'sudo ls'"), Some(Action::Deny));
+        assert_eq!(action("sh -c $script"), Some(Action::Deny));
+        assert_eq!(action("nu -c 'ls'"), None);
+        assert_eq!(action("nu script.nu -c x"), None);
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        assert_eq!(action("git push --force --dry-run"), None);
+    }
+
+    #[test]
+    fn test_every_segment_is_checked() {
+        assert_eq!(action("ls; sudo ls"), Some(Action::Deny));
+        assert_eq!(
+            action("ls | each { |f| rm -r $f.name }"),
+            Some(Action::Deny)
+        );
+        assert_eq!(action("print (sudo whoami)"), Some(Action::Deny));
+        assert_eq!(
+            action("kubectl delete pod x; sudo ls"),
+            Some(Action::Deny),
+            "deny wins over ask"
+        );
+    }
+
+    #[test]
+    fn test_dynamic_command_name_denied() {
+        assert_eq!(action("^$cmd ls"), Some(Action::Deny));
+        assert_eq!(action("run-external $cmd"), Some(Action::Deny));
+        assert_eq!(action("$x | get name"), None);
+    }
+
+    #[test]
+    fn test_example_policy_file() {
+        let policy = Policy::from_json(include_str!("../../docs/policy.example.json")).unwrap();
+        let action = |source| policy.evaluate(source).map(|verdict| verdict.action);
+        assert_eq!(action("sudo ls"), Some(Action::Deny));
+        assert_eq!(action("^ssh host"), Some(Action::Deny));
+        assert_eq!(action("git push --force-with-lease"), Some(Action::Deny));
+        assert_eq!(action("git push origin main"), Some(Action::Ask));
+        assert_eq!(action("git status; ls | where size > 1kb"), None);
+    }
+
+    #[test]
+    fn test_empty_policy_allows_everything() {
+        assert_eq!(Policy::default().evaluate("sudo ^$cmd"), None);
+    }
+
+    #[test]
+    fn test_rules_need_a_message() {
+        let err = Policy::from_json(
+            r#"{"rules": [{"action": "deny", "command": "sudo", "message": " "}]}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("rule 1"), "{}", err);
+        assert!(
+            Policy::from_json(r#"{"rules": [{"action": "deny", "command": "sudo"}]}"#).is_err()
+        );
+        assert!(
+            Policy::from_json(
+                r#"{"rules": [{"action": "block", "command": "x", "message": "m"}]}"#
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Commands that run other commands
+//!
+//! `timeout 5 curl ...`, `env X=1 rm ...` and `sh -c "..."` run a command or
+//! script of their own, which the command policy, path validation and the
+//! network allowlist must see too. They all look through wrappers with
+//! `wrapped` (on the policy's plain strings) or `unwrap` (on parsed words).
+
+use super::lexer::Word;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Builtins that run the program named by their first argument
+pub const RUNNERS: &[&str] = &["run-external", "exec"];
+
+/// Programs that run the command in their arguments, with their options that
+/// take a value. `timeout` also takes a duration before the command.
+const WRAPPERS: &[(&str, &[&str])] = &[
+    ("timeout", &["-s", "--signal", "-k", "--kill-after"]),
+    (
+        "env",
+        &["-u", "--unset", "-C", "--chdir", "-S", "--split-string"],
+    ),
+    ("nice", &["-n", "--adjustment"]),
+    ("nohup", &[]),
+    ("time", &["-f", "--format", "-o", "--output"]),
+    (
+        "stdbuf",
+        &["-i", "-o", "-e", "--input", "--output", "--error"],
+    ),
+    (
+        "xargs",
+        &[
+            "-a",
+            "-d",
+            "-E",
+            "-I",
+            "-L",
+            "-n",
+            "-P",
+            "-s",
+            "--arg-file",
+            "--delimiter",
+        ],
+    ),
+    (
+        "sudo",
+        &[
+            "-u", "--user", "-g", "--group", "-C", "-D", "--chdir", "-h", "--host", "-p",
+        ],
+    ),
+    ("doas", &["-u", "-C"]),
+];
+
+/// Shells whose `-c` argument is a script
+const SHELLS: &[&str] = &["nu", "sh", "bash", "zsh", "dash", "ksh", "fish"];
+
+/// A command word as far as wrappers are concerned: its text, or an empty
+/// string if it is only known when the command runs
+pub trait Arg {
+    fn text(&self) -> Cow<'_, str>;
+}
+
+impl Arg for String {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl Arg for Word {
+    fn text(&self) -> Cow<'_, str> {
+        self.literal().map(Cow::Owned).unwrap_or_default()
+    }
+}
+
+/// What a wrapper or shell runs
+pub enum Wrapped<'a, W> {
+    /// The wrapped program and its arguments
+    Command(&'a [W]),
+    /// The word holding a `-c` script
+    Script(&'a W),
+}
+
+/// The command `words` runs through a wrapper (`timeout 5 sudo ls` runs
+/// `sudo ls`), or the script it passes to a shell
+pub fn wrapped<W: Arg>(words: &[W]) -> Option<Wrapped<'_, W>> {
+    let first = words.first()?.text();
+    let name = program_name(&first);
+    if SHELLS.contains(&name) {
+        let mut args = words[1..].iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.text();
+            let cluster = arg
+                .strip_prefix('-')
+                .filter(|flags| !flags.starts_with('-'));
+            if arg == "--commands" || cluster.is_some_and(|flags| flags.contains('c')) {
+                return args.next().map(Wrapped::Script);
+            }
+            if !arg.starts_with('-') {
+                // A script file, which can't be checked
+                return None;
+            }
+        }
+        return None;
+    }
+
+    let (_, options) = WRAPPERS.iter().find(|(wrapper, _)| *wrapper == name)?;
+    let mut duration = name == "timeout";
+    let mut index = 1;
+    while let Some(arg) = words.get(index) {
+        let arg = arg.text();
+        if arg == "--" {
+            index += 1;
+            break;
+        }
+        if name == "env" && (arg == "-S" || arg == "--split-string") {
+            return words.get(index + 1).map(Wrapped::Script);
+        }
+        if options.contains(&arg.as_ref()) {
+            index += 2;
+        } else if arg.starts_with('-') || (name == "env" && arg.contains('=')) {
+            index += 1;
+        } else if duration {
+            duration = false;
+            index += 1;
+        } else {
+            break;
+        }
+    }
+    (index < words.len()).then(|| Wrapped::Command(&words[index..]))
+}
+
+/// A command and each command it runs through `run-external`, `exec` and
+/// wrappers, followed by the word holding the script it passes to a shell
+pub fn unwrap(words: &[Word]) -> (Vec<&[Word]>, Option<&Word>) {
+    let mut chain = Vec::new();
+    let mut words = run_target(words);
+    while !words.is_empty() {
+        chain.push(words);
+        match wrapped(words) {
+            Some(Wrapped::Command(inner)) => words = run_target(inner),
+            Some(Wrapped::Script(script)) => return (chain, Some(script)),
+            None => break,
+        }
+    }
+    (chain, None)
+}
+
+/// The program `run-external` or `exec` runs and its arguments, or `words`
+fn run_target(words: &[Word]) -> &[Word] {
+    let is_runner = words
+        .first()
+        .and_then(Word::literal)
+        .is_some_and(|name| RUNNERS.contains(&name.trim_start_matches('^')));
+    if !is_runner {
+        return words;
+    }
+    match words[1..].iter().position(|word| !word.is_flag()) {
+        Some(program) => &words[program + 1..],
+        None => &[],
+    }
+}
+
+/// `/usr/bin/sudo`, `^sudo` and `sudo` are the same program
+pub fn program_name(name: &str) -> &str {
+    let name = name.trim_start_matches('^');
+    Path::new(name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(name)
+}
@@ -1,6 +1,7 @@
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::{Context, Result, anyhow};
@@ -8,12 +9,24 @@ use rmcp::{
     model::Tool,
     serde_json::{Map, Value},
 };
-use tokio::process::Command;
 
 use super::ExtensionTool;
+use super::execution::NushellToolExecutor;
+use crate::execution::get_default_timeout;
+use crate::execution::group::{WaitError, output_with_timeout};
 
 /// Discover tools from nushell modules in the given directory
 pub async fn discover_tools(tools_dir: &PathBuf) -> Result<Vec<ExtensionTool>> {
+    discover_verified_tools(tools_dir, &NushellToolExecutor::default()).await
+}
+
+/// Discover tools from the modules in the given directory that match the
+/// executor's lock. Modules that don't are skipped with a warning, without
+/// being run; the others list their tools confined the way their tools run.
+pub async fn discover_verified_tools(
+    tools_dir: &PathBuf,
+    executor: &NushellToolExecutor,
+) -> Result<Vec<ExtensionTool>> {
     let mut extension_tools = Vec::new();
 
     // Check if directory exists
@@ -25,7 +38,7 @@ pub async fn discover_tools(tools_dir: &PathBuf) -> Result<Vec<ExtensionTool>> {
     let mod_file = tools_dir.join("mod.nu");
     if mod_file.exists() {
         // The tools_dir is itself a module directory, process it directly
-        match discover_tools_from_module(tools_dir).await {
+        match discover_tools_from_module(tools_dir, executor).await {
             Ok(mut tools) => extension_tools.append(&mut tools),
             Err(e) => eprintln!(
                 "Warning: Failed to discover tools from {}: {}",
@@ -46,7 +59,7 @@ pub async fn discover_tools(tools_dir: &PathBuf) -> Result<Vec<ExtensionTool>> {
         if path.is_dir() {
             let mod_file = path.join("mod.nu");
             if mod_file.exists() {
-                match discover_tools_from_module(&path).await {
+                match discover_tools_from_module(&path, executor).await {
                     Ok(mut tools) => extension_tools.append(&mut tools),
                     Err(e) => eprintln!(
                         "Warning: Failed to discover tools from {}: {}",
@@ -62,24 +75,38 @@ pub async fn discover_tools(tools_dir: &PathBuf) -> Result<Vec<ExtensionTool>> {
 }
 
 /// Discover tools from a nushell module
-async fn discover_tools_from_module(module_path: &Path) -> Result<Vec<ExtensionTool>> {
+async fn discover_tools_from_module(
+    module_path: &Path,
+    executor: &NushellToolExecutor,
+) -> Result<Vec<ExtensionTool>> {
     let mod_file = module_path.join("mod.nu");
+    executor.lock().verify(module_path)?;
 
     // Execute the mod.nu file with list-tools subcommand
-    let output = Command::new("nu")
-        .arg(&mod_file)
-        .arg("list-tools")
-        .output()
-        .await
-        .with_context(|| {
-            format!(
-                "Failed to execute nushell command for {}",
+    let (mut cmd, child) = executor.module_command(module_path, &["list-tools"]);
+    let timeout = Duration::from_secs(get_default_timeout());
+    let output = match output_with_timeout(&mut cmd, timeout).await {
+        Ok(output) => output,
+        Err(WaitError::TimedOut(_)) => {
+            return Err(anyhow!(
+                "Listing tools timed out after {} seconds for {}",
+                timeout.as_secs(),
                 mod_file.display()
-            )
-        })?;
+            ));
+        }
+        Err(WaitError::Spawn(e) | WaitError::Wait(e)) => {
+            return Err(e).with_context(|| {
+                format!(
+                    "Failed to execute nushell command for {}",
+                    mod_file.display()
+                )
+            });
+        }
+    };
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        child.limits.annotate(&output.status, &mut stderr);
         return Err(anyhow!(
             "Module execution failed for {}: {stderr}",
             mod_file.display()
@@ -1,10 +1,14 @@
 use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::process::Command;
-use tokio::time::timeout;
 
 use super::ExtensionTool;
-use crate::execution::get_default_timeout;
+use super::lock::ModuleLock;
+use super::paths::{READ_ONLY_DIRS_ENV, SANDBOX_DIRS_ENV};
+use crate::execution::group::{WaitError, output_with_timeout};
+use crate::execution::{ChildConfig, EnvPolicy, get_default_timeout};
 
 pub trait ToolExecutor: Send + Sync {
     fn execute_tool(
@@ -16,8 +20,84 @@ pub trait ToolExecutor: Send + Sync {
     ) -> impl std::future::Future<Output = Result<String>> + Send;
 }
 
-#[derive(Clone)]
-pub struct NushellToolExecutor;
+#[derive(Clone, Default)]
+pub struct NushellToolExecutor {
+    child: ChildConfig,
+    /// Environments for modules that don't use `child.env`, by directory name
+    module_env: HashMap<String, EnvPolicy>,
+    /// Sandbox directories, and the read-only ones, passed to tools in the environment
+    sandbox: Vec<PathBuf>,
+    read_only: Vec<PathBuf>,
+    /// Hashes modules must match before their tools run
+    lock: ModuleLock,
+}
+
+impl NushellToolExecutor {
+    /// An executor whose tool processes are confined by `child`
+    pub fn new(child: ChildConfig) -> Self {
+        Self {
+            child,
+            ..Default::default()
+        }
+    }
+
+    /// Tell tools which directories they may use, in `NU_MCP_SANDBOX_DIRS`
+    /// and `NU_MCP_READ_ONLY_DIRS`
+    pub fn with_sandbox(mut self, sandbox: &[PathBuf], read_only: &[PathBuf]) -> Self {
+        let canonical = |dirs: &[PathBuf]| {
+            dirs.iter()
+                .map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+                .collect()
+        };
+        self.sandbox = canonical(sandbox);
+        self.read_only = canonical(read_only);
+        self
+    }
+
+    /// Give the tools of these modules their own environment
+    pub fn with_module_env(mut self, module_env: HashMap<String, EnvPolicy>) -> Self {
+        self.module_env = module_env;
+        self
+    }
+
+    /// Refuse to run tools from modules that changed since they were locked
+    pub fn with_lock(mut self, lock: ModuleLock) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Hashes modules must match before they are run
+    pub fn lock(&self) -> &ModuleLock {
+        &self.lock
+    }
+
+    /// Confinement for the module at `module_path`
+    fn child_for(&self, module_path: &Path) -> ChildConfig {
+        let module = module_path.file_name().map(|name| name.to_string_lossy());
+        match module.and_then(|module| self.module_env.get(module.as_ref())) {
+            Some(env) => self.child.with_env(env.clone()),
+            None => self.child.clone(),
+        }
+    }
+
+    /// `nu mod.nu <args>` for the module at `module_path`, confined the way
+    /// its tools are and told about the sandbox. Returns the confinement too.
+    pub fn module_command(&self, module_path: &Path, args: &[&str]) -> (Command, ChildConfig) {
+        let mut cmd = Command::new("nu");
+        cmd.arg(module_path.join("mod.nu")).args(args);
+        let child = self.child_for(module_path);
+        child.apply(&mut cmd);
+        for (name, dirs) in [
+            (SANDBOX_DIRS_ENV, &self.sandbox),
+            (READ_ONLY_DIRS_ENV, &self.read_only),
+        ] {
+            if let Ok(value) = std::env::join_paths(dirs) {
+                cmd.env(name, value);
+            }
+        }
+        (cmd, child)
+    }
+}
 
 impl ToolExecutor for NushellToolExecutor {
     async fn execute_tool(
@@ -28,37 +108,43 @@ impl ToolExecutor for NushellToolExecutor {
         timeout_secs: Option<u64>,
     ) -> Result<String> {
         let mod_file = extension.module_path.join("mod.nu");
+        self.lock.verify(&extension.module_path)?;
 
         // Priority: parameter > env var > built-in default (60s)
         let timeout_duration =
             Duration::from_secs(timeout_secs.unwrap_or_else(get_default_timeout));
 
-        let cmd_future = Command::new("nu")
-            .arg(&mod_file)
-            .arg("call-tool")
-            .arg(tool_name)
-            .arg(args)
-            .output();
-
-        let output = timeout(timeout_duration, cmd_future)
-            .await
-            .map_err(|_| {
-                anyhow!(
+        let (mut cmd, child) =
+            self.module_command(&extension.module_path, &["call-tool", tool_name, args]);
+
+        let output = match output_with_timeout(&mut cmd, timeout_duration).await {
+            Ok(output) => output,
+            Err(WaitError::TimedOut(termination)) => {
+                let mut message = format!(
                     "Tool '{}' timed out after {} seconds",
                     tool_name,
                     timeout_duration.as_secs()
-                )
-            })?
-            .with_context(|| {
-                format!(
-                    "Failed to execute tool '{}' from {}",
-                    tool_name,
-                    mod_file.display()
-                )
-            })?;
+                );
+                if let Some(note) = termination.note() {
+                    message.push('\n');
+                    message.push_str(&note);
+                }
+                return Err(anyhow!(message));
+            }
+            Err(WaitError::Spawn(e) | WaitError::Wait(e)) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to execute tool '{}' from {}",
+                        tool_name,
+                        mod_file.display()
+                    )
+                });
+            }
+        };
 
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            child.limits.annotate(&output.status, &mut stderr);
             return Err(anyhow!("Tool '{}' execution failed: {stderr}", tool_name));
         }
 
@@ -5,7 +5,11 @@ use anyhow::Result;
 use rmcp::model::Tool;
 use serde_json::Map;
 
-use super::{ExtensionTool, NushellToolExecutor, ToolExecutor, discover_tools};
+use super::lock::write_lock;
+use super::{
+    ExtensionTool, ModuleLock, NushellToolExecutor, ToolExecutor, discover_tools,
+    discover_verified_tools,
+};
 
 fn get_test_tools_dir() -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test/tools")
@@ -17,7 +21,7 @@ async fn execute_extension_tool_helper(
     args: &str,
     timeout_secs: Option<u64>,
 ) -> Result<String> {
-    let tool_executor = NushellToolExecutor;
+    let tool_executor = NushellToolExecutor::default();
     tool_executor
         .execute_tool(extension, tool_name, args, timeout_secs)
         .await
@@ -346,3 +350,186 @@ async fn test_tool_executor_short_command_completes() {
     let output = result.unwrap();
     assert!(output.contains("Slept for 1 seconds"));
 }
+
+#[tokio::test]
+async fn test_tool_executor_module_env_policy() {
+    use crate::execution::{ChildConfig, EnvMode, EnvPolicy};
+    use std::collections::HashMap;
+
+    let tools_dir = std::env::temp_dir().join("nu_mcp_env_tools");
+    for module in ["k8s", "other"] {
+        let module_dir = tools_dir.join(module);
+        std::fs::create_dir_all(&module_dir).unwrap();
+        std::fs::write(
+            module_dir.join("mod.nu"),
+            "def \"main call-tool\" [tool_name: string, args: string = \"{}\"] {\n  \
+             [($env.HOME? | default 'unset') ($env.KUBECONFIG? | default 'unset')] | str join ' '\n}\n\
+             def main [] {}\n",
+        )
+        .unwrap();
+    }
+    let extension = |module: &str| ExtensionTool {
+        module_path: tools_dir.join(module),
+        tool_definition: Tool::new("show_env", "Show env", Arc::new(Map::new())),
+    };
+
+    let hide_home = ChildConfig {
+        env: EnvPolicy {
+            mode: EnvMode::Denylist,
+            vars: vec!["HOME".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let k8s = EnvPolicy {
+        set: [("KUBECONFIG".to_string(), "/kube/config".to_string())].into(),
+        ..Default::default()
+    };
+    let executor = NushellToolExecutor::new(hide_home)
+        .with_module_env(HashMap::from([("k8s".to_string(), k8s)]));
+
+    let output = executor
+        .execute_tool(&extension("other"), "show_env", "{}", None)
+        .await
+        .unwrap();
+    assert_eq!(output.trim(), "unset unset");
+
+    // The module's own policy replaces the executor's
+    let output = executor
+        .execute_tool(&extension("k8s"), "show_env", "{}", None)
+        .await
+        .unwrap();
+    assert!(output.trim().ends_with(" /kube/config"), "{}", output);
+    assert!(!output.starts_with("unset"), "{}", output);
+}
+
+#[tokio::test]
+async fn test_tool_executor_passes_sandbox_in_env() {
+    let module_dir = std::env::temp_dir().join("nu_mcp_sandbox_env_tool");
+    std::fs::create_dir_all(&module_dir).unwrap();
+    std::fs::write(
+        module_dir.join("mod.nu"),
+        "def \"main call-tool\" [tool_name: string, args: string = \"{}\"] {\n  \
+         [$env.NU_MCP_SANDBOX_DIRS $env.NU_MCP_READ_ONLY_DIRS] | str join '|'\n}\n\
+         def main [] {}\n",
+    )
+    .unwrap();
+    let extension = ExtensionTool {
+        module_path: module_dir.clone(),
+        tool_definition: Tool::new("show_sandbox", "Show sandbox", Arc::new(Map::new())),
+    };
+    let sandbox = module_dir.canonicalize().unwrap();
+    let read_only = std::env::temp_dir().canonicalize().unwrap();
+
+    let executor = NushellToolExecutor::default().with_sandbox(
+        &[sandbox.clone(), read_only.clone()],
+        std::slice::from_ref(&read_only),
+    );
+    let output = executor
+        .execute_tool(&extension, "show_sandbox", "{}", None)
+        .await
+        .unwrap();
+
+    let separator = if cfg!(windows) { ";" } else { ":" };
+    assert_eq!(
+        output.trim(),
+        format!(
+            "{}{}{}|{}",
+            sandbox.display(),
+            separator,
+            read_only.display(),
+            read_only.display()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_changed_modules_are_not_loaded_or_run() {
+    let tools_dir = std::env::temp_dir().join("nu_mcp_locked_tools");
+    let _ = std::fs::remove_dir_all(&tools_dir);
+    let module_dir = tools_dir.join("greet");
+    std::fs::create_dir_all(&module_dir).unwrap();
+    let module = |greeting: &str| {
+        format!(
+            "def \"main list-tools\" [] {{\n  \
+             [{{name: \"greet\", description: \"Greet\", input_schema: {{type: \"object\"}}}}] | to json\n}}\n\
+             def \"main call-tool\" [tool_name: string, args: string = \"{{}}\"] {{ '{}' }}\n\
+             def main [] {{}}\n",
+            greeting
+        )
+    };
+    std::fs::write(module_dir.join("mod.nu"), module("hello")).unwrap();
+    let lock_path = tools_dir.join("tools.lock.json");
+    write_lock(&tools_dir, &lock_path).unwrap();
+    let lock = ModuleLock::load(&lock_path, &tools_dir, false).unwrap();
+
+    let executor = NushellToolExecutor::default().with_lock(lock.clone());
+    let tools = discover_verified_tools(&tools_dir, &executor)
+        .await
+        .unwrap();
+    assert_eq!(tools.len(), 1);
+    let output = executor
+        .execute_tool(&tools[0], "greet", "{}", None)
+        .await
+        .unwrap();
+    assert_eq!(output.trim(), "hello");
+
+    // Changed after the server loaded it: the tool is refused
+    std::fs::write(module_dir.join("mod.nu"), module("pwned")).unwrap();
+    let err = executor
+        .execute_tool(&tools[0], "greet", "{}", None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("'greet' changed"), "{}", err);
+
+    // Changed before discovery: the module isn't run at all
+    assert!(
+        discover_verified_tools(&tools_dir, &executor)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let warn_only = ModuleLock::load(&lock_path, &tools_dir, true).unwrap();
+    let executor = NushellToolExecutor::default().with_lock(warn_only);
+    assert_eq!(
+        discover_verified_tools(&tools_dir, &executor)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_discovery_is_confined_like_the_tools() {
+    use crate::execution::{ChildConfig, EnvMode, EnvPolicy};
+
+    let tools_dir = std::env::temp_dir().join("nu_mcp_confined_discovery");
+    let _ = std::fs::remove_dir_all(&tools_dir);
+    let module_dir = tools_dir.join("probe");
+    std::fs::create_dir_all(&module_dir).unwrap();
+    std::fs::write(
+        module_dir.join("mod.nu"),
+        "def \"main list-tools\" [] {\n  \
+         [{name: \"probe\", description: ($env.HOME? | default 'unset'), input_schema: {type: \"object\"}}] | to json\n}\n\
+         def main [] {}\n",
+    )
+    .unwrap();
+
+    let hide_home = ChildConfig {
+        env: EnvPolicy {
+            mode: EnvMode::Denylist,
+            vars: vec!["HOME".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let tools = discover_verified_tools(&tools_dir, &NushellToolExecutor::new(hide_home))
+        .await
+        .unwrap();
+    assert_eq!(tools.len(), 1);
+    assert_eq!(
+        tools[0].tool_definition.description.as_deref(),
+        Some("unset")
+    );
+}
@@ -0,0 +1,341 @@
+//! Integrity pinning for extension modules
+//!
+//! Discovery runs every `mod.nu` in the tools directory, so anyone who can
+//! write to a shared or synced tools directory can run code in the server.
+//! A lock file records a SHA-256 hash of each module's `.nu` files after
+//! they have been reviewed:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "modules": {
+//!     "_common": "sha256:9f2c...",
+//!     "k8s": "sha256:41ab..."
+//!   }
+//! }
+//! ```
+//!
+//! Every directory in the tools directory that holds `.nu` files is pinned,
+//! including shared libraries without a `mod.nu` (`_common`), since modules
+//! `use` them. A module is only loaded and run while its own hash and those
+//! of the shared directories match the lock.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Format of the lock file
+const LOCK_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LockFile {
+    version: u32,
+    /// Hash of each directory, by name
+    modules: BTreeMap<String, String>,
+}
+
+/// Pinned module hashes. The default pins nothing and accepts every module.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleLock {
+    /// The tools directory the hashes were recorded for; `None` without a lock
+    tools_dir: Option<PathBuf>,
+    pinned: BTreeMap<String, String>,
+    /// Log mismatches instead of refusing the module
+    warn_only: bool,
+}
+
+/// How the tools directory differs from a lock
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockChanges {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl LockChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl ModuleLock {
+    /// Read the lock file for the modules in `tools_dir`
+    pub fn load(path: &Path, tools_dir: &Path, warn_only: bool) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read tools lock {}. Review the modules and run `nu-mcp --tools-dir={} --tools-lock={} lock-tools` to create it",
+                path.display(),
+                tools_dir.display(),
+                path.display()
+            )
+        })?;
+        let pinned =
+            parse(&content).with_context(|| format!("Invalid tools lock {}", path.display()))?;
+        Ok(Self {
+            tools_dir: Some(tools_dir.to_path_buf()),
+            pinned,
+            warn_only,
+        })
+    }
+
+    /// Check that the module at `module_path`, and the shared directories
+    /// next to it, still match the lock. Mismatches are errors, or warnings
+    /// if the lock only warns.
+    pub fn verify(&self, module_path: &Path) -> Result<()> {
+        let Some(tools_dir) = &self.tools_dir else {
+            return Ok(());
+        };
+
+        let module = directory_name(module_path);
+        let mut dirs = vec![(module.clone(), module_path.to_path_buf())];
+        if module_path != tools_dir {
+            dirs.extend(
+                self.pinned
+                    .keys()
+                    .filter(|name| **name != module)
+                    .map(|name| (name.clone(), tools_dir.join(name)))
+                    .filter(|(_, dir)| !dir.join("mod.nu").exists()),
+            );
+        }
+
+        let mut problems = Vec::new();
+        for (name, dir) in dirs {
+            let current = hash_directory(&dir);
+            match (self.pinned.get(&name), current) {
+                (None, _) => problems.push(format!("'{}' is not in the lock", name)),
+                (Some(pinned), Ok(current)) if *pinned == current => {}
+                (Some(_), Ok(_)) => problems.push(format!("'{}' changed", name)),
+                (Some(_), Err(e)) => problems.push(format!("'{}' can't be hashed: {:#}", name, e)),
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        let message = format!(
+            "Module {} doesn't match the tools lock: {}. Review the changes and run `nu-mcp lock-tools` to accept them.",
+            module_path.display(),
+            problems.join(", ")
+        );
+        if !self.warn_only {
+            bail!(message);
+        }
+        tracing::warn!("{}", message);
+        Ok(())
+    }
+}
+
+/// Hash every directory of `tools_dir` that holds `.nu` files, or
+/// `tools_dir` itself if it is a module
+pub fn hash_tools(tools_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    if tools_dir.join("mod.nu").exists() {
+        hashes.insert(directory_name(tools_dir), hash_directory(tools_dir)?);
+        return Ok(hashes);
+    }
+
+    let entries = std::fs::read_dir(tools_dir)
+        .with_context(|| format!("Failed to read {}", tools_dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() && !nu_files(&path)?.is_empty() {
+            hashes.insert(directory_name(&path), hash_directory(&path)?);
+        }
+    }
+    Ok(hashes)
+}
+
+/// Record the current hashes of `tools_dir` in the lock file at `path`,
+/// returning how they differ from the lock it replaces
+pub fn write_lock(tools_dir: &Path, path: &Path) -> Result<LockChanges> {
+    let previous = match std::fs::read_to_string(path) {
+        Ok(content) => parse(&content).unwrap_or_default(),
+        Err(_) => BTreeMap::new(),
+    };
+    let modules = hash_tools(tools_dir)?;
+
+    let changes = LockChanges {
+        added: names(&modules, |name, _| !previous.contains_key(name)),
+        changed: names(&modules, |name, hash| {
+            previous.get(name).is_some_and(|old| old != hash)
+        }),
+        removed: names(&previous, |name, _| !modules.contains_key(name)),
+    };
+
+    let lock = LockFile {
+        version: LOCK_VERSION,
+        modules,
+    };
+    let mut content = serde_json::to_string_pretty(&lock)?;
+    content.push('\n');
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write tools lock {}", path.display()))?;
+    Ok(changes)
+}
+
+fn parse(content: &str) -> Result<BTreeMap<String, String>> {
+    let lock: LockFile = serde_json::from_str(content)?;
+    if lock.version != LOCK_VERSION {
+        return Err(anyhow!(
+            "unsupported version {} (expected {})",
+            lock.version,
+            LOCK_VERSION
+        ));
+    }
+    Ok(lock.modules)
+}
+
+fn names(hashes: &BTreeMap<String, String>, keep: impl Fn(&str, &str) -> bool) -> Vec<String> {
+    hashes
+        .iter()
+        .filter(|(name, hash)| keep(name, hash))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+fn directory_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// SHA-256 over the relative path and contents of each `.nu` file in `dir`
+/// and its subdirectories, in path order
+fn hash_directory(dir: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for path in nu_files(dir)? {
+        let content =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(format!("sha256:{}", hex))
+}
+
+/// The `.nu` files in `dir` and its subdirectories, sorted. Symlinked
+/// directories aren't followed.
+fn nu_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "nu") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tools directory with a module and a shared library
+    fn tools_fixture(name: &str) -> PathBuf {
+        let tools = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&tools);
+        std::fs::create_dir_all(tools.join("k8s")).unwrap();
+        std::fs::create_dir_all(tools.join("_common")).unwrap();
+        std::fs::create_dir_all(tools.join("docs")).unwrap();
+        std::fs::write(tools.join("k8s/mod.nu"), "use ../_common/toon.nu *").unwrap();
+        std::fs::write(tools.join("k8s/README.md"), "k8s tools").unwrap();
+        std::fs::write(tools.join("_common/toon.nu"), "export def to-toon [] {}").unwrap();
+        std::fs::write(tools.join("docs/notes.md"), "no nushell here").unwrap();
+        tools
+    }
+
+    #[test]
+    fn test_hash_tools() {
+        let tools = tools_fixture("nu_mcp_lock_hash");
+        let hashes = hash_tools(&tools).unwrap();
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), ["_common", "k8s"]);
+        assert!(hashes["k8s"].starts_with("sha256:"));
+        assert_eq!(hashes["k8s"].len(), "sha256:".len() + 64);
+
+        // Only .nu files count
+        std::fs::write(tools.join("k8s/README.md"), "edited").unwrap();
+        assert_eq!(hash_tools(&tools).unwrap(), hashes);
+        std::fs::write(tools.join("k8s/extra.nu"), "").unwrap();
+        assert_ne!(hash_tools(&tools).unwrap()["k8s"], hashes["k8s"]);
+
+        // A tools directory that is itself a module is pinned under its name
+        let module = hash_tools(&tools.join("k8s")).unwrap();
+        assert_eq!(module.keys().collect::<Vec<_>>(), ["k8s"]);
+    }
+
+    #[test]
+    fn test_verify() {
+        let tools = tools_fixture("nu_mcp_lock_verify");
+        let lock_path = tools.join("tools.lock.json");
+        let changes = write_lock(&tools, &lock_path).unwrap();
+        assert_eq!(changes.added, ["_common", "k8s"]);
+
+        let lock = ModuleLock::load(&lock_path, &tools, false).unwrap();
+        assert!(lock.verify(&tools.join("k8s")).is_ok());
+
+        // A change to a shared library refuses the modules that may use it
+        std::fs::write(
+            tools.join("_common/toon.nu"),
+            "export def to-toon [] { rm -rf ~ }",
+        )
+        .unwrap();
+        let err = lock.verify(&tools.join("k8s")).unwrap_err().to_string();
+        assert!(err.contains("'_common' changed"), "{}", err);
+        assert!(
+            ModuleLock::load(&lock_path, &tools, true)
+                .unwrap()
+                .verify(&tools.join("k8s"))
+                .is_ok()
+        );
+
+        // New modules aren't trusted until they are locked
+        std::fs::create_dir_all(tools.join("new")).unwrap();
+        std::fs::write(tools.join("new/mod.nu"), "").unwrap();
+        let err = lock.verify(&tools.join("new")).unwrap_err().to_string();
+        assert!(err.contains("'new' is not in the lock"), "{}", err);
+
+        let changes = write_lock(&tools, &lock_path).unwrap();
+        assert_eq!(changes.added, ["new"]);
+        assert_eq!(changes.changed, ["_common"]);
+        assert!(changes.removed.is_empty());
+        let lock = ModuleLock::load(&lock_path, &tools, false).unwrap();
+        assert!(lock.verify(&tools.join("new")).is_ok());
+        assert!(lock.verify(&tools.join("k8s")).is_ok());
+
+        assert!(ModuleLock::default().verify(&tools.join("new")).is_ok());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let tools = tools_fixture("nu_mcp_lock_errors");
+        let missing = ModuleLock::load(&tools.join("missing.json"), &tools, false)
+            .unwrap_err()
+            .to_string();
+        assert!(missing.contains("lock-tools"), "{}", missing);
+
+        let lock_path = tools.join("bad.json");
+        std::fs::write(&lock_path, r#"{"version": 2, "modules": {}}"#).unwrap();
+        let err = format!(
+            "{:#}",
+            ModuleLock::load(&lock_path, &tools, false).unwrap_err()
+        );
+        assert!(err.contains("unsupported version 2"), "{}", err);
+    }
+}
@@ -9,9 +9,13 @@ pub struct ExtensionTool {
 
 pub mod discovery;
 pub mod execution;
+pub mod lock;
+pub mod paths;
 
-pub use discovery::discover_tools;
+pub use discovery::{discover_tools, discover_verified_tools};
 pub use execution::{NushellToolExecutor, ToolExecutor};
+pub use lock::ModuleLock;
+pub use paths::path_arguments;
 
 #[cfg(test)]
 pub mod mock;
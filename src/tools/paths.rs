@@ -0,0 +1,118 @@
+//! Filesystem path arguments of extension tools
+//!
+//! A tool's `input_schema` marks string properties that name files with
+//! `format: "path"` (or `"x-sandbox-path": true` where `format` is taken).
+//! Their values are checked against the sandbox before the module runs,
+//! like the paths in `run` and `shell` commands.
+
+use rmcp::model::JsonObject;
+use serde_json::Value;
+
+/// Schema keyword marking a path property, for schemas that use `format` for something else
+pub const PATH_KEYWORD: &str = "x-sandbox-path";
+
+/// Environment variable listing the sandbox directories for tool processes,
+/// separated like `PATH`
+pub const SANDBOX_DIRS_ENV: &str = "NU_MCP_SANDBOX_DIRS";
+
+/// Environment variable listing the read-only subset of the sandbox directories
+pub const READ_ONLY_DIRS_ENV: &str = "NU_MCP_READ_ONLY_DIRS";
+
+/// The path arguments in `args`, as (argument name, value). Nested objects
+/// and arrays are followed through `properties` and `items`; names look like
+/// `files[0]` or `options.output`.
+pub fn path_arguments(schema: &JsonObject, args: &JsonObject) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    collect_object(schema, args, "", &mut found);
+    found
+}
+
+fn collect_object(
+    schema: &JsonObject,
+    args: &JsonObject,
+    prefix: &str,
+    found: &mut Vec<(String, String)>,
+) {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return;
+    };
+    for (name, property) in properties {
+        if let (Some(property), Some(value)) = (property.as_object(), args.get(name)) {
+            collect(property, value, format!("{}{}", prefix, name), found);
+        }
+    }
+}
+
+fn collect(schema: &JsonObject, value: &Value, name: String, found: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(path) if is_path(schema) => found.push((name, path.clone())),
+        Value::Array(items) => {
+            if let Some(items_schema) = schema.get("items").and_then(Value::as_object) {
+                for (index, item) in items.iter().enumerate() {
+                    collect(items_schema, item, format!("{}[{}]", name, index), found);
+                }
+            }
+        }
+        Value::Object(object) => collect_object(schema, object, &format!("{}.", name), found),
+        _ => {}
+    }
+}
+
+fn is_path(schema: &JsonObject) -> bool {
+    schema.get("format").and_then(Value::as_str) == Some("path")
+        || schema.get(PATH_KEYWORD).and_then(Value::as_bool) == Some(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> JsonObject {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_finds_marked_properties() {
+        let schema = object(json!({
+            "type": "object",
+            "properties": {
+                "file": { "type": "string", "format": "path" },
+                "name": { "type": "string" },
+                "inputs": { "type": "array", "items": { "type": "string", "x-sandbox-path": true } },
+                "options": {
+                    "type": "object",
+                    "properties": { "output": { "type": "string", "format": "path" } }
+                }
+            }
+        }));
+        let args = object(json!({
+            "file": "/etc/passwd",
+            "name": "/not/a/path/argument",
+            "inputs": ["a.txt", "../b.txt"],
+            "options": { "output": "out.json" }
+        }));
+
+        let mut found = path_arguments(&schema, &args);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                ("file".to_string(), "/etc/passwd".to_string()),
+                ("inputs[0]".to_string(), "a.txt".to_string()),
+                ("inputs[1]".to_string(), "../b.txt".to_string()),
+                ("options.output".to_string(), "out.json".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_missing_and_mistyped_values() {
+        let schema = object(json!({
+            "properties": { "file": { "type": "string", "format": "path" } }
+        }));
+        assert!(path_arguments(&schema, &object(json!({}))).is_empty());
+        assert!(path_arguments(&schema, &object(json!({ "file": 3 }))).is_empty());
+        assert!(path_arguments(&object(json!({})), &object(json!({ "file": "/etc" }))).is_empty());
+    }
+}